@@ -0,0 +1,108 @@
+//! Payouts credited to the receivers, who withdraw them with `claim`.
+
+use std::collections::HashMap;
+
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_sdk::Gas;
+
+use crate::types::{convert_old_to_new_token, ONE_YOCTO_NEAR};
+use crate::*;
+
+/// Gas for the callback after a claim, re-crediting failed transfers.
+const GAS_FOR_ON_CLAIM: Gas = Gas(10_000_000_000_000);
+
+#[near_bindgen]
+impl Contract {
+    /// Withdraws everything claimable by the caller in given token. Use "" for $NEAR.
+    pub fn claim(&mut self, token_id: OldAccountId) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let mut claimable = self.claimable.get(&account_id).unwrap_or_default();
+        let amount = claimable.remove(&token_id).expect("ERR_NOTHING_TO_CLAIM");
+        self.internal_set_claimable(&account_id, claimable);
+        let total = self.claimable_totals.get_mut(&token_id).unwrap();
+        *total -= amount;
+        if *total == 0 {
+            self.claimable_totals.remove(&token_id);
+        }
+        let transfer = match convert_old_to_new_token(&token_id) {
+            None => {
+                self.locked_amount -= amount;
+                Promise::new(account_id.clone()).transfer(amount)
+            }
+            Some(ft_token_id) => {
+                self.internal_spend_ft_balance(&ft_token_id, amount);
+                let gas = self.config.get().unwrap().ft_transfer_gas(&ft_token_id);
+                ext_fungible_token::ft_transfer(
+                    account_id.clone(),
+                    U128(amount),
+                    None,
+                    ft_token_id,
+                    ONE_YOCTO_NEAR,
+                    gas,
+                )
+            }
+        };
+        transfer.then(ext_self::on_claim_callback(
+            account_id,
+            token_id,
+            U128(amount),
+            env::current_account_id(),
+            0,
+            GAS_FOR_ON_CLAIM,
+        ))
+    }
+
+    /// Credits the claim back if the transfer has failed, along with the cached balance.
+    #[private]
+    pub fn on_claim_callback(
+        &mut self,
+        account_id: AccountId,
+        token_id: OldAccountId,
+        amount: U128,
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "ERR_UNEXPECTED_CALLBACK_PROMISES"
+        );
+        if let PromiseResult::Failed = env::promise_result(0) {
+            self.internal_credit_claimable(&account_id, &token_id, amount.0);
+            if let Some(token_id) = convert_old_to_new_token(&token_id) {
+                if let Some(balance) = self.ft_balances.get(&token_id) {
+                    self.ft_balances.insert(&token_id, &(balance + amount.0));
+                }
+            }
+        }
+    }
+}
+
+impl Contract {
+    /// Credits `amount` of given token to be claimed by `account_id`.
+    /// Credited $NEAR stays locked until claimed.
+    pub(crate) fn internal_credit_claimable(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &OldAccountId,
+        amount: Balance,
+    ) {
+        let mut claimable = self.claimable.get(account_id).unwrap_or_default();
+        *claimable.entry(token_id.clone()).or_default() += amount;
+        self.claimable.insert(account_id, &claimable);
+        *self.claimable_totals.entry(token_id.clone()).or_default() += amount;
+        if token_id == OLD_BASE_TOKEN {
+            self.locked_amount += amount;
+        }
+    }
+
+    fn internal_set_claimable(
+        &mut self,
+        account_id: &AccountId,
+        claimable: HashMap<OldAccountId, Balance>,
+    ) {
+        if claimable.is_empty() {
+            self.claimable.remove(account_id);
+        } else {
+            self.claimable.insert(account_id, &claimable);
+        }
+    }
+}
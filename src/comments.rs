@@ -0,0 +1,50 @@
+//! Discussion threads recorded on chain for each proposal.
+
+use near_sdk::collections::Vector;
+use near_sdk::BlockHeight;
+
+use crate::*;
+
+/// Maximum length of a comment in bytes.
+pub const MAX_COMMENT_LENGTH: usize = 500;
+
+/// Maximum number of comments on a single proposal.
+pub const MAX_COMMENTS_PER_PROPOSAL: u64 = 100;
+
+/// Comment left by a member on a proposal.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct Comment {
+    pub author: AccountId,
+    pub text: String,
+    pub block_height: BlockHeight,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Adds a comment to given proposal. Only members of any role can comment.
+    pub fn add_comment(&mut self, id: u64, text: String) -> u64 {
+        assert!(self.proposals.contains_key(&id), "ERR_NO_PROPOSAL");
+        let author = env::predecessor_account_id();
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(policy.is_member(&author), "ERR_NOT_MEMBER");
+        assert!(!text.is_empty(), "ERR_EMPTY_COMMENT");
+        assert!(text.len() <= MAX_COMMENT_LENGTH, "ERR_COMMENT_TOO_LONG");
+        let mut comments = self
+            .comments
+            .get(&id)
+            .unwrap_or_else(|| Vector::new(StorageKeys::ProposalComments { proposal_id: id }));
+        assert!(
+            comments.len() < MAX_COMMENTS_PER_PROPOSAL,
+            "ERR_TOO_MANY_COMMENTS"
+        );
+        comments.push(&Comment {
+            author,
+            text,
+            block_height: env::block_height(),
+        });
+        self.comments.insert(&id, &comments);
+        comments.len() - 1
+    }
+}
@@ -0,0 +1,75 @@
+//! Votes hidden until the end of a commit phase, so running tallies can't sway voters.
+//! During the commit phase voters submit a hash of their vote, after it they reveal the vote
+//! and the salt until the deadline. Commits that aren't revealed in time are never counted.
+
+use near_sdk::json_types::Base64VecU8;
+
+use crate::*;
+
+/// Hash committed to by a voter: sha256 of `<action>:<salt>`, e.g. `VoteApprove:secret`.
+pub fn vote_commitment(action: &Action, salt: &str) -> Vec<u8> {
+    env::sha256(format!("{}:{}", action.to_policy_label(), salt).as_bytes())
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Commits to a vote on given commit-reveal proposal, replacing the caller's previous commit.
+    /// `hash` is the `vote_commitment` of the vote and a salt kept secret until the reveal.
+    pub fn commit_vote(&mut self, id: u64, hash: Base64VecU8) {
+        self.assert_not_paused();
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        let commit_end = proposal.commit_end.expect("ERR_NOT_COMMIT_REVEAL");
+        assert!(
+            env::block_timestamp() < commit_end.0,
+            "ERR_COMMIT_PHASE_OVER"
+        );
+        assert!(
+            matches!(proposal.status, ProposalStatus::InProgress),
+            "ERR_PROPOSAL_NOT_READY_FOR_VOTE"
+        );
+        assert_eq!(hash.0.len(), 32, "ERR_INVALID_COMMIT_HASH");
+        assert!(
+            proposal.is_whitelisted_voter(&env::predecessor_account_id()),
+            "ERR_NOT_WHITELISTED"
+        );
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            [Action::VoteApprove, Action::VoteReject, Action::VoteRemove]
+                .iter()
+                .any(|action| policy
+                    .can_execute_action(self.internal_user_info(), &proposal.kind, action)
+                    .1),
+            "ERR_PERMISSION_DENIED"
+        );
+        proposal.commits.insert(env::predecessor_account_id(), hash);
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+    }
+
+    /// Reveals the vote the caller committed to on given proposal and casts it.
+    /// Can only be done after the commit phase and up to the deadline of the proposal.
+    pub fn reveal_vote(&mut self, id: u64, action: Action, salt: String) {
+        assert!(
+            matches!(
+                action,
+                Action::VoteApprove | Action::VoteReject | Action::VoteRemove
+            ),
+            "ERR_WRONG_ACTION"
+        );
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        let commit_end = proposal.commit_end.expect("ERR_NOT_COMMIT_REVEAL");
+        assert!(
+            env::block_timestamp() >= commit_end.0,
+            "ERR_COMMIT_PHASE_NOT_OVER"
+        );
+        let account_id = env::predecessor_account_id();
+        let hash = proposal.commits.remove(&account_id).expect("ERR_NO_COMMIT");
+        assert_eq!(
+            hash.0,
+            vote_commitment(&action, &salt),
+            "ERR_COMMIT_MISMATCH"
+        );
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+        let user = self.internal_user_info();
+        self.internal_act_proposal(id, action, None, None, user, true);
+    }
+}
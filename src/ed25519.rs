@@ -0,0 +1,97 @@
+//! Verification of ed25519 signatures (RFC 8032), used for votes signed off-chain.
+//! The runtime of this SDK version doesn't expose signature verification, so it's done with
+//! `ed25519-dalek`. Verification is strict: non-canonical scalars and small order keys or
+//! commitments are refused, so a signature can't be altered into another valid one.
+//! A verification takes about 8.6 million wasm instructions, see `GAS_PER_SIGNED_VOTE`.
+
+use std::convert::TryFrom;
+
+use ed25519_dalek::{PublicKey, Signature};
+
+/// Verifies ed25519 `signature` of `message` by the owner of `public_key`.
+pub(crate) fn verify(public_key: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+    let public_key = match PublicKey::from_bytes(public_key) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    match Signature::try_from(&signature[..]) {
+        Ok(signature) => public_key.verify_strict(message, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Order of the base point, little endian.
+    const L: [u8; 32] = [
+        0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde,
+        0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+    ];
+
+    fn from_hex<const N: usize>(hex: &str) -> [u8; N] {
+        hex::decode(hex).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_rfc8032_vectors() {
+        let public_key =
+            from_hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        let signature = from_hex("e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b");
+        assert!(verify(&public_key, b"", &signature));
+        let public_key =
+            from_hex("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c");
+        let signature = from_hex("92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00");
+        assert!(verify(&public_key, &[0x72], &signature));
+        // Different message, different key, tampered signature.
+        assert!(!verify(&public_key, &[0x73], &signature));
+        let other_key =
+            from_hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        assert!(!verify(&other_key, &[0x72], &signature));
+        let mut tampered = signature;
+        tampered[40] ^= 1;
+        assert!(!verify(&public_key, &[0x72], &tampered));
+    }
+
+    #[test]
+    fn test_reject_non_canonical_s() {
+        let public_key =
+            from_hex("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c");
+        let mut signature: [u8; 64] = from_hex("92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00");
+        // s + L is equivalent to s, but malleable signatures are refused.
+        let mut carry = 0u16;
+        for i in 0..32 {
+            let sum = signature[32 + i] as u16 + L[i] as u16 + carry;
+            signature[32 + i] = sum as u8;
+            carry = sum >> 8;
+        }
+        assert!(!verify(&public_key, &[0x72], &signature));
+    }
+
+    #[test]
+    fn test_reject_small_order_points() {
+        use ed25519_dalek::Verifier;
+
+        // With the identity as key and commitment and s = 0, [s]B = R + [h]A for any message.
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&identity);
+        // Passes the equation the lax verification checks.
+        assert!(PublicKey::from_bytes(&identity)
+            .unwrap()
+            .verify(
+                b"any message",
+                &Signature::try_from(&signature[..]).unwrap()
+            )
+            .is_ok());
+        assert!(!verify(&identity, b"any message", &signature));
+
+        // Same with y = 2^255 - 19 + 1, the non-canonical encoding of the identity, as key.
+        let mut non_canonical = [0xff; 32];
+        non_canonical[0] = 0xee;
+        non_canonical[31] = 0x7f;
+        assert!(!verify(&non_canonical, b"any message", &signature));
+    }
+}
@@ -0,0 +1,20 @@
+//! Events emitted by the DAO, following the NEP-297 log format.
+
+use near_sdk::env;
+use near_sdk::serde_json::{json, Value};
+
+/// Name of the event standard used by this contract.
+const EVENT_STANDARD: &str = "dao";
+
+/// Logs `EVENT_JSON:` event with given name and data.
+pub(crate) fn emit_event(event: &str, data: Value) {
+    env::log_str(&format!(
+        "EVENT_JSON:{}",
+        json!({
+            "standard": EVENT_STANDARD,
+            "version": env!("CARGO_PKG_VERSION"),
+            "event": event,
+            "data": data,
+        })
+    ));
+}
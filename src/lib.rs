@@ -1,26 +1,68 @@
+use std::collections::HashMap;
+
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap};
-use near_sdk::json_types::{Base58CryptoHash, U128};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedSet, Vector};
+use near_sdk::json_types::{Base58CryptoHash, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, ext_contract, near_bindgen, AccountId, Balance, BorshStorageKey, CryptoHash,
-    PanicOnDefault, Promise, PromiseResult,
+    env, ext_contract, log, near_bindgen, AccountId, Balance, BlockHeight, BorshStorageKey,
+    CryptoHash, PanicOnDefault, Promise, PromiseResult, PublicKey,
 };
 
+pub use crate::commit_reveal::vote_commitment;
+pub use crate::comments::{Comment, MAX_COMMENTS_PER_PROPOSAL, MAX_COMMENT_LENGTH};
+pub use crate::lockup::{lock_multiplier, StakeLockup, MAX_LOCK_DURATION, MAX_LOCK_MULTIPLIER};
+pub use crate::observer::GAS_FOR_OBSERVER;
 pub use crate::policy::{
-    default_policy, Policy, RoleKind, RolePermission, VersionedPolicy, VotePolicy,
+    default_policy, Policy, PolicyHistoryEntry, RoleKind, RolePermission, VersionedPolicy,
+    VotePolicy, WeightKind, WeightOrRatio, ADMIN_ROLE, GUARDIAN_ROLE, MAX_APPROVAL_PROGRESS,
 };
 use crate::proposals::VersionedProposal;
-pub use crate::proposals::{Proposal, ProposalInput, ProposalKind, ProposalStatus};
-pub use crate::types::{Action, Config, OldAccountId, OLD_BASE_TOKEN};
+pub use crate::proposals::{
+    ActionOutcome, PolicyParameters, Proposal, ProposalInput, ProposalKind, ProposalStatus, Vote,
+    VoteWithTimestamp, IDEMPOTENCY_KEY_TTL, MAX_BULK_ACTIONS, MAX_COSPONSORS, MAX_PROPOSAL_TAGS,
+    MAX_REFERENCE_URL_LENGTH, MAX_TAG_LENGTH, MAX_TRANSFER_LEGS, MAX_VOTE_REASON_LENGTH,
+    PROPOSAL_KIND_LABELS, PROPOSAL_KIND_SCHEMA, REFERENCE_HASH_LENGTH,
+};
+pub use crate::signed_votes::{
+    proposal_approval_message, signed_vote_message, SignedApproval, SignedVote,
+    GAS_PER_SIGNED_VOTE, MAX_SIGNED_VOTES,
+};
+pub use crate::staking::{GAS_FOR_STAKING, GAS_FOR_WITHDRAW};
+pub use crate::streams::Stream;
+pub use crate::templates::ProposalTemplate;
+pub use crate::treasury::CachedTotalSupply;
+pub use crate::types::{
+    Action, Config, ConfigHistoryEntry, OldAccountId, RateLimit, SlashingConfig, SuggestionPruning,
+    TieBreak, VoteCooldown, VotingPowerSource, DEFAULT_TOTAL_SUPPLY_MAX_AGE, MAX_CONFIG_HISTORY,
+    OLD_BASE_TOKEN,
+};
 use crate::upgrade::{internal_get_factory_info, internal_set_factory_info, FactoryInfo};
-pub use crate::views::{ProposalOutput};
+pub use crate::views::{
+    CouncilDigest, ProposalOutput, ProposalSort, ReconcileReport, TokenReconciliation,
+    TreasurySummary, EXPECTED_BLOCK_TIME, MAX_SORTED_PROPOSALS,
+};
+pub use crate::voting_power::{LOCK_VOTING_POWER_MSG, MAX_VOTING_BALANCE_HISTORY};
 
+mod claims;
+mod comments;
+mod commit_reveal;
+mod ed25519;
+mod events;
+mod lockup;
+mod migrate;
+mod observer;
 mod policy;
 mod proposals;
+mod signed_votes;
+mod staking;
+mod streams;
+mod templates;
+mod treasury;
 mod types;
 mod upgrade;
 pub mod views;
+mod voting_power;
 
 #[derive(BorshStorageKey, BorshSerialize)]
 pub enum StorageKeys {
@@ -28,6 +70,32 @@ pub enum StorageKeys {
     Policy,
     Proposals,
     Blobs,
+    PolicyHistory,
+    Lockups,
+    Comments,
+    ProposalComments { proposal_id: u64 },
+    FtBalances,
+    RemovedAt,
+    Claimable,
+    ProposalsByProposer,
+    VoteKeys,
+    UsedVoteNonces,
+    RecentSubmissions,
+    MemberSince,
+    IdempotencyKeys,
+    PendingExecutions,
+    Templates,
+    ScheduledConfigs,
+    AwaitingExecution,
+    ProposalsByKind,
+    ConfigHistory,
+    Streams,
+    LastVotes,
+    VotingBalances,
+    FtTokens,
+    MemberTerms,
+    PrunedApproved,
+    ProposalsByTag,
 }
 
 /// After payouts, allows a callback
@@ -35,6 +103,21 @@ pub enum StorageKeys {
 pub trait ExtSelf {
     /// Callback after proposal execution.
     fn on_proposal_callback(&mut self, proposal_id: u64) -> PromiseOrValue<()>;
+    /// Callback after fetching the balance of the DAO in given token.
+    fn on_ft_balance(&mut self, token_id: AccountId) -> U128;
+    /// Callback after fetching the total supply of the governance token.
+    fn on_total_supply(&mut self) -> U128;
+    /// Callback after returning locked voting power, locking it again on failure.
+    fn on_unlock_voting_balance(
+        &mut self,
+        token_id: AccountId,
+        account_id: AccountId,
+        amount: U128,
+    );
+    /// Callback after transferring a claim, re-crediting it on failure.
+    fn on_claim_callback(&mut self, account_id: AccountId, token_id: OldAccountId, amount: U128);
+    /// Callback after a stream withdrawal, re-crediting it on failure.
+    fn on_stream_withdraw_callback(&mut self, id: u64, amount: U128);
 }
 
 #[near_bindgen]
@@ -48,18 +131,84 @@ pub struct Contract {
     pub last_proposal_id: u64,
     /// Proposal map from ID to proposal information.
     pub proposals: LookupMap<u64, VersionedProposal>,
+    /// Amount of $NEAR locked for bonds.
+    pub locked_amount: Balance,
+    /// Previous policies, oldest first. Bounded to `MAX_POLICY_HISTORY` entries.
+    pub policy_history: LazyOption<Vec<PolicyHistoryEntry>>,
+    /// If set, proposals can't be added, acted on or executed.
+    pub paused: bool,
+    /// Large blobs of code, with the account that paid for their storage.
+    pub blobs: LookupMap<CryptoHash, AccountId>,
+    /// Number of stored proposals in each status.
+    pub proposal_status_counts: HashMap<ProposalStatus, u64>,
+    /// Stake locked by each account, used for duration weighted voting.
+    pub lockups: LookupMap<AccountId, StakeLockup>,
+    /// Comments on each proposal, oldest first.
+    pub comments: LookupMap<u64, Vector<Comment>>,
+    /// Last fetched balance of the DAO in each fungible token.
+    pub ft_balances: LookupMap<AccountId, Balance>,
+    /// Block at which each proposal was voted to be removed, until it's restored or deleted.
+    pub removed_at: LookupMap<u64, BlockHeight>,
+    /// Payouts waiting to be claimed by each account, per token.
+    pub claimable: LookupMap<AccountId, HashMap<OldAccountId, Balance>>,
+    /// Ids of the stored proposals of each proposer, oldest first.
+    pub proposals_by_proposer: LookupMap<AccountId, Vec<u64>>,
+    /// Keys that accounts sign off-chain votes with.
+    pub vote_keys: LookupMap<AccountId, PublicKey>,
+    /// Nonces of signed votes that were already applied, per voter.
+    pub used_vote_nonces: LookupSet<(AccountId, u64)>,
+    /// Total stake locked by all accounts, part of `locked_amount`.
+    pub locked_stake: Balance,
+    /// Total payouts waiting to be claimed, per token. The $NEAR part is in `locked_amount`.
+    pub claimable_totals: HashMap<OldAccountId, Balance>,
+    /// Tokens being sent by approved transfers, until their callback.
+    pub pending_transfers: HashMap<AccountId, Balance>,
+    /// Tokens whose balance is cached: the ones paid by a proposal.
+    pub ft_tokens: UnorderedSet<AccountId>,
+    /// Blocks at which each account added proposals within the current rate limit window.
+    pub recent_submissions: LookupMap<AccountId, Vec<BlockHeight>>,
+    /// Block at which each account was added to each role by an `AddMemberToRole` proposal.
+    pub member_since: LookupMap<(String, AccountId), BlockHeight>,
+    /// Recent idempotency keys of each proposer, with the proposal id and the block they were used at.
+    pub idempotency_keys: LookupMap<AccountId, Vec<(String, u64, BlockHeight)>>,
+    /// Last fetched total supply of the governance token.
+    pub total_supply: Option<CachedTotalSupply>,
+    /// Proposals whose execution is waiting for its callback.
+    pub pending_executions: LookupSet<u64>,
+    /// Proposal templates by name.
+    pub templates: LookupMap<String, ProposalTemplate>,
+    /// Approved `ChangeConfig` proposals waiting for their effective block.
+    pub scheduled_configs: LookupSet<u64>,
+    /// Approved proposals waiting for `execute`, when `Config::auto_execute` is not set.
+    pub awaiting_execution: LookupSet<u64>,
+    /// Ids of the stored proposals of each kind, by policy label, oldest first.
+    pub proposals_by_kind: LookupMap<String, Vec<u64>>,
+    /// Config changes, oldest first. Bounded to `MAX_CONFIG_HISTORY` entries.
+    pub config_history: LazyOption<Vec<ConfigHistoryEntry>>,
+    /// $NEAR delegated to each staking pool by executed staking proposals.
+    pub delegations: HashMap<AccountId, Balance>,
+    /// Streams created by approved `StreamCreate` proposals, by proposal id.
+    pub streams: LookupMap<u64, Stream>,
+    /// Block at which the membership of each account in each role ends, for members added with a term.
+    pub member_terms: LookupMap<(String, AccountId), BlockHeight>,
+    /// Block of the last vote of each account, tracked while a vote cooldown is set.
+    pub last_votes: LookupMap<AccountId, BlockHeight>,
+    /// Changes of the balance of each token locked by each account as voting power, oldest first,
+    /// with the block of each change. Bounded to `MAX_VOTING_BALANCE_HISTORY` entries.
+    pub voting_balances: LookupMap<(AccountId, AccountId), Vec<(BlockHeight, Balance)>>,
+    /// Ids of the approved proposals deleted by `prune_proposals`, so proposals depending on
+    /// them can still be executed.
+    pub pruned_approved: LookupSet<u64>,
+    /// Ids of the stored proposals with each tag, oldest first.
+    pub proposals_by_tag: LookupMap<String, Vec<u64>>,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
     pub fn new(config: Config, policy: VersionedPolicy) -> Self {
-        let this = Self {
-            config: LazyOption::new(StorageKeys::Config, Some(&config)),
-            policy: LazyOption::new(StorageKeys::Policy, Some(&policy.upgrade())),
-            last_proposal_id: 0,
-            proposals: LookupMap::new(StorageKeys::Proposals),
-        };
+        config.assert_valid();
+        let this = Self::internal_new(&config, policy, 0);
         internal_set_factory_info(&FactoryInfo {
             factory_id: env::predecessor_account_id(),
             auto_update: true,
@@ -71,10 +220,155 @@ impl Contract {
     pub fn get_factory_info(&self) -> FactoryInfo {
         internal_get_factory_info()
     }
+
+    /// Pauses or resumes the contract. Only members of the guardian role can call this.
+    pub fn set_paused(&mut self, paused: bool) {
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy.is_role_member(GUARDIAN_ROLE, &env::predecessor_account_id()),
+            "ERR_NOT_GUARDIAN"
+        );
+        self.paused = paused;
+        log!("Contract paused: {}", paused);
+    }
+
+    /// Removes blob from the storage and returns the storage deposit to the account that stored it.
+    pub fn remove_blob(&mut self, hash: Base58CryptoHash) -> Promise {
+        let hash: CryptoHash = hash.into();
+        let account_id = self.blobs.remove(&hash).expect("ERR_NO_BLOB");
+        assert_eq!(
+            env::predecessor_account_id(),
+            account_id,
+            "ERR_INVALID_CALLER"
+        );
+        let blob = env::storage_read(&hash).expect("ERR_NO_BLOB");
+        env::storage_remove(&hash);
+        Promise::new(account_id).transfer(blob_storage_cost(blob.len()))
+    }
+}
+
+impl Contract {
+    /// Contract with given config and policy and empty state otherwise.
+    /// Collections are created with their storage keys, so existing entries are kept.
+    pub(crate) fn internal_new(
+        config: &Config,
+        policy: VersionedPolicy,
+        last_proposal_id: u64,
+    ) -> Self {
+        Self {
+            config: LazyOption::new(StorageKeys::Config, Some(config)),
+            policy: LazyOption::new(StorageKeys::Policy, Some(&policy.upgrade())),
+            last_proposal_id,
+            proposals: LookupMap::new(StorageKeys::Proposals),
+            locked_amount: 0,
+            policy_history: LazyOption::new(StorageKeys::PolicyHistory, None),
+            paused: false,
+            blobs: LookupMap::new(StorageKeys::Blobs),
+            proposal_status_counts: HashMap::new(),
+            lockups: LookupMap::new(StorageKeys::Lockups),
+            comments: LookupMap::new(StorageKeys::Comments),
+            ft_balances: LookupMap::new(StorageKeys::FtBalances),
+            removed_at: LookupMap::new(StorageKeys::RemovedAt),
+            claimable: LookupMap::new(StorageKeys::Claimable),
+            proposals_by_proposer: LookupMap::new(StorageKeys::ProposalsByProposer),
+            vote_keys: LookupMap::new(StorageKeys::VoteKeys),
+            used_vote_nonces: LookupSet::new(StorageKeys::UsedVoteNonces),
+            locked_stake: 0,
+            claimable_totals: HashMap::new(),
+            pending_transfers: HashMap::new(),
+            ft_tokens: UnorderedSet::new(StorageKeys::FtTokens),
+            recent_submissions: LookupMap::new(StorageKeys::RecentSubmissions),
+            member_since: LookupMap::new(StorageKeys::MemberSince),
+            idempotency_keys: LookupMap::new(StorageKeys::IdempotencyKeys),
+            total_supply: None,
+            pending_executions: LookupSet::new(StorageKeys::PendingExecutions),
+            templates: LookupMap::new(StorageKeys::Templates),
+            scheduled_configs: LookupSet::new(StorageKeys::ScheduledConfigs),
+            awaiting_execution: LookupSet::new(StorageKeys::AwaitingExecution),
+            proposals_by_kind: LookupMap::new(StorageKeys::ProposalsByKind),
+            config_history: LazyOption::new(StorageKeys::ConfigHistory, None),
+            delegations: HashMap::new(),
+            streams: LookupMap::new(StorageKeys::Streams),
+            member_terms: LookupMap::new(StorageKeys::MemberTerms),
+            last_votes: LookupMap::new(StorageKeys::LastVotes),
+            voting_balances: LookupMap::new(StorageKeys::VotingBalances),
+            pruned_approved: LookupSet::new(StorageKeys::PrunedApproved),
+            proposals_by_tag: LookupMap::new(StorageKeys::ProposalsByTag),
+        }
+    }
+
+    /// Panics if the contract is paused.
+    pub(crate) fn assert_not_paused(&self) {
+        assert!(!self.paused, "ERR_CONTRACT_PAUSED");
+    }
+
+    /// Moves a proposal between status counters.
+    /// `None` means the proposal is added to or deleted from the storage.
+    pub(crate) fn internal_update_status_counts(
+        &mut self,
+        from: Option<&ProposalStatus>,
+        to: Option<&ProposalStatus>,
+    ) {
+        if from == to {
+            return;
+        }
+        if let Some(from) = from {
+            let count = self.proposal_status_counts.entry(from.clone()).or_default();
+            *count = count.saturating_sub(1);
+        }
+        if let Some(to) = to {
+            *self.proposal_status_counts.entry(to.clone()).or_default() += 1;
+        }
+    }
+
+    /// Stores given blob paid by the attached deposit, refunding the excess.
+    pub(crate) fn internal_store_blob(&mut self, blob: Vec<u8>) -> Base58CryptoHash {
+        let sha256_hash = env::sha256(&blob);
+        assert!(!env::storage_has_key(&sha256_hash), "ERR_ALREADY_EXISTS");
+        let storage_cost = blob_storage_cost(blob.len());
+        assert!(
+            env::attached_deposit() >= storage_cost,
+            "ERR_NOT_ENOUGH_DEPOSIT:{}",
+            storage_cost
+        );
+        env::storage_write(&sha256_hash, &blob);
+        let mut blob_hash = [0u8; 32];
+        blob_hash.copy_from_slice(&sha256_hash);
+        self.blobs
+            .insert(&blob_hash, &env::predecessor_account_id());
+        let refund = env::attached_deposit() - storage_cost;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        Base58CryptoHash::from(blob_hash)
+    }
+}
+
+/// Storage cost of a blob of given length, including its hash key.
+fn blob_storage_cost(blob_len: usize) -> Balance {
+    ((blob_len + 32) as u128) * env::storage_byte_cost()
+}
+
+/// Stores attached data as a blob keyed by its sha256 hash and returns the hash.
+/// Attached deposit must cover the storage, any excess is refunded.
+/// Takes raw input to avoid encoding the code as JSON.
+#[no_mangle]
+pub extern "C" fn store_blob() {
+    env::setup_panic_hook();
+    let mut contract: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
+    let blob_hash = contract.internal_store_blob(env::input().expect("ERR_NO_INPUT"));
+    let blob_hash_str = near_sdk::serde_json::to_string(&blob_hash)
+        .unwrap()
+        .into_bytes();
+    env::value_return(&blob_hash_str);
+    env::state_write(&contract);
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
     use near_sdk::test_utils::{accounts, VMContextBuilder};
     use near_sdk::testing_env;
     use near_sdk_sim::to_yocto;
@@ -82,6 +376,9 @@ mod tests {
     //use crate::proposals::ProposalStatus;
 
     use super::*;
+    use crate::migrate::{OldConfig, OldContract, OldProposalKind, OldVersionedPolicy};
+    use crate::proposals::{ProposalV1, VoteWithTimestampV1};
+    use crate::types::default_callback_gas;
 
     fn create_proposal(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
         testing_env!(context.attached_deposit(to_yocto("1")).build());
@@ -92,9 +389,6917 @@ mod tests {
                 receiver_id: accounts(2).into(),
                 amount: U128(to_yocto("100")),
                 msg: None,
+                register_receiver: false,
+            },
+            ..Default::default()
+        })
+    }
+
+    fn create_tagged_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        tags: Vec<&str>,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "tagged".to_string(),
+            kind: ProposalKind::Vote,
+            tags: tags.into_iter().map(|tag| tag.to_string()).collect(),
+            category: Some("grants".to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn setup_contract(context: &mut VMContextBuilder) -> Contract {
+        setup_contract_with_council(context, vec![accounts(1)])
+    }
+
+    fn setup_contract_with_council(
+        context: &mut VMContextBuilder,
+        council: Vec<AccountId>,
+    ) -> Contract {
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .account_balance(to_yocto("1000"))
+            .build());
+        Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default {
+                council,
+                stakers: vec![accounts(1), accounts(2)],
+            },
+        )
+    }
+
+    fn set_default_vote_policy(contract: &mut Contract, vote_policy: VotePolicy) {
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        policy.update_default_vote_policy(&vote_policy);
+        contract.policy.set(&VersionedPolicy::Current(policy));
+    }
+
+    fn vote(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        voter: AccountId,
+        id: u64,
+        action: Action,
+    ) {
+        testing_env!(context.predecessor_account_id(voter).build());
+        contract.act_proposal(id, action, None, None);
+    }
+
+    fn add_council_permission(contract: &mut Contract, permission: &str) {
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        let mut council = policy
+            .roles
+            .iter()
+            .find(|role| role.name == "council")
+            .unwrap()
+            .clone();
+        council.permissions.insert(permission.to_string());
+        policy.add_or_update_role(&council);
+        contract.policy.set(&VersionedPolicy::Current(policy));
+    }
+
+    #[test]
+    fn test_proposal_tags() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id0 = create_tagged_proposal(&mut context, &mut contract, vec!["grants", "solar"]);
+        let id1 = create_proposal(&mut context, &mut contract);
+        let id2 = create_tagged_proposal(&mut context, &mut contract, vec!["solar"]);
+
+        let proposal = contract.get_proposal(id0).proposal;
+        assert_eq!(proposal.tags, vec!["grants".to_string(), "solar".to_string()]);
+        assert_eq!(proposal.category, Some("grants".to_string()));
+        assert!(contract.get_proposal(id1).proposal.tags.is_empty());
+
+        let solar: Vec<u64> = contract
+            .get_proposals_by_tag("solar".to_string(), 0, 10)
+            .into_iter()
+            .map(|output| output.id)
+            .collect();
+        assert_eq!(solar, vec![id0, id2]);
+        let limited = contract.get_proposals_by_tag("solar".to_string(), 0, 1);
+        assert_eq!(limited.len(), 1);
+        let next = contract.get_proposals_by_tag("solar".to_string(), 1, 10);
+        assert_eq!(next[0].id, id2);
+        assert_eq!(next.len(), 1);
+        assert!(contract
+            .get_proposals_by_tag("wind".to_string(), 0, 10)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOO_MANY_TAGS")]
+    fn test_proposal_too_many_tags() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        create_tagged_proposal(
+            &mut context,
+            &mut contract,
+            vec!["a", "b", "c", "d", "e", "f"],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TAG_LENGTH")]
+    fn test_proposal_tag_too_long() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let tag = "x".repeat(MAX_TAG_LENGTH + 1);
+        create_tagged_proposal(&mut context, &mut contract, vec![tag.as_str()]);
+    }
+
+    fn create_referenced_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        reference_hash: Vec<u8>,
+        reference_url: String,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "referenced".to_string(),
+            kind: ProposalKind::Vote,
+            reference_hash: Some(reference_hash.into()),
+            reference_url: Some(reference_url),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_proposal_reference() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let url = "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+        let hash = env::sha256(b"proposal document");
+        let id =
+            create_referenced_proposal(&mut context, &mut contract, hash.clone(), url.to_string());
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.reference_hash.unwrap().0, hash);
+        assert_eq!(proposal.reference_url.unwrap(), url);
+        // Both are optional.
+        let id = create_proposal(&mut context, &mut contract);
+        let proposal = contract.get_proposal(id).proposal;
+        assert!(proposal.reference_hash.is_none() && proposal.reference_url.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_REFERENCE_URL_LENGTH")]
+    fn test_proposal_reference_url_too_long() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let url = "x".repeat(MAX_REFERENCE_URL_LENGTH + 1);
+        create_referenced_proposal(&mut context, &mut contract, env::sha256(b"doc"), url);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_REFERENCE_HASH")]
+    fn test_proposal_reference_hash_length() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let hash = vec![0; REFERENCE_HASH_LENGTH - 1];
+        create_referenced_proposal(&mut context, &mut contract, hash, "ipfs://doc".to_string());
+    }
+
+    fn create_keyed_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        key: &str,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "keyed".to_string(),
+            kind: ProposalKind::Vote,
+            idempotency_key: Some(key.to_string()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_idempotency_key() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3)]);
+        let id = create_keyed_proposal(&mut context, &mut contract, "retry-1");
+        let locked = contract.locked_amount;
+        assert_eq!(
+            create_keyed_proposal(&mut context, &mut contract, "retry-1"),
+            id
+        );
+        assert_eq!(contract.get_last_proposal_id(), id + 1);
+        // The second bond is sent back.
+        assert_eq!(contract.locked_amount, locked);
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == accounts(1)));
+
+        let other = create_keyed_proposal(&mut context, &mut contract, "retry-2");
+        assert_ne!(other, id);
+        // Another proposer can use the same key.
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert_ne!(
+            create_keyed_proposal(&mut context, &mut contract, "retry-1"),
+            id
+        );
+    }
+
+    #[test]
+    fn test_idempotency_key_expired() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_keyed_proposal(&mut context, &mut contract, "retry-1");
+        testing_env!(context.block_index(IDEMPOTENCY_KEY_TTL).build());
+        assert_eq!(
+            create_keyed_proposal(&mut context, &mut contract, "retry-1"),
+            id + 1
+        );
+    }
+
+    #[test]
+    fn test_extend_voting_period() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_permission(&mut contract, "*:ExtendVotingPeriod");
+        let id = create_proposal(&mut context, &mut contract);
+        let period = contract.get_policy().proposal_period.0;
+        contract.extend_voting_period(id, U64(1_000));
+        let proposal = contract.get_proposal(id).proposal;
+        assert!(proposal.extended);
+        assert_eq!(
+            contract.get_policy().proposal_deadline(&proposal),
+            period + 1_000
+        );
+
+        // Past the original deadline but within the extension the proposal is still open.
+        testing_env!(context.block_timestamp(period + 500).build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_EXTENDED")]
+    fn test_extend_voting_period_twice() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_permission(&mut contract, "*:ExtendVotingPeriod");
+        let id = create_proposal(&mut context, &mut contract);
+        contract.extend_voting_period(id, U64(1_000));
+        contract.extend_voting_period(id, U64(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EXTENSION_TOO_LONG")]
+    fn test_extend_voting_period_too_long() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_permission(&mut contract, "*:ExtendVotingPeriod");
+        let id = create_proposal(&mut context, &mut contract);
+        let period = contract.get_policy().proposal_period.0;
+        contract.extend_voting_period(id, U64(period + 1));
+    }
+
+    #[test]
+    fn test_proposal_deadline_saturates() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        let mut proposal = contract.get_proposal(id).proposal;
+        proposal.period_extension = U64(u64::MAX);
+        assert_eq!(contract.get_policy().proposal_deadline(&proposal), u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_EXPIRED")]
+    fn test_extend_expired_proposal() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_permission(&mut contract, "*:ExtendVotingPeriod");
+        let id = create_proposal(&mut context, &mut contract);
+        let period = contract.get_policy().proposal_period.0;
+        testing_env!(context.block_timestamp(period + 1).build());
+        contract.extend_voting_period(id, U64(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PERMISSION_DENIED")]
+    fn test_extend_voting_period_no_permission() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        contract.extend_voting_period(id, U64(1_000));
+    }
+
+    #[test]
+    fn test_proposal_period_per_kind() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(1),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
             },
+        );
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        let period = policy.proposal_period.0;
+        policy
+            .proposal_periods
+            .insert("vote".to_string(), U64(period / 7));
+        contract.policy.set(&VersionedPolicy::Current(policy));
+        let transfer_id = create_proposal(&mut context, &mut contract);
+        let vote_id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+
+        // The vote proposal expires with its own period, the transfer keeps the global one.
+        testing_env!(context.block_timestamp(period / 7 + 1).build());
+        assert!(contract.can_finalize(vote_id));
+        assert!(!contract.can_finalize(transfer_id));
+        contract.act_proposal(vote_id, Action::Finalize, None, None);
+        assert_eq!(
+            contract.get_proposal(vote_id).proposal.status,
+            ProposalStatus::Expired
+        );
+
+        testing_env!(context.block_timestamp(period + 1).build());
+        contract.act_proposal(transfer_id, Action::Finalize, None, None);
+        assert_eq!(
+            contract.get_proposal(transfer_id).proposal.status,
+            ProposalStatus::Expired
+        );
+    }
+
+    #[test]
+    fn test_get_proposal_deadline() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_permission(&mut contract, "*:ExtendVotingPeriod");
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        let period = policy.proposal_period.0;
+        policy
+            .proposal_periods
+            .insert("vote".to_string(), U64(period / 7));
+        contract.policy.set(&VersionedPolicy::Current(policy));
+        testing_env!(context.block_timestamp(1_000).build());
+        let transfer_id = create_proposal(&mut context, &mut contract);
+        let vote_id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        assert_eq!(
+            contract.get_proposal_deadline(transfer_id).0,
+            1_000 + period
+        );
+        assert_eq!(
+            contract.get_proposal_deadline(vote_id).0,
+            1_000 + period / 7
+        );
+
+        contract.extend_voting_period(vote_id, U64(500));
+        assert_eq!(
+            contract.get_proposal_deadline(vote_id).0,
+            1_000 + period / 7 + 500
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_PROPOSAL")]
+    fn test_get_proposal_deadline_missing() {
+        let mut context = VMContextBuilder::new();
+        let contract = setup_contract(&mut context);
+        contract.get_proposal_deadline(0);
+    }
+
+    fn create_commit_reveal_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        commit_end: u64,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "tariff".to_string(),
+            kind: ProposalKind::Vote,
+            commit_end: Some(U64(commit_end)),
+            ..Default::default()
         })
     }
+
+    fn commit_vote(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        voter: AccountId,
+        id: u64,
+        action: Action,
+        salt: &str,
+    ) {
+        testing_env!(context.predecessor_account_id(voter).build());
+        contract.commit_vote(id, vote_commitment(&action, salt).into());
+    }
+
+    #[test]
+    fn test_commit_reveal_vote() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_commit_reveal_proposal(&mut context, &mut contract, 1_000);
+        commit_vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+            "salt",
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        assert!(proposal.vote_counts.is_empty());
+        assert!(proposal.commits.contains_key(&accounts(1)));
+
+        testing_env!(context.block_timestamp(1_000).build());
+        contract.reveal_vote(id, Action::VoteApprove, "salt".to_string());
+        let proposal = contract.get_proposal(id).proposal;
+        assert!(proposal.commits.is_empty());
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_COMMIT_MISMATCH")]
+    fn test_commit_reveal_mismatch() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_commit_reveal_proposal(&mut context, &mut contract, 1_000);
+        commit_vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteReject,
+            "salt",
+        );
+        testing_env!(context.block_timestamp(1_000).build());
+        contract.reveal_vote(id, Action::VoteApprove, "salt".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_COMMIT_REVEAL_VOTE")]
+    fn test_commit_reveal_direct_vote() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_commit_reveal_proposal(&mut context, &mut contract, 1_000);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+    }
+
+    #[test]
+    fn test_commit_reveal_unrevealed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_commit_reveal_proposal(&mut context, &mut contract, 1_000);
+        commit_vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+            "salt",
+        );
+        let deadline = contract.get_proposal_deadline(id).0;
+        testing_env!(context.block_timestamp(deadline + 1).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Expired);
+        assert!(proposal.vote_counts.is_empty());
+    }
+
+    /// Finalizes a proposal of `accounts(1)` tied by the given votes of `accounts(1)` and
+    /// `accounts(3)` under given rule, returning its status.
+    fn finalize_tied_proposal(tie_break: TieBreak, votes: [Action; 2]) -> ProposalStatus {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let mut config = contract.get_config();
+        config.tie_break = tie_break;
+        contract.config.set(&config);
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        for (voter, action) in [accounts(1), accounts(3)].into_iter().zip(votes) {
+            if contract.get_proposal(id).proposal.status != ProposalStatus::InProgress {
+                break;
+            }
+            vote(&mut context, &mut contract, voter, id, action);
+        }
+        if contract.get_proposal(id).proposal.status != ProposalStatus::InProgress {
+            // Rejected right away once a tie can't be decided in favor anymore.
+            return contract.get_proposal(id).proposal.status;
+        }
+        let deadline = contract.get_proposal_deadline(id).0;
+        testing_env!(context.block_timestamp(deadline + 1).build());
+        assert!(contract.can_finalize(id));
+        contract.act_proposal(id, Action::Finalize, None, None);
+        contract.get_proposal(id).proposal.status
+    }
+
+    #[test]
+    fn test_tie_break() {
+        assert_eq!(
+            finalize_tied_proposal(
+                TieBreak::RejectOnTie,
+                [Action::VoteApprove, Action::VoteReject]
+            ),
+            ProposalStatus::Rejected
+        );
+        assert_eq!(
+            finalize_tied_proposal(
+                TieBreak::ApproveOnTie,
+                [Action::VoteApprove, Action::VoteReject]
+            ),
+            ProposalStatus::Approved
+        );
+        assert_eq!(
+            finalize_tied_proposal(
+                TieBreak::ProposerWins,
+                [Action::VoteApprove, Action::VoteReject]
+            ),
+            ProposalStatus::Approved
+        );
+        assert_eq!(
+            finalize_tied_proposal(
+                TieBreak::ProposerWins,
+                [Action::VoteReject, Action::VoteApprove]
+            ),
+            ProposalStatus::Rejected
+        );
+    }
+
+    #[test]
+    fn test_vote_at_deadline_executes() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        let deadline = contract
+            .get_policy()
+            .proposal_deadline(&contract.get_proposal(id).proposal);
+        testing_env!(context.block_timestamp(deadline).build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert!(!near_sdk::test_utils::get_created_receipts().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_EXPIRED")]
+    fn test_vote_after_deadline() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        let deadline = contract
+            .get_policy()
+            .proposal_deadline(&contract.get_proposal(id).proposal);
+        testing_env!(context.block_timestamp(deadline + 1).build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+    }
+
+    #[test]
+    fn test_spam_threshold() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(1),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(3),
+                max_weight_bps: None,
+            },
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        assert_eq!(contract.locked_amount, to_yocto("1"));
+
+        // Two remove votes pass the regular threshold but not the spam one.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteRemove,
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteRemove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteRemove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Removed
+        );
+        // Bond stays locked while the proposal can be restored and no refund is scheduled.
+        assert_eq!(contract.locked_amount, to_yocto("1"));
+        assert!(near_sdk::test_utils::get_created_receipts().is_empty());
+    }
+
+    fn create_period_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        period: u64,
+    ) -> u64 {
+        create_kind_proposal(
+            context,
+            contract,
+            ProposalKind::ChangePolicyUpdateParameters {
+                parameters: PolicyParameters {
+                    proposal_period: Some(U64(period)),
+                    proposal_periods: None,
+                },
+            },
+        )
+    }
+
+    #[test]
+    fn test_min_voting_period() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.min_voting_period = U64(1_000);
+        contract.config.set(&config);
+        let id = create_period_proposal(&mut context, &mut contract, 1_000);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(contract.get_policy().proposal_period, U64(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VOTING_PERIOD_TOO_SHORT")]
+    fn test_min_voting_period_too_short() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.min_voting_period = U64(1_000);
+        contract.config.set(&config);
+        create_period_proposal(&mut context, &mut contract, 999);
+    }
+
+    #[test]
+    fn test_get_policy_parameters() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let parameters = contract.get_policy_parameters();
+        assert_eq!(
+            parameters.proposal_period,
+            Some(contract.get_policy().proposal_period)
+        );
+        assert_eq!(parameters.proposal_periods, Some(HashMap::default()));
+
+        let id = create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::ChangePolicyUpdateParameters {
+                parameters: PolicyParameters {
+                    proposal_period: Some(U64(1_000)),
+                    proposal_periods: Some(
+                        vec![("vote".to_string(), U64(500))].into_iter().collect(),
+                    ),
+                },
+            },
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        let parameters = contract.get_policy_parameters();
+        assert_eq!(parameters.proposal_period, Some(U64(1_000)));
+        assert_eq!(
+            parameters.proposal_periods.unwrap().get("vote"),
+            Some(&U64(500))
+        );
+    }
+
+    #[test]
+    fn test_get_default_vote_policy() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        assert_eq!(contract.get_default_vote_policy(), VotePolicy::default());
+        let vote_policy = VotePolicy {
+            weight_kind: WeightKind::RoleWeight,
+            quorum: U128(2),
+            threshold: WeightOrRatio::Ratio(2, 3),
+            spam_threshold: U128(0),
+            max_weight_bps: None,
+        };
+        let id = create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::ChangePolicyUpdateDefaultVotePolicy {
+                vote_policy: vote_policy.clone(),
+            },
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(contract.get_default_vote_policy(), vote_policy);
+    }
+
+    #[test]
+    fn test_policy_history() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let original_policy = contract.get_policy();
+        for period in [100, 200] {
+            testing_env!(context.attached_deposit(to_yocto("1")).build());
+            let id = contract.add_proposal(ProposalInput {
+                description: "change period".to_string(),
+                kind: ProposalKind::ChangePolicyUpdateParameters {
+                    parameters: PolicyParameters {
+                        proposal_period: Some(U64(period)),
+                        proposal_periods: None,
+                    },
+                },
+                ..Default::default()
+            });
+            contract.act_proposal(id, Action::VoteApprove, None, None);
+        }
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.starts_with("EVENT_JSON:") && log.contains("policy_change")));
+
+        let history = contract.get_policy_history(10);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].proposal_id, 1);
+        assert_eq!(
+            history[0].policy.clone().to_policy().proposal_period,
+            U64(100)
+        );
+        assert_eq!(history[1].proposal_id, 0);
+        assert_eq!(history[1].policy.clone().to_policy(), original_policy);
+        assert_eq!(contract.get_policy_history(1).len(), 1);
+    }
+
+    #[test]
+    fn test_get_role() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        assert_eq!(contract.get_roles().len(), 2);
+        assert!(contract.get_role("auditors".to_string()).is_none());
+        let role = RolePermission {
+            name: "auditors".to_string(),
+            kind: RoleKind::Group(vec![accounts(3), accounts(4)].into_iter().collect()),
+            permissions: vec!["transfer:VoteReject".to_string()]
+                .into_iter()
+                .collect(),
+            vote_policy: vec![("transfer".to_string(), VotePolicy::default())]
+                .into_iter()
+                .collect(),
+        };
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let id = contract.add_proposal(ProposalInput {
+            description: "add auditors".to_string(),
+            kind: ProposalKind::ChangePolicyAddOrUpdateRole { role: role.clone() },
+            ..Default::default()
+        });
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+
+        assert_eq!(
+            contract.get_role("auditors".to_string()),
+            Some(role.clone())
+        );
+        let roles = contract.get_roles();
+        assert_eq!(roles.len(), 3);
+        assert!(roles.contains(&role));
+        let json =
+            near_sdk::serde_json::to_value(contract.get_role("auditors".to_string())).unwrap();
+        let mut members: Vec<String> = json["kind"]["Group"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|member| member.as_str().unwrap().to_string())
+            .collect();
+        members.sort();
+        assert_eq!(
+            members,
+            vec![accounts(3).to_string(), accounts(4).to_string()]
+        );
+        assert_eq!(
+            json["permissions"],
+            near_sdk::serde_json::json!(["transfer:VoteReject"])
+        );
+    }
+
+    /// Adds `member` to the council by proposal, with a voting delay of 100 blocks, and
+    /// requires three votes from then on.
+    fn add_council_member_with_delay(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        member: AccountId,
+    ) {
+        let mut config = contract.get_config();
+        config.member_voting_delay_blocks = U64(100);
+        contract.config.set(&config);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let id = contract.add_proposal(ProposalInput {
+            description: "add member".to_string(),
+            kind: ProposalKind::AddMemberToRole {
+                member_id: member,
+                role: "council".to_string(),
+                term_end_block: None,
+            },
+            ..Default::default()
+        });
+        vote(context, contract, accounts(1), id, Action::VoteApprove);
+        set_default_vote_policy(
+            contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(2),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+    }
+
+    /// Adds `member` to the council by proposal, until block 100.
+    fn add_council_member_with_term(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        member: AccountId,
+    ) {
+        let id = create_kind_proposal(
+            context,
+            contract,
+            ProposalKind::AddMemberToRole {
+                member_id: member,
+                role: "council".to_string(),
+                term_end_block: Some(100),
+            },
+        );
+        vote(context, contract, accounts(1), id, Action::VoteApprove);
+        set_default_vote_policy(
+            contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_member_term() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_member_with_term(&mut context, &mut contract, accounts(3));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_index(99)
+            .build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert!(contract.has_voted(id, accounts(3)));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_index(100)
+            .build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.act_proposal(id, Action::VoteApprove, None, None)
+        }));
+        assert!(result.is_err());
+        assert!(!contract.has_voted(id, accounts(3)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PERMISSION_DENIED")]
+    fn test_member_term_ended() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_member_with_term(&mut context, &mut contract, accounts(3));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.block_index(150).build());
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+    }
+
+    #[test]
+    fn test_prune_expired_members() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_member_with_term(&mut context, &mut contract, accounts(3));
+        assert!(contract
+            .get_policy()
+            .is_role_member("council", &accounts(3)));
+        assert_eq!(contract.prune_expired_members(), 0);
+
+        testing_env!(context.block_index(100).build());
+        assert_eq!(contract.prune_expired_members(), 1);
+        let policy = contract.get_policy();
+        assert!(!policy.is_role_member("council", &accounts(3)));
+        assert!(policy.is_role_member("council", &accounts(1)));
+        assert!(!contract
+            .member_terms
+            .contains_key(&("council".to_string(), accounts(3))));
+    }
+
+    #[test]
+    fn test_member_voting_delay() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_member_with_delay(&mut context, &mut contract, accounts(3));
+        let id = create_proposal(&mut context, &mut contract);
+        // Established members vote right away.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.act_proposal(id, Action::VoteApprove, None, None)
+        }));
+        assert!(result.is_err());
+
+        testing_env!(context.block_index(100).build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(contract.get_proposal(id).proposal.votes.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MEMBERSHIP_TOO_RECENT")]
+    fn test_member_voting_delay_not_over() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_member_with_delay(&mut context, &mut contract, accounts(3));
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.block_index(99).build());
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+    }
+
+    #[test]
+    fn test_vote_reason() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(2),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(
+            id,
+            Action::VoteApprove,
+            None,
+            Some("Solar farm pays back in 3 years".to_string()),
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteReject,
+        );
+
+        let vote = contract.get_vote(id, accounts(1)).unwrap();
+        assert!(matches!(vote.vote, Vote::Approve));
+        assert_eq!(
+            vote.reason,
+            Some("Solar farm pays back in 3 years".to_string())
+        );
+        let votes = contract.get_votes(id);
+        assert_eq!(votes.len(), 2);
+        assert_eq!(votes.get(&accounts(2)).unwrap().reason, None);
+        assert!(contract.get_vote(id, accounts(3)).is_none());
+    }
+
+    #[test]
+    fn test_vote_timeline() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract_with_council(
+            &mut context,
+            vec![accounts(1), accounts(2), accounts(3), accounts(4)],
+        );
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        for (voter, block, action) in [
+            (accounts(4), 5, Action::VoteApprove),
+            (accounts(2), 2, Action::VoteReject),
+            (accounts(1), 9, Action::VoteApprove),
+            (accounts(3), 5, Action::VoteRemove),
+        ] {
+            context.block_index(block);
+            vote(&mut context, &mut contract, voter, id, action);
+        }
+
+        let timeline = contract.get_vote_timeline(id);
+        assert_eq!(
+            timeline
+                .iter()
+                .map(|(account_id, _, block)| (account_id.clone(), *block))
+                .collect::<Vec<_>>(),
+            vec![
+                (accounts(2), 2),
+                (accounts(3), 5),
+                (accounts(4), 5),
+                (accounts(1), 9)
+            ]
+        );
+        assert!(matches!(timeline[0].1, Vote::Reject));
+        assert!(matches!(timeline[1].1, Vote::Remove));
+    }
+
+    #[test]
+    fn test_vote_without_reason_deserializes() {
+        let vote: VoteWithTimestamp =
+            near_sdk::serde_json::from_str(r#"{"vote":"Approve","blocknumber":10}"#).unwrap();
+        assert_eq!(vote.reason, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REASON_TOO_LONG")]
+    fn test_vote_reason_too_long() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(
+            id,
+            Action::VoteApprove,
+            None,
+            Some("x".repeat(MAX_VOTE_REASON_LENGTH + 1)),
+        );
+    }
+
+    fn add_admin(contract: &mut Contract, admin: AccountId) {
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        policy.add_or_update_role(&RolePermission {
+            name: ADMIN_ROLE.to_string(),
+            kind: RoleKind::Group(vec![admin].into_iter().collect()),
+            permissions: HashSet::default(),
+            vote_policy: HashMap::default(),
+        });
+        contract.policy.set(&VersionedPolicy::Current(policy));
+    }
+
+    fn enable_force_execute(contract: &mut Contract, admin: AccountId) {
+        add_admin(contract, admin);
+        let mut config = contract.get_config();
+        config.allow_force_execute = true;
+        contract.config.set(&config);
+    }
+
+    #[test]
+    fn test_proposal_templates() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        enable_force_execute(&mut contract, accounts(3));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.add_template(
+            "grant".to_string(),
+            ProposalKind::Transfer {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(2),
+                amount: U128(100),
+                msg: None,
+                register_receiver: false,
+            },
+            "monthly grant".to_string(),
+        );
+        assert_eq!(
+            contract
+                .get_template("grant".to_string())
+                .unwrap()
+                .description,
+            "monthly grant"
+        );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        let id = contract.add_proposal_from_template("grant".to_string(), None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.description, "monthly grant");
+        assert!(matches!(
+            proposal.kind,
+            ProposalKind::Transfer {
+                amount: U128(100),
+                ..
+            }
+        ));
+
+        let id = contract.add_proposal_from_template(
+            "grant".to_string(),
+            Some(ProposalInput {
+                description: String::new(),
+                kind: ProposalKind::Transfer {
+                    token_id: String::from(OLD_BASE_TOKEN),
+                    receiver_id: accounts(3),
+                    amount: U128(200),
+                    msg: None,
+                    register_receiver: false,
+                },
+                tags: vec!["grants".to_string()],
+                ..Default::default()
+            }),
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.description, "monthly grant");
+        assert_eq!(proposal.tags, vec!["grants".to_string()]);
+        assert!(matches!(
+            proposal.kind,
+            ProposalKind::Transfer {
+                amount: U128(200),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_TEMPLATE")]
+    fn test_proposal_template_missing() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal_from_template("grant".to_string(), None);
+    }
+
+    #[test]
+    fn test_force_execute() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        enable_force_execute(&mut contract, accounts(3));
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.force_execute(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+        assert!(proposal.votes.is_empty());
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == accounts(2)));
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("proposal_force_executed")));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ADMIN")]
+    fn test_force_execute_not_admin() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        enable_force_execute(&mut contract, accounts(3));
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.force_execute(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FORCE_EXECUTE_DISABLED")]
+    fn test_force_execute_disabled() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        enable_force_execute(&mut contract, accounts(3));
+        let mut config = contract.get_config();
+        config.allow_force_execute = false;
+        contract.config.set(&config);
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.force_execute(id);
+    }
+
+    fn add_guardian(contract: &mut Contract, guardian: AccountId) {
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        policy.add_or_update_role(&RolePermission {
+            name: GUARDIAN_ROLE.to_string(),
+            kind: RoleKind::Group(vec![guardian].into_iter().collect()),
+            permissions: HashSet::default(),
+            vote_policy: HashMap::default(),
+        });
+        contract.policy.set(&VersionedPolicy::Current(policy));
+    }
+
+    /// Adds finance and engineering roles and a proposal both of them must approve.
+    fn create_joint_proposal(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        set_default_vote_policy(
+            contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(1),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        for (name, member) in [("finance", accounts(2)), ("engineering", accounts(3))] {
+            policy.add_or_update_role(&RolePermission {
+                name: name.to_string(),
+                kind: RoleKind::Group(vec![member].into_iter().collect()),
+                permissions: vec!["*:VoteApprove".to_string()].into_iter().collect(),
+                vote_policy: HashMap::default(),
+            });
+        }
+        contract.policy.set(&VersionedPolicy::Current(policy));
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.add_proposal(ProposalInput {
+            description: "joint".to_string(),
+            kind: ProposalKind::Vote,
+            required_roles: vec!["finance".to_string(), "engineering".to_string()],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_required_roles() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_joint_proposal(&mut context, &mut contract);
+        // Neither the council nor finance alone can approve it.
+        for voter in [accounts(1), accounts(2)] {
+            vote(&mut context, &mut contract, voter, id, Action::VoteApprove);
+            assert_eq!(
+                contract.get_proposal(id).proposal.status,
+                ProposalStatus::InProgress
+            );
+        }
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ROLE_NOT_FOUND")]
+    fn test_required_roles_unknown_role() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "joint".to_string(),
+            kind: ProposalKind::Vote,
+            required_roles: vec!["finance".to_string()],
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_prune_proposals() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        let approved = create_tagged_proposal(&mut context, &mut contract, vec!["solar"]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            approved,
+            Action::VoteApprove,
+        );
+        let executing = create_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            executing,
+            Action::VoteApprove,
+        );
+        let in_progress = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let rejected = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            rejected,
+            Action::VoteReject,
+        );
+        testing_env!(context.block_index(50).build());
+        let recent = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            recent,
+            Action::VoteApprove,
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert_eq!(contract.prune_proposals(0, 10, 1), 1);
+        assert_eq!(contract.prune_proposals(1, 10, 2), 0);
+        assert_eq!(contract.prune_proposals(0, 10, 10), 1);
+        assert_eq!(contract.prune_proposals(0, 10, 10), 0);
+        for id in [approved, rejected] {
+            assert!(contract.proposals.get(&id).is_none());
+        }
+        assert!(contract
+            .get_proposals_by_tag("solar".to_string(), 0, 10)
+            .is_empty());
+        for id in [executing, in_progress, recent] {
+            assert!(contract.proposals.get(&id).is_some());
+        }
+        assert_eq!(status_count(&contract, ProposalStatus::Rejected), 0);
+        assert_eq!(status_count(&contract, ProposalStatus::Approved), 2);
+
+        // Done executing once the callback is received.
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(executing);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert_eq!(contract.prune_proposals(0, 10, 10), 1);
+        assert!(contract.proposals.get(&executing).is_none());
+    }
+
+    #[test]
+    fn test_prune_approved_dependency() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        let contractor = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            contractor,
+            Action::VoteApprove,
+        );
+        testing_env!(context
+            .block_index(50)
+            .predecessor_account_id(accounts(3))
+            .build());
+        assert_eq!(contract.prune_proposals(0, 10, 10), 1);
+        assert!(contract.proposals.get(&contractor).is_none());
+
+        // Proposals can still depend on the deleted approved proposal.
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        let milestone = contract.add_proposal(ProposalInput {
+            description: "milestone".to_string(),
+            kind: ProposalKind::Vote,
+            depends_on: Some(contractor),
+            ..Default::default()
+        });
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            milestone,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(milestone).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GUARDIAN")]
+    fn test_prune_proposals_not_guardian() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.prune_proposals(0, 10, 10);
+    }
+
+    #[test]
+    fn test_prune_stale_suggestions() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(2),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let mut config = contract.get_config();
+        config.suggestion_pruning = Some(SuggestionPruning {
+            max_age: U64(100),
+            min_support: 3,
+        });
+        contract.config.set(&config);
+        let suggestion = |n: u32| ProposalKind::Suggestion {
+            suggestion: format!("idea {}", n),
+        };
+        let ignored = create_kind_proposal(&mut context, &mut contract, suggestion(0));
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            ignored,
+            Action::VoteApprove,
+        );
+        let promoted = create_kind_proposal(&mut context, &mut contract, suggestion(1));
+        for voter in [accounts(1), accounts(2)] {
+            vote(
+                &mut context,
+                &mut contract,
+                voter,
+                promoted,
+                Action::VoteApprove,
+            );
+        }
+        let vote_id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        testing_env!(context.block_index(50).build());
+        let recent = create_kind_proposal(&mut context, &mut contract, suggestion(2));
+        assert_eq!(contract.locked_amount, to_yocto("3"));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_index(120)
+            .build());
+        assert_eq!(contract.prune_stale_suggestions(10), 1);
+        assert!(contract.proposals.get(&ignored).is_none());
+        for id in [promoted, vote_id, recent] {
+            assert!(contract.proposals.get(&id).is_some());
+        }
+        assert_eq!(transfers_to(accounts(1)), vec![to_yocto("1")]);
+        assert_eq!(contract.locked_amount, to_yocto("2"));
+        assert_eq!(status_count(&contract, ProposalStatus::InProgress), 2);
+
+        testing_env!(context.block_index(150).build());
+        assert_eq!(contract.prune_stale_suggestions(10), 1);
+        assert!(contract.proposals.get(&recent).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SUGGESTION_PRUNING_DISABLED")]
+    fn test_prune_stale_suggestions_disabled() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.prune_stale_suggestions(10);
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        let id = create_proposal(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.set_paused(true);
+        assert!(contract.is_paused());
+        // Views are still available.
+        assert_eq!(contract.get_proposal(id).id, id);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.act_proposal(id, Action::VoteApprove, None, None)
+        }));
+        assert!(result.is_err());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.set_paused(false);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CONTRACT_PAUSED")]
+    fn test_add_proposal_while_paused() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.set_paused(true);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create_proposal(&mut context, &mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GUARDIAN")]
+    fn test_pause_not_guardian() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        contract.set_paused(true);
+    }
+
+    fn function_call_kind(gas: Vec<u64>) -> ProposalKind {
+        let actions: Vec<_> = gas
+            .into_iter()
+            .map(|gas| {
+                near_sdk::serde_json::json!({
+                    "method_name": "install_panel",
+                    "args": "",
+                    "deposit": "0",
+                    "gas": U64(gas),
+                })
+            })
+            .collect();
+        near_sdk::serde_json::from_value(near_sdk::serde_json::json!({
+            "FunctionCall": { "receiver_id": accounts(4), "actions": actions }
+        }))
+        .unwrap()
+    }
+
+    fn function_call_kind_with_deposits(deposits: Vec<Balance>) -> ProposalKind {
+        let actions: Vec<_> = deposits
+            .into_iter()
+            .map(|deposit| {
+                near_sdk::serde_json::json!({
+                    "method_name": "install_panel",
+                    "args": "",
+                    "deposit": U128(deposit),
+                    "gas": U64(10_000_000_000_000),
+                })
+            })
+            .collect();
+        near_sdk::serde_json::from_value(near_sdk::serde_json::json!({
+            "FunctionCall": { "receiver_id": accounts(4), "actions": actions }
+        }))
+        .unwrap()
+    }
+
+    fn create_function_call_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "call".to_string(),
+            kind: function_call_kind(vec![10_000_000_000_000]),
+            ..Default::default()
+        })
+    }
+
+    /// Adds a function call proposal and fails its execution.
+    fn create_failed_call(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        let id = create_function_call_proposal(context, contract);
+        fail_call(context, contract, id)
+    }
+
+    /// Approves given function call proposal and fails its execution.
+    fn fail_call(context: &mut VMContextBuilder, contract: &mut Contract, id: u64) -> u64 {
+        vote(context, contract, accounts(1), id, Action::VoteApprove);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(id);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Failed
+        );
+        id
+    }
+
+    #[test]
+    fn test_amend_failed_call() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_admin(&mut contract, accounts(3));
+        let id = create_failed_call(&mut context, &mut contract);
+        assert_eq!(contract.get_proposal(id).proposal.retry_count, 1);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.amend_failed_call(id, accounts(5));
+        let proposal = contract.get_proposal(id).proposal;
+        match proposal.kind {
+            ProposalKind::FunctionCall { receiver_id, .. } => assert_eq!(receiver_id, accounts(5)),
+            _ => panic!("unexpected kind"),
+        }
+        assert_eq!(proposal.retry_count, 0);
+
+        // Finalizing executes the call again, on the new receiver.
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == accounts(5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_NOT_FAILED")]
+    fn test_amend_approved_call() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_admin(&mut contract, accounts(3));
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.amend_failed_call(id, accounts(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMENDED_CALL_HAS_DEPOSIT")]
+    fn test_amend_failed_call_with_deposit() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_admin(&mut contract, accounts(3));
+        let id = create_kind_proposal(
+            &mut context,
+            &mut contract,
+            function_call_kind_with_deposits(vec![0, to_yocto("5")]),
+        );
+        fail_call(&mut context, &mut contract, id);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.amend_failed_call(id, accounts(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ADMIN")]
+    fn test_amend_failed_call_not_admin() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_failed_call(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.amend_failed_call(id, accounts(5));
+    }
+
+    #[test]
+    fn test_approved_status_persisted_before_execution() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        // Both members have to approve.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteApprove,
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        // The call and its callback are scheduled while the approval is already stored.
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 2);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+
+        // A reentrant attempt to act on the same proposal is refused.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            vote(
+                &mut context,
+                &mut contract,
+                accounts(2),
+                id,
+                Action::VoteApprove,
+            )
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            vote(
+                &mut context,
+                &mut contract,
+                accounts(2),
+                id,
+                Action::Finalize,
+            )
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_ALREADY_EXECUTED")]
+    fn test_execute_approved_proposal_twice() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        let mut proposal: Proposal = contract.proposals.get(&id).unwrap().into();
+        let policy = contract.policy.get().unwrap().to_policy();
+        contract.internal_execute_proposal(&policy, &mut proposal, id);
+    }
+
+    #[test]
+    fn test_function_call_gas_accounting() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        // 2 x 135 Tgas + 20 Tgas for the callback fits into 300 Tgas of prepaid gas.
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "two calls".to_string(),
+            kind: function_call_kind(vec![135_000_000_000_000, 135_000_000_000_000]),
+            ..Default::default()
+        });
+        assert_eq!(contract.get_last_proposal_id(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_GAS")]
+    fn test_function_call_over_gas() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "two calls".to_string(),
+            kind: function_call_kind(vec![150_000_000_000_000, 150_000_000_000_000]),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_function_call_deposit_under_cap() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.max_function_call_deposit = U128(to_yocto("1"));
+        contract.config.set(&config);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "no deposit".to_string(),
+            kind: function_call_kind_with_deposits(vec![0, 0]),
+            ..Default::default()
+        });
+        contract.add_proposal(ProposalInput {
+            description: "small deposits".to_string(),
+            kind: function_call_kind_with_deposits(vec![to_yocto("1") / 2, to_yocto("1") / 2]),
+            ..Default::default()
+        });
+        assert_eq!(contract.get_last_proposal_id(), 2);
+
+        // Zero deposit calls pass even when no deposit is allowed at all.
+        config.max_function_call_deposit = U128(0);
+        contract.config.set(&config);
+        contract.add_proposal(ProposalInput {
+            description: "no deposit".to_string(),
+            kind: function_call_kind_with_deposits(vec![0]),
+            ..Default::default()
+        });
+        assert_eq!(contract.get_last_proposal_id(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_OVER_CAP")]
+    fn test_function_call_deposit_over_cap() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.max_function_call_deposit = U128(to_yocto("1"));
+        contract.config.set(&config);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        // Each action is under the cap, but together they exceed it.
+        contract.add_proposal(ProposalInput {
+            description: "drain".to_string(),
+            kind: function_call_kind_with_deposits(vec![to_yocto("1"), 1]),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_get_expiring_soon() {
+        const DAY: u64 = 1_000_000_000 * 60 * 60 * 24;
+        const DAY_BLOCKS: u64 = DAY / EXPECTED_BLOCK_TIME;
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut ids = vec![];
+        for submitted in [0, DAY, 3 * DAY] {
+            testing_env!(context.block_timestamp(submitted).build());
+            ids.push(create_proposal(&mut context, &mut contract));
+        }
+        // Proposals expire after 7 days, so at day 5 they have 2, 3 and 5 days left.
+        testing_env!(context.block_timestamp(5 * DAY).build());
+        let expiring: Vec<u64> = contract
+            .get_expiring_soon(0, 2 * DAY_BLOCKS + DAY_BLOCKS / 2, 10)
+            .into_iter()
+            .map(|output| output.id)
+            .collect();
+        assert_eq!(expiring, vec![ids[0]]);
+        assert_eq!(contract.get_expiring_soon(0, 4 * DAY_BLOCKS, 10).len(), 2);
+        // Only the first proposal is scanned, then only the other two.
+        assert_eq!(contract.get_expiring_soon(0, 4 * DAY_BLOCKS, 1).len(), 1);
+        let expiring: Vec<u64> = contract
+            .get_expiring_soon(1, 4 * DAY_BLOCKS, 10)
+            .into_iter()
+            .map(|output| output.id)
+            .collect();
+        assert_eq!(expiring, vec![ids[1]]);
+
+        // Expired and decided proposals are not included.
+        contract.act_proposal(ids[1], Action::VoteApprove, None, None);
+        testing_env!(context.block_timestamp(7 * DAY + 1).build());
+        let expiring: Vec<u64> = contract
+            .get_expiring_soon(0, 10 * DAY_BLOCKS, 10)
+            .into_iter()
+            .map(|output| output.id)
+            .collect();
+        assert_eq!(expiring, vec![ids[2]]);
+    }
+
+    #[test]
+    fn test_read_default_proposal() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut votes = HashMap::default();
+        votes.insert(
+            accounts(2),
+            VoteWithTimestampV1 {
+                vote: Vote::Reject,
+                blocknumber: 5,
+            },
+        );
+        let v1 = ProposalV1 {
+            proposer: accounts(1),
+            description: "old".to_string(),
+            kind: OldProposalKind::Vote,
+            status: ProposalStatus::InProgress,
+            vote_counts: HashMap::default(),
+            votes,
+            threshold_block: None,
+            submission_time: U64(0),
+        };
+        // Store the proposal exactly as the first version of the contract did.
+        let bytes = VersionedProposal::Default(v1).try_to_vec().unwrap();
+        contract
+            .proposals
+            .insert(&0, &VersionedProposal::try_from_slice(&bytes).unwrap());
+        contract.last_proposal_id = 1;
+
+        let proposal = contract.get_proposal(0).proposal;
+        assert_eq!(proposal.description, "old");
+        assert!(proposal.tags.is_empty());
+        assert_eq!(proposal.bond, U128(0));
+        assert!(!proposal.extended);
+        assert_eq!(proposal.votes.get(&accounts(2)).unwrap().blocknumber, 5);
+        assert_eq!(proposal.votes.get(&accounts(2)).unwrap().reason, None);
+        assert!(!contract.proposals.get(&0).unwrap().is_latest());
+
+        // Acting on the proposal rewrites it in the latest version.
+        contract.act_proposal(0, Action::VoteApprove, None, None);
+        assert!(contract.proposals.get(&0).unwrap().is_latest());
+        assert_eq!(
+            contract.get_proposal(0).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    /// Writes the state of the first version of the contract with given proposals.
+    fn write_old_state(proposals: Vec<ProposalV1>) {
+        let last_proposal_id = proposals.len() as u64;
+        let mut stored = LookupMap::new(StorageKeys::Proposals);
+        for (id, proposal) in proposals.into_iter().enumerate() {
+            stored.insert(&(id as u64), &VersionedProposal::Default(proposal));
+        }
+        env::state_write(&OldContract {
+            config: LazyOption::new(
+                StorageKeys::Config,
+                Some(&OldConfig {
+                    name: "old".to_string(),
+                    purpose: "to migrate".to_string(),
+                    metadata: vec![].into(),
+                }),
+            ),
+            policy: LazyOption::new(
+                StorageKeys::Policy,
+                Some(&OldVersionedPolicy::Default {
+                    council: vec![accounts(1)],
+                    stakers: vec![],
+                }),
+            ),
+            last_proposal_id,
+            proposals: stored,
+        });
+    }
+
+    /// Transfer of 100 yocto $NEAR to charlie with a "memo" msg, approved by bob at block 3,
+    /// as written by the first version of the contract.
+    const BASELINE_TRANSFER_PROPOSAL: &str = "0005000000616c696365030000006f6c64070000000007000000636861726c69656400000000000000000000000000000001040000006d656d6f000100000007000000636f756e63696c0100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000100000003000000626f62000300000000000000000500000000000000";
+
+    /// Config change to name "dao", purpose "old" and metadata [1, 2], approved by bob at
+    /// block 3, as written by the first version of the contract.
+    const BASELINE_CONFIG_PROPOSAL: &str = "0005000000616c696365030000006f6c64000300000064616f030000006f6c64020000000102000100000007000000636f756e63696c0100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000100000003000000626f62000300000000000000000500000000000000";
+
+    fn baseline_proposal(bytes: &str) -> ProposalV1 {
+        match VersionedProposal::try_from_slice(&hex::decode(bytes).unwrap()).unwrap() {
+            VersionedProposal::Default(proposal) => proposal,
+            VersionedProposal::V2(_) => panic!("not a first version proposal"),
+        }
+    }
+
+    #[test]
+    fn test_read_baseline_transfer() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.proposals.insert(
+            &0,
+            &VersionedProposal::Default(baseline_proposal(BASELINE_TRANSFER_PROPOSAL)),
+        );
+        contract.last_proposal_id = 1;
+        assert!(contract.has_voted(0, accounts(1)));
+        match contract.get_proposal(0).proposal.kind {
+            ProposalKind::Transfer {
+                amount,
+                msg,
+                register_receiver,
+                ..
+            } => {
+                assert_eq!(amount, U128(100));
+                assert_eq!(msg, Some("memo".to_string()));
+                assert!(!register_receiver);
+            }
+            _ => panic!("not a transfer"),
+        }
+    }
+
+    #[test]
+    fn test_migrate_baseline_proposals() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .predecessor_account_id(env::current_account_id())
+            .build());
+        write_old_state(vec![
+            baseline_proposal(BASELINE_TRANSFER_PROPOSAL),
+            baseline_proposal(BASELINE_CONFIG_PROPOSAL),
+        ]);
+        let contract = Contract::migrate();
+
+        let transfer = contract.get_proposal(0).proposal;
+        assert_eq!(transfer.proposer, accounts(0));
+        assert_eq!(transfer.submission_time, U64(5));
+        assert_eq!(transfer.vote_counts["council"], [1, 0, 0]);
+        assert_eq!(transfer.votes[&accounts(1)].blocknumber, 3);
+        match transfer.kind {
+            ProposalKind::Transfer {
+                token_id,
+                receiver_id,
+                amount,
+                msg,
+                register_receiver,
+            } => {
+                assert_eq!(token_id, OLD_BASE_TOKEN);
+                assert_eq!(receiver_id, accounts(2));
+                assert_eq!(amount, U128(100));
+                assert_eq!(msg, Some("memo".to_string()));
+                assert!(!register_receiver);
+            }
+            _ => panic!("not a transfer"),
+        }
+
+        match contract.get_proposal(1).proposal.kind {
+            ProposalKind::ChangeConfig {
+                config,
+                effective_block,
+            } => {
+                assert_eq!(config.name, "dao");
+                assert_eq!(config.purpose, "old");
+                assert_eq!(config.metadata.0, vec![1, 2]);
+                assert_eq!(config.callback_gas, default_callback_gas());
+                assert_eq!(config.voting_power_source, VotingPowerSource::OnePerMember);
+                assert_eq!(effective_block, None);
+            }
+            _ => panic!("not a config change"),
+        }
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.build());
+        testing_env!(context
+            .predecessor_account_id(env::current_account_id())
+            .build());
+        write_old_state(
+            ["first", "second"]
+                .iter()
+                .map(|description| ProposalV1 {
+                    proposer: accounts(1),
+                    description: description.to_string(),
+                    kind: OldProposalKind::Vote,
+                    status: ProposalStatus::InProgress,
+                    vote_counts: HashMap::default(),
+                    votes: HashMap::default(),
+                    threshold_block: None,
+                    submission_time: U64(0),
+                })
+                .collect(),
+        );
+
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.get_config().name, "old");
+        assert!(contract
+            .get_policy()
+            .is_role_member("council", &accounts(1)));
+        assert_eq!(contract.get_last_proposal_id(), 2);
+        assert_eq!(contract.get_proposal(1).proposal.description, "second");
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(0, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(0).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert_eq!(
+            create_tagged_proposal(&mut context, &mut contract, vec![]),
+            2
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MUST_BE_SELF_OR_FACTORY")]
+    fn test_migrate_not_self() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        write_old_state(vec![]);
+        Contract::migrate();
+    }
+
+    fn blob_hash(blob: &[u8]) -> Base58CryptoHash {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&env::sha256(blob));
+        Base58CryptoHash::from(hash)
+    }
+
+    fn create_upgrade_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        hash: Base58CryptoHash,
+    ) -> u64 {
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.add_proposal(ProposalInput {
+            description: "upgrade".to_string(),
+            kind: ProposalKind::UpgradeSelf { hash },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_upgrade_from_blob() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.upgrade_from_blob = true;
+        contract.config.set(&config);
+
+        let code = vec![1u8; 100];
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        let hash = contract.internal_store_blob(code.clone());
+        assert_eq!(hash, blob_hash(&code));
+        assert!(contract.has_blob(hash));
+        // Excess deposit is refunded.
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 1);
+
+        let id = create_upgrade_proposal(&mut context, &mut contract, hash);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        // The code is deployed directly to this account, not through the factory.
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == env::current_account_id()));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.remove_blob(hash);
+        assert!(!contract.has_blob(hash));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_BLOB")]
+    fn test_upgrade_from_missing_blob() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.upgrade_from_blob = true;
+        contract.config.set(&config);
+        create_upgrade_proposal(&mut context, &mut contract, blob_hash(&[1, 2, 3]));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_USE_UPGRADE_SELF")]
+    fn test_upgrade_remote_self() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::UpgradeRemote {
+                receiver_id: env::current_account_id(),
+                method_name: "upgrade".to_string(),
+                hash: blob_hash(&[]),
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_DEPOSIT")]
+    fn test_store_blob_not_enough_deposit() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.internal_store_blob(vec![1u8; 100]);
+    }
+
+    fn status_count(contract: &Contract, status: ProposalStatus) -> u64 {
+        *contract
+            .get_proposal_status_counts()
+            .get(&status)
+            .unwrap_or(&0)
+    }
+
+    #[test]
+    fn test_proposal_status_counts() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_permission(&mut contract, "*:RemoveProposal");
+        let approved = create_proposal(&mut context, &mut contract);
+        let removed = create_proposal(&mut context, &mut contract);
+        let expired = create_proposal(&mut context, &mut contract);
+        let failed = create_function_call_proposal(&mut context, &mut contract);
+        assert_eq!(status_count(&contract, ProposalStatus::InProgress), 4);
+
+        contract.act_proposal(approved, Action::VoteApprove, None, None);
+        assert_eq!(status_count(&contract, ProposalStatus::InProgress), 3);
+        assert_eq!(status_count(&contract, ProposalStatus::Approved), 1);
+
+        // Deleted proposals are no longer counted.
+        contract.act_proposal(removed, Action::RemoveProposal, None, None);
+        assert_eq!(status_count(&contract, ProposalStatus::InProgress), 2);
+
+        contract.act_proposal(failed, Action::VoteApprove, None, None);
+        assert_eq!(status_count(&contract, ProposalStatus::Approved), 2);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(failed);
+        assert_eq!(status_count(&contract, ProposalStatus::Approved), 1);
+        assert_eq!(status_count(&contract, ProposalStatus::Failed), 1);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(1_000_000_000 * 24 * 60 * 60 * 8)
+            .build());
+        contract.act_proposal(expired, Action::Finalize, None, None);
+        assert_eq!(status_count(&contract, ProposalStatus::InProgress), 0);
+        assert_eq!(status_count(&contract, ProposalStatus::Expired), 1);
+        assert_eq!(status_count(&contract, ProposalStatus::Approved), 1);
+    }
+
+    fn create_ft_transfer_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        register_receiver: bool,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "ft payout".to_string(),
+            kind: ProposalKind::Transfer {
+                token_id: accounts(3).to_string(),
+                receiver_id: accounts(2),
+                amount: U128(100),
+                msg: None,
+                register_receiver,
+            },
+            ..Default::default()
+        })
+    }
+
+    fn created_function_calls() -> Vec<(AccountId, String)> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id.clone();
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        near_sdk::mock::VmAction::FunctionCall { method_name, .. } => {
+                            Some((receiver_id.clone(), method_name))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    /// Adds a role whose members can only vote on transfers, not submit them.
+    fn add_transfer_voters(contract: &mut Contract, members: Vec<AccountId>) {
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        policy.add_or_update_role(&RolePermission {
+            name: "voters".to_string(),
+            kind: RoleKind::Group(members.into_iter().collect()),
+            permissions: vec![
+                "transfer:VoteApprove".to_string(),
+                "transfer:VoteReject".to_string(),
+            ]
+            .into_iter()
+            .collect(),
+            vote_policy: HashMap::default(),
+        });
+        contract.policy.set(&VersionedPolicy::Current(policy));
+    }
+
+    #[test]
+    fn test_vote_without_submit_permission() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_transfer_voters(&mut contract, vec![accounts(2), accounts(3)]);
+        let id = create_proposal(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert!(matches!(proposal.votes[&accounts(2)].vote, Vote::Approve));
+        assert_eq!(proposal.vote_counts["voters"], [1, 0, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PERMISSION_DENIED")]
+    fn test_submit_without_submit_permission() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_transfer_voters(&mut contract, vec![accounts(2), accounts(3)]);
+        context.predecessor_account_id(accounts(2));
+        create_proposal(&mut context, &mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PERMISSION_DENIED")]
+    fn test_remove_without_permission() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_transfer_voters(&mut contract, vec![accounts(2), accounts(3)]);
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.act_proposal(id, Action::VoteRemove, None, None);
+    }
+
+    #[test]
+    fn test_get_all_members() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        add_transfer_voters(&mut contract, vec![accounts(3), accounts(2)]);
+        let members = contract.get_all_members();
+        assert_eq!(members["council"], vec![accounts(1), accounts(2)]);
+        assert_eq!(members["voters"], vec![accounts(2), accounts(3)]);
+
+        update_council(&mut contract, vec![accounts(4)], vec![accounts(1)]);
+        assert_eq!(
+            contract.get_all_members()["council"],
+            vec![accounts(2), accounts(4)]
+        );
+    }
+
+    fn create_cosponsored_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        cosponsors: Vec<AccountId>,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "cosponsored".to_string(),
+            kind: ProposalKind::Vote,
+            cosponsors,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_cosponsors() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_cosponsored_proposal(
+            &mut context,
+            &mut contract,
+            vec![accounts(2), accounts(3)],
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Draft
+        );
+        testing_env!(context.build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.act_proposal(id, Action::VoteApprove, None, None)
+        }));
+        assert!(result.is_err());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(100)
+            .build());
+        contract.confirm_cosponsorship(id);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Draft
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.confirm_cosponsorship(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+        // The voting period starts once the proposal opens.
+        assert_eq!(proposal.submission_time, U64(100));
+        assert_eq!(
+            contract.get_proposal_status_counts()[&ProposalStatus::InProgress],
+            1
+        );
+
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_cosponsor_never_confirms() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_cosponsored_proposal(&mut context, &mut contract, vec![accounts(2)]);
+        let locked = contract.locked_amount;
+        let period = contract.get_policy().proposal_period.0;
+        testing_env!(context.block_timestamp(period).build());
+        assert_eq!(contract.finalize_expired(10), 0);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(period + 1)
+            .build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.confirm_cosponsorship(id)
+        }));
+        assert!(result.is_err());
+        // The draft expires and its bond is returned.
+        assert_eq!(contract.finalize_expired(10), 1);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Expired
+        );
+        assert_eq!(contract.locked_amount, locked - to_yocto("1"));
+        assert_eq!(status_count(&contract, ProposalStatus::Draft), 0);
+        assert_eq!(status_count(&contract, ProposalStatus::Expired), 1);
+    }
+
+    #[test]
+    fn test_no_cosponsors() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_cosponsored_proposal(&mut context, &mut contract, vec![]);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_COSPONSOR")]
+    fn test_confirm_cosponsorship_not_listed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_cosponsored_proposal(&mut context, &mut contract, vec![accounts(2)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.confirm_cosponsorship(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_COSPONSORS")]
+    fn test_proposer_as_cosponsor() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        create_cosponsored_proposal(&mut context, &mut contract, vec![accounts(1)]);
+    }
+
+    fn create_whitelisted_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        voter_whitelist: Vec<AccountId>,
+    ) -> u64 {
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.add_proposal(ProposalInput {
+            description: "whitelisted".to_string(),
+            kind: ProposalKind::Vote,
+            voter_whitelist,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_whitelisted_voter() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        let id = create_whitelisted_proposal(&mut context, &mut contract, vec![accounts(2)]);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let outcomes = contract.act_proposals(vec![(id, Action::VoteApprove, None)]);
+        assert_eq!(outcomes[0].error, None);
+        assert!(contract.has_voted(id, accounts(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_WHITELISTED")]
+    fn test_non_whitelisted_voter() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        let id = create_whitelisted_proposal(&mut context, &mut contract, vec![accounts(2)]);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let outcomes = contract.act_proposals(vec![(id, Action::VoteApprove, None)]);
+        assert_eq!(outcomes[0].error, Some("ERR_NOT_WHITELISTED".to_string()));
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+    }
+
+    #[test]
+    fn test_proposer_can_vote() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert!(contract
+            .get_proposal(id)
+            .proposal
+            .votes
+            .contains_key(&accounts(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSER_CANNOT_VOTE")]
+    fn test_proposer_cannot_vote() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.proposer_can_vote = false;
+        contract.config.set(&config);
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+    }
+
+    #[test]
+    fn test_proposer_cannot_vote_others_can() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        let mut config = contract.get_config();
+        config.proposer_can_vote = false;
+        contract.config.set(&config);
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteApprove,
+        );
+        assert!(contract
+            .get_proposal(id)
+            .proposal
+            .votes
+            .contains_key(&accounts(2)));
+    }
+
+    #[test]
+    fn test_act_proposals() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(2),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let voted = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let open = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            voted,
+            Action::VoteApprove,
+        );
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let outcomes = contract.act_proposals(vec![
+            (voted, Action::VoteReject, None),
+            (open, Action::VoteApprove, Some("looks good".to_string())),
+            (99, Action::VoteApprove, None),
+            (open, Action::RemoveProposal, None),
+        ]);
+        let errors: Vec<Option<&str>> = outcomes
+            .iter()
+            .map(|outcome| outcome.error.as_deref())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                Some("ERR_ALREADY_VOTED"),
+                None,
+                Some("ERR_NO_PROPOSAL"),
+                Some("ERR_PERMISSION_DENIED")
+            ]
+        );
+        assert_eq!(outcomes[1].id, open);
+        let proposal = contract.get_proposal(open).proposal;
+        assert!(matches!(proposal.votes[&accounts(2)].vote, Vote::Approve));
+        assert_eq!(
+            proposal.votes[&accounts(2)].reason.as_deref(),
+            Some("looks good")
+        );
+        assert!(!contract.get_proposal(voted).proposal.votes.is_empty());
+    }
+
+    #[test]
+    fn test_act_proposals_skips_each_error() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        // Council of accounts(1) and accounts(3), who just joined, with a quorum of 2.
+        add_council_member_with_delay(&mut context, &mut contract, accounts(3));
+        let expired = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let period = contract.get_policy().proposal_period.0;
+        testing_env!(context.block_timestamp(period + 1).build());
+        let open = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let other = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let poll = create_poll(&mut context, &mut contract);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let whitelisted = contract.add_proposal(ProposalInput {
+            description: "whitelisted".to_string(),
+            kind: ProposalKind::Vote,
+            voter_whitelist: vec![accounts(3)],
+            ..Default::default()
+        });
+        set_vote_cooldown(&mut contract, vec![]);
+
+        testing_env!(context.attached_deposit(0).build());
+        let outcomes = contract.act_proposals(vec![
+            (expired, Action::VoteApprove, None),
+            (poll, Action::VoteOption(3), None),
+            (open, Action::VoteOption(0), None),
+            (whitelisted, Action::VoteApprove, None),
+            (
+                other,
+                Action::VoteApprove,
+                Some("x".repeat(MAX_VOTE_REASON_LENGTH + 1)),
+            ),
+            (open, Action::Finalize, None),
+            (open, Action::VoteApprove, Some("looks good".to_string())),
+            (other, Action::VoteApprove, None),
+        ]);
+        let errors: Vec<Option<&str>> = outcomes
+            .iter()
+            .map(|outcome| outcome.error.as_deref())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                Some("ERR_PROPOSAL_EXPIRED"),
+                Some("ERR_INVALID_POLL_OPTION"),
+                Some("ERR_NOT_A_POLL"),
+                Some("ERR_NOT_WHITELISTED"),
+                Some("ERR_REASON_TOO_LONG"),
+                Some("ERR_PROPOSAL_NOT_EXPIRED_OR_FAILED"),
+                None,
+                Some("ERR_VOTE_COOLDOWN"),
+            ]
+        );
+        assert_eq!(
+            contract.get_proposal(open).proposal.votes[&accounts(1)]
+                .reason
+                .as_deref(),
+            Some("looks good")
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let outcomes = contract.act_proposals(vec![(other, Action::VoteApprove, None)]);
+        assert_eq!(
+            outcomes[0].error.as_deref(),
+            Some("ERR_MEMBERSHIP_TOO_RECENT")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOO_MANY_ACTIONS")]
+    fn test_act_proposals_too_many() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.act_proposals(
+            (0..MAX_BULK_ACTIONS as u64 + 1)
+                .map(|id| (id, Action::VoteApprove, None))
+                .collect(),
+        );
+    }
+
+    #[test]
+    fn test_auto_execute() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        testing_env!(context.build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert_eq!(
+            created_function_calls()
+                .iter()
+                .filter(|(_, method_name)| method_name == "install_panel")
+                .count(),
+            1
+        );
+        assert!(!contract.awaiting_execution.contains(&id));
+    }
+
+    #[test]
+    fn test_manual_execute() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.auto_execute = false;
+        contract.config.set(&config);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        testing_env!(context.build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert!(created_function_calls().is_empty());
+
+        // Anyone can execute it.
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.execute(id);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert_eq!(
+            created_function_calls()
+                .iter()
+                .filter(|(_, method_name)| method_name == "install_panel")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_NOT_AWAITING_EXECUTION")]
+    fn test_manual_execute_twice() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.auto_execute = false;
+        contract.config.set(&config);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        contract.execute(id);
+        contract.execute(id);
+    }
+
+    #[test]
+    fn test_abort_execution() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        let mut config = contract.get_config();
+        config.auto_execute = false;
+        contract.config.set(&config);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        testing_env!(context.build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.abort_execution(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Rejected);
+        assert_eq!(proposal.bond, U128(0));
+        assert_eq!(transfers_to(accounts(1)), vec![to_yocto("1")]);
+        assert!(!contract.awaiting_execution.contains(&id));
+        assert!(created_function_calls().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_NOT_AWAITING_EXECUTION")]
+    fn test_abort_execution_after_execute() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        let mut config = contract.get_config();
+        config.auto_execute = false;
+        contract.config.set(&config);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        contract.execute(id);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.abort_execution(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GUARDIAN")]
+    fn test_abort_execution_not_guardian() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        let mut config = contract.get_config();
+        config.auto_execute = false;
+        contract.config.set(&config);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        contract.abort_execution(id);
+    }
+
+    #[test]
+    fn test_observer_notified() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.observer = Some(accounts(4));
+        contract.config.set(&config);
+        let id = create_proposal(&mut context, &mut contract);
+        assert_eq!(
+            created_function_calls(),
+            vec![(accounts(4), "on_proposal_event".to_string())]
+        );
+
+        testing_env!(context.build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert!(created_function_calls().contains(&(accounts(4), "on_proposal_event".to_string())));
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(id);
+        assert_eq!(
+            created_function_calls(),
+            vec![(accounts(4), "on_proposal_event".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_transfer_registers_receiver() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_ft_transfer_proposal(&mut context, &mut contract, true);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(3), "storage_deposit".to_string()),
+                (accounts(3), "ft_transfer".to_string()),
+                (env::current_account_id(), "on_proposal_callback".to_string()),
+            ]
+        );
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+        assert_eq!(proposal.bond, U128(0));
+    }
+
+    #[test]
+    fn test_transfer_without_registration() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(3), "ft_transfer".to_string()),
+                (env::current_account_id(), "on_proposal_callback".to_string()),
+            ]
+        );
+    }
+
+    fn ft_transfer_gas() -> near_sdk::Gas {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .find_map(|action| match action {
+                near_sdk::mock::VmAction::FunctionCall {
+                    method_name, gas, ..
+                } if method_name == "ft_transfer" => Some(gas),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_ft_transfer_gas_override() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config
+            .ft_transfer_gas
+            .insert(accounts(3), U64(30_000_000_000_000));
+        contract.config.set(&config);
+        let id = create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(ft_transfer_gas(), near_sdk::Gas(30_000_000_000_000));
+    }
+
+    #[test]
+    fn test_ft_transfer_gas_default() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config
+            .ft_transfer_gas
+            .insert(accounts(4), U64(30_000_000_000_000));
+        contract.config.set(&config);
+        let id = create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(ft_transfer_gas(), crate::types::GAS_FOR_FT_TRANSFER);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_FT_TRANSFER_GAS")]
+    fn test_ft_transfer_gas_out_of_bounds() {
+        let mut config = Config::test_config();
+        config
+            .ft_transfer_gas
+            .insert(accounts(3), U64(200_000_000_000_000));
+        config.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BASE_TOKEN_NO_REGISTRATION")]
+    fn test_base_token_transfer_registration() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "payout".to_string(),
+            kind: ProposalKind::Transfer {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(2),
+                amount: U128(100),
+                msg: None,
+                register_receiver: true,
+            },
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_configured_callback_gas() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.callback_gas = U64(50_000_000_000_000);
+        contract.config.set(&config);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        let callback_gas = near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .find_map(|action| match action {
+                near_sdk::mock::VmAction::FunctionCall {
+                    method_name, gas, ..
+                } if method_name == "on_proposal_callback" => Some(gas),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(callback_gas, near_sdk::Gas(50_000_000_000_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_CALLBACK_GAS")]
+    fn test_change_config_callback_gas_out_of_bounds() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.callback_gas = U64(200_000_000_000_000);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "config".to_string(),
+            kind: ProposalKind::ChangeConfig {
+                config,
+                effective_block: None,
+            },
+            ..Default::default()
+        });
+    }
+
+    fn create_scheduled_config_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        effective_block: BlockHeight,
+    ) -> u64 {
+        let mut config = contract.get_config();
+        config.purpose = "scheduled".to_string();
+        create_kind_proposal(
+            context,
+            contract,
+            ProposalKind::ChangeConfig {
+                config,
+                effective_block: Some(effective_block),
+            },
+        )
+    }
+
+    #[test]
+    fn test_config_history() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        assert!(contract.get_config_history(10).is_empty());
+        testing_env!(context.block_index(5).build());
+        let id = create_scheduled_config_proposal(&mut context, &mut contract, 0);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.starts_with("EVENT_JSON:") && log.contains("config_change")));
+
+        let history = contract.get_config_history(10);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].block_height, 5);
+        assert_eq!(history[0].proposal_id, id);
+        assert_eq!(history[0].proposer, accounts(1));
+        assert_eq!(history[0].old_config.purpose, "to test");
+        assert_eq!(history[0].new_config.purpose, "scheduled");
+        assert_eq!(contract.get_config().purpose, "scheduled");
+    }
+
+    #[test]
+    fn test_config_history_bounded() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        for _ in 0..MAX_CONFIG_HISTORY + 1 {
+            let id = create_scheduled_config_proposal(&mut context, &mut contract, 0);
+            contract.act_proposal(id, Action::VoteApprove, None, None);
+        }
+        let history = contract.get_config_history(100);
+        assert_eq!(history.len(), MAX_CONFIG_HISTORY);
+        assert_eq!(history[0].proposal_id, MAX_CONFIG_HISTORY as u64);
+        assert_eq!(contract.get_config_history(1).len(), 1);
+    }
+
+    #[test]
+    fn test_scheduled_config() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_scheduled_config_proposal(&mut context, &mut contract, 10);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert_ne!(contract.get_config().purpose, "scheduled");
+
+        testing_env!(context.block_index(10).build());
+        contract.poke_scheduled_config(id);
+        assert_eq!(contract.get_config().purpose, "scheduled");
+        assert!(!contract.scheduled_configs.contains(&id));
+    }
+
+    #[test]
+    fn test_scheduled_config_past_block() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.block_index(20).build());
+        let id = create_scheduled_config_proposal(&mut context, &mut contract, 10);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(contract.get_config().purpose, "scheduled");
+        assert!(!contract.scheduled_configs.contains(&id));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CONFIG_NOT_EFFECTIVE_YET")]
+    fn test_scheduled_config_too_early() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_scheduled_config_proposal(&mut context, &mut contract, 10);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        testing_env!(context.block_index(9).build());
+        contract.poke_scheduled_config(id);
+    }
+
+    #[test]
+    fn test_council_digest() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let expired = create_proposal(&mut context, &mut contract);
+        context.block_timestamp(1_000_000_000 * 24 * 60 * 60 * 8);
+        let voted = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(voted, Action::VoteApprove, None, None);
+        let not_voted = create_proposal(&mut context, &mut contract);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let suggestion = contract.add_proposal(ProposalInput {
+            description: "suggestion".to_string(),
+            kind: ProposalKind::Suggestion {
+                suggestion: "more panels".to_string(),
+            },
+            ..Default::default()
+        });
+        let failed = create_proposal(&mut context, &mut contract);
+        let mut proposal: Proposal = contract.proposals.get(&failed).unwrap().into();
+        proposal.status = ProposalStatus::Failed;
+        contract
+            .proposals
+            .insert(&failed, &VersionedProposal::V2(proposal));
+
+        let ids = |outputs: Vec<ProposalOutput>| -> Vec<u64> {
+            outputs.into_iter().map(|output| output.id).collect()
+        };
+        let digest = contract.get_council_digest(accounts(1));
+        assert_eq!(ids(digest.to_vote), vec![not_voted]);
+        assert_eq!(ids(digest.to_finalize), vec![expired, failed]);
+        assert_eq!(ids(digest.suggestions), vec![suggestion]);
+
+        // Other members still need to vote on the proposal voted by accounts(1).
+        let digest = contract.get_council_digest(accounts(2));
+        assert_eq!(ids(digest.to_vote), vec![voted, not_voted]);
+    }
+
+    fn set_min_proposal_stake(contract: &mut Contract, min_proposal_stake: Balance) {
+        let mut config = contract.get_config();
+        config.min_proposal_stake = U128(min_proposal_stake);
+        contract.config.set(&config);
+    }
+
+    #[test]
+    fn test_min_proposal_stake() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_voting_power_source(&mut contract, VotingPowerSource::StakedBalance);
+        set_min_proposal_stake(&mut contract, to_yocto("10"));
+        lock_stake(&mut context, &mut contract, accounts(1), 10);
+        let id = create_proposal(&mut context, &mut contract);
+        assert_eq!(contract.get_proposal(id).proposal.proposer, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_STAKE")]
+    fn test_min_proposal_stake_not_met() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_voting_power_source(&mut contract, VotingPowerSource::StakedBalance);
+        set_min_proposal_stake(&mut contract, 1);
+        create_proposal(&mut context, &mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MIN_STAKE_WITHOUT_BALANCE")]
+    fn test_min_proposal_stake_one_per_member() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.min_proposal_stake = U128(1);
+        create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::ChangeConfig {
+                config,
+                effective_block: None,
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DESCRIPTION_TOO_SHORT")]
+    fn test_min_description_length() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.min_description_length = 20;
+        contract.config.set(&config);
+        create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::UpgradeSelf {
+                hash: blob_hash(&[]),
+            },
+        );
+    }
+
+    #[test]
+    fn test_min_description_length_signaling_exempt() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.min_description_length = 20;
+        config.high_risk_kinds.push("vote".to_string());
+        contract.config.set(&config);
+        let id = create_kind_proposal(&mut context, &mut contract, ProposalKind::Vote);
+        assert_eq!(
+            contract.get_proposal(id).proposal.description,
+            "token supply"
+        );
+    }
+
+    fn update_council(contract: &mut Contract, add: Vec<AccountId>, remove: Vec<AccountId>) {
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        let council = "council".to_string();
+        for account_id in add {
+            policy.add_member_to_role(&council, &account_id);
+        }
+        for account_id in remove {
+            policy.remove_member_from_role(&council, &account_id);
+        }
+        contract.policy.set(&VersionedPolicy::Current(policy));
+    }
+
+    #[test]
+    fn test_role_threshold_follows_members_added() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        // Two of five members are no longer a majority.
+        update_council(&mut contract, vec![accounts(4), accounts(5)], vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_role_threshold_follows_members_removed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract_with_council(
+            &mut context,
+            vec![accounts(1), accounts(2), accounts(3), accounts(4)],
+        );
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        for account_id in [accounts(1), accounts(2)] {
+            vote(
+                &mut context,
+                &mut contract,
+                account_id,
+                id,
+                Action::VoteApprove,
+            );
+        }
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+        // Votes of members who left still count, against the threshold of the remaining members.
+        update_council(&mut contract, vec![], vec![accounts(2), accounts(4)]);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_staker_reject_does_not_approve() {
+        let mut context = VMContextBuilder::new();
+        // Stakers are accounts(1) and accounts(2), a member role.
+        let mut contract = setup_contract(&mut context);
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        let mut stakers = policy
+            .roles
+            .iter()
+            .find(|role| role.name == "stakers")
+            .unwrap()
+            .clone();
+        stakers.permissions.insert("vote:VoteReject".to_string());
+        policy.add_or_update_role(&stakers);
+        contract.policy.set(&VersionedPolicy::Current(policy));
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteReject,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+    }
+
+    #[test]
+    fn test_early_rejection() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract_with_council(
+            &mut context,
+            vec![accounts(1), accounts(2), accounts(3), accounts(4)],
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        let locked = contract.locked_amount;
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteReject,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+        // Two rejects don't reach the threshold of 3, but the 2 members left can't approve.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteReject,
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Rejected);
+        assert_eq!(proposal.vote_counts["council"], [0, 2, 0]);
+        assert_eq!(contract.locked_amount, locked - to_yocto("1"));
+    }
+
+    #[test]
+    fn test_early_rejection_after_members_removed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert!(!contract.can_finalize(id));
+        // The quorum of 3 can't be reached by the only member left to vote.
+        update_council(&mut contract, vec![], vec![accounts(3)]);
+        assert!(contract.can_finalize(id));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Rejected
+        );
+    }
+
+    #[test]
+    fn test_disabled_kinds() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        let in_flight = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let mut config = contract.get_config();
+        config.disabled_kinds = vec!["vote".to_string()];
+        contract.config.set(&config);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            create_tagged_proposal(&mut context, &mut contract, vec![])
+        }));
+        assert!(result.is_err());
+        // Other kinds can still be added.
+        create_proposal(&mut context, &mut contract);
+
+        // The proposal added before the kind was disabled can still pass.
+        for account_id in [accounts(1), accounts(2)] {
+            vote(
+                &mut context,
+                &mut contract,
+                account_id,
+                in_flight,
+                Action::VoteApprove,
+            );
+        }
+        assert_eq!(
+            contract.get_proposal(in_flight).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_KIND_DISABLED")]
+    fn test_add_disabled_kind() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.disabled_kinds = vec!["vote".to_string()];
+        contract.config.set(&config);
+        create_tagged_proposal(&mut context, &mut contract, vec![]);
+    }
+
+    fn set_rate_limit(contract: &mut Contract, exempt_roles: Vec<&str>) {
+        let mut config = contract.get_config();
+        config.rate_limit = Some(RateLimit {
+            max_proposals: 2,
+            window: U64(10),
+            exempt_roles: exempt_roles
+                .into_iter()
+                .map(|role| role.to_string())
+                .collect(),
+        });
+        contract.config.set(&config);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_rate_limit(&mut contract, vec![]);
+        testing_env!(context.block_index(100).build());
+        create_tagged_proposal(&mut context, &mut contract, vec![]);
+        testing_env!(context.block_index(105).build());
+        create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            create_tagged_proposal(&mut context, &mut contract, vec![])
+        }));
+        assert!(result.is_err());
+
+        // The first proposal leaves the window, the second is still in it.
+        testing_env!(context.block_index(110).build());
+        create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            create_tagged_proposal(&mut context, &mut contract, vec![])
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_last_proposal_id(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_RATE_LIMITED")]
+    fn test_rate_limited() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_rate_limit(&mut contract, vec![]);
+        for _ in 0..3 {
+            create_tagged_proposal(&mut context, &mut contract, vec![]);
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_exempt_role() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_rate_limit(&mut contract, vec!["council"]);
+        for _ in 0..3 {
+            create_tagged_proposal(&mut context, &mut contract, vec![]);
+        }
+        assert_eq!(contract.get_last_proposal_id(), 3);
+    }
+
+    fn set_vote_cooldown(contract: &mut Contract, exempt_roles: Vec<&str>) {
+        let mut config = contract.get_config();
+        config.vote_cooldown = Some(VoteCooldown {
+            blocks: U64(10),
+            exempt_roles: exempt_roles
+                .into_iter()
+                .map(|role| role.to_string())
+                .collect(),
+        });
+        contract.config.set(&config);
+    }
+
+    #[test]
+    fn test_vote_cooldown() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_transfer_voters(&mut contract, vec![accounts(3), accounts(4)]);
+        set_vote_cooldown(&mut contract, vec!["council"]);
+        let first = create_proposal(&mut context, &mut contract);
+        let second = create_proposal(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_index(5)
+            .build());
+        contract.act_proposal(first, Action::VoteApprove, None, None);
+
+        testing_env!(context.block_index(14).build());
+        let outcomes = contract.act_proposals(vec![(second, Action::VoteApprove, None)]);
+        assert_eq!(outcomes[0].error, Some("ERR_VOTE_COOLDOWN".to_string()));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.act_proposal(second, Action::VoteApprove, None, None)
+        }));
+        assert!(result.is_err());
+
+        testing_env!(context.block_index(15).build());
+        contract.act_proposal(second, Action::VoteApprove, None, None);
+        assert!(contract.has_voted(second, accounts(3)));
+    }
+
+    #[test]
+    fn test_vote_cooldown_exempt_role() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        set_vote_cooldown(&mut contract, vec!["council"]);
+        let first = create_proposal(&mut context, &mut contract);
+        let second = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(first, Action::VoteApprove, None, None);
+        contract.act_proposal(second, Action::VoteApprove, None, None);
+        assert!(contract.has_voted(second, accounts(1)));
+    }
+
+    fn set_voting_power_source(contract: &mut Contract, source: VotingPowerSource) {
+        let mut config = contract.get_config();
+        config.voting_power_source = source;
+        contract.config.set(&config);
+    }
+
+    /// Approve weight of accounts(1) and accounts(3) voting on a token weighted proposal.
+    fn token_weighted_tally(source: VotingPowerSource) -> Balance {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::TokenWeight,
+                quorum: U128(0),
+                threshold: WeightOrRatio::AbsoluteWeight(U128(1_000)),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        set_voting_power_source(&mut contract, source);
+        contract
+            .voting_balances
+            .insert(&(accounts(4), accounts(1)), &vec![(0, 30)]);
+        contract
+            .voting_balances
+            .insert(&(accounts(4), accounts(3)), &vec![(0, 50)]);
+        testing_env!(context.block_index(1).build());
+        for (account_id, amount) in [(accounts(1), 5), (accounts(3), 7)] {
+            contract.lockups.insert(
+                &account_id,
+                &StakeLockup {
+                    amount: U128(amount),
+                    unlock_block: 100,
+                },
+            );
+        }
+        let id = create_proposal(&mut context, &mut contract);
+        for voter in [accounts(1), accounts(3)] {
+            vote(&mut context, &mut contract, voter, id, Action::VoteApprove);
+        }
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+        proposal.vote_counts["council"][Vote::Approve as usize]
+    }
+
+    #[test]
+    fn test_voting_power_sources() {
+        assert_eq!(token_weighted_tally(VotingPowerSource::OnePerMember), 2);
+        assert_eq!(
+            token_weighted_tally(VotingPowerSource::FtBalance(accounts(4))),
+            80
+        );
+        assert_eq!(token_weighted_tally(VotingPowerSource::StakedBalance), 12);
+    }
+
+    #[test]
+    fn test_no_voting_power() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::TokenWeight,
+                quorum: U128(0),
+                threshold: WeightOrRatio::AbsoluteWeight(U128(1_000)),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        set_voting_power_source(&mut contract, VotingPowerSource::StakedBalance);
+        let id = create_proposal(&mut context, &mut contract);
+        let outcomes = contract.act_proposals(vec![(id, Action::VoteApprove, None)]);
+        assert_eq!(outcomes[0].error, Some("ERR_NO_VOTING_POWER".to_string()));
+        assert_eq!(contract.get_effective_weight(id, accounts(1)), U128(0));
+
+        // Roles that aren't token weighted can still vote.
+        set_default_vote_policy(&mut contract, VotePolicy::default());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    fn lock_voting_balance(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        account_id: AccountId,
+        amount: Balance,
+    ) {
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.ft_on_transfer(account_id, U128(amount), LOCK_VOTING_POWER_MSG.to_string());
+    }
+
+    #[test]
+    fn test_locked_voting_balance_counted_once() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::TokenWeight,
+                quorum: U128(0),
+                threshold: WeightOrRatio::AbsoluteWeight(U128(1_000)),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        set_voting_power_source(&mut contract, VotingPowerSource::FtBalance(accounts(4)));
+        testing_env!(context.block_index(1).build());
+        lock_voting_balance(&mut context, &mut contract, accounts(1), 100);
+        assert!(contract.get_ft_balance(accounts(4)).is_none());
+        testing_env!(context
+            .block_index(2)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let id = create_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+
+        // Moving the tokens to another member doesn't give them a second vote.
+        testing_env!(context
+            .block_index(3)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.unlock_voting_balance(U128(100));
+        lock_voting_balance(&mut context, &mut contract, accounts(3), 100);
+        assert_eq!(
+            contract.get_voting_balance(accounts(4), accounts(3)),
+            U128(100)
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let outcomes = contract.act_proposals(vec![(id, Action::VoteApprove, None)]);
+        assert_eq!(outcomes[0].error, Some("ERR_NO_VOTING_POWER".to_string()));
+        assert_eq!(
+            contract.get_proposal(id).proposal.vote_counts["council"],
+            [100, 0, 0]
+        );
+
+        // They count on proposals submitted after they were locked.
+        testing_env!(context
+            .block_index(4)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let id = create_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.vote_counts["council"],
+            [100, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_unlock_voting_balance_failed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_voting_power_source(&mut contract, VotingPowerSource::FtBalance(accounts(4)));
+        lock_voting_balance(&mut context, &mut contract, accounts(3), 40);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.unlock_voting_balance(U128(40));
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(4), "ft_transfer".to_string()),
+                (
+                    env::current_account_id(),
+                    "on_unlock_voting_balance".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            contract.get_voting_balance(accounts(4), accounts(3)),
+            U128(0)
+        );
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_unlock_voting_balance(accounts(4), accounts(3), U128(40));
+        assert_eq!(
+            contract.get_voting_balance(accounts(4), accounts(3)),
+            U128(40)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_VOTING_TOKEN")]
+    fn test_lock_voting_balance_wrong_token() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_voting_power_source(&mut contract, VotingPowerSource::FtBalance(accounts(5)));
+        lock_voting_balance(&mut context, &mut contract, accounts(3), 40);
+    }
+
+    #[test]
+    fn test_change_voting_power_source() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.voting_power_source = VotingPowerSource::StakedBalance;
+        let id = create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::ChangeConfig {
+                config,
+                effective_block: None,
+            },
+        );
+        assert_eq!(
+            contract.get_config().voting_power_source,
+            VotingPowerSource::OnePerMember
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_config().voting_power_source,
+            VotingPowerSource::StakedBalance
+        );
+    }
+
+    #[test]
+    fn test_finalize_expired() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        let mut vote_policy = VotePolicy {
+            weight_kind: WeightKind::RoleWeight,
+            quorum: U128(3),
+            threshold: WeightOrRatio::Weight(U128(1)),
+            spam_threshold: U128(0),
+            max_weight_bps: None,
+        };
+        set_default_vote_policy(&mut contract, vote_policy.clone());
+        let first = create_proposal(&mut context, &mut contract);
+        let approved = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(approved, Action::VoteApprove, None, None);
+        let second = create_proposal(&mut context, &mut contract);
+        context.block_timestamp(1_000_000_000 * 24 * 60 * 60 * 8);
+        let recent = create_proposal(&mut context, &mut contract);
+        // Lowering the quorum makes the voted proposal approved.
+        vote_policy.quorum = U128(1);
+        set_default_vote_policy(&mut contract, vote_policy);
+
+        assert_eq!(contract.finalize_expired(1), 1);
+        assert_eq!(
+            contract.get_proposal(first).proposal.status,
+            ProposalStatus::Expired
+        );
+        assert_eq!(
+            contract.get_proposal(second).proposal.status,
+            ProposalStatus::InProgress
+        );
+        assert_eq!(contract.finalize_expired(10), 1);
+        assert_eq!(contract.finalize_expired(10), 0);
+        let proposal = contract.get_proposal(second).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Expired);
+        assert_eq!(proposal.bond, U128(0));
+        assert_eq!(
+            contract.get_proposal(approved).proposal.status,
+            ProposalStatus::InProgress
+        );
+        assert_eq!(
+            contract.get_proposal(recent).proposal.status,
+            ProposalStatus::InProgress
+        );
+        assert_eq!(status_count(&contract, ProposalStatus::Expired), 2);
+    }
+
+    fn create_poll(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "energy supplier".to_string(),
+            kind: ProposalKind::Poll {
+                options: vec!["solar".to_string(), "wind".to_string(), "hydro".to_string()],
+            },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_poll() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3), accounts(4)]);
+        let id = create_poll(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteOption(2),
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteOption(0),
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            id,
+            Action::VoteOption(2),
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+        assert_eq!(
+            proposal.poll_counts,
+            vec![(0, 1), (2, 2)].into_iter().collect()
+        );
+        assert_eq!(proposal.poll_winner, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(1_000_000_000 * 24 * 60 * 60 * 8)
+            .build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Expired);
+        assert_eq!(proposal.poll_winner, Some(2));
+        assert_eq!(proposal.bond, U128(0));
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("poll_resolved") && log.contains("hydro")));
+    }
+
+    #[test]
+    fn test_poll_tie() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3)]);
+        let id = create_poll(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteOption(1),
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteOption(0),
+        );
+        assert_eq!(contract.get_proposal(id).proposal.leading_option(), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_POLL_OPTION")]
+    fn test_poll_invalid_option() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_poll(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteOption(3),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_POLL_VOTE_OPTION")]
+    fn test_poll_vote_approve() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_poll(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+    }
+
+    fn create_proposal_with_executor(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        executor: AccountId,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "timed payout".to_string(),
+            kind: ProposalKind::Transfer {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(2),
+                amount: U128(to_yocto("100")),
+                msg: None,
+                register_receiver: false,
+            },
+            executor: Some(executor),
+            ..Default::default()
+        })
+    }
+
+    fn paid_receiver() -> bool {
+        near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == accounts(2))
+    }
+
+    #[test]
+    fn test_executor() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3), accounts(4)]);
+        let id = create_proposal_with_executor(&mut context, &mut contract, accounts(3));
+        for account_id in [accounts(1), accounts(4)] {
+            vote(
+                &mut context,
+                &mut contract,
+                account_id,
+                id,
+                Action::VoteApprove,
+            );
+        }
+        // Approved by the votes, but only the executor can execute it.
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+        assert!(!paid_receiver());
+
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::Finalize,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert!(paid_receiver());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_EXECUTOR")]
+    fn test_not_executor() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3), accounts(4)]);
+        let id = create_proposal_with_executor(&mut context, &mut contract, accounts(3));
+        for account_id in [accounts(1), accounts(4)] {
+            vote(
+                &mut context,
+                &mut contract,
+                account_id,
+                id,
+                Action::VoteApprove,
+            );
+        }
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::Finalize,
+        );
+    }
+
+    #[test]
+    fn test_refresh_status() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        let mut vote_policy = VotePolicy {
+            weight_kind: WeightKind::RoleWeight,
+            quorum: U128(3),
+            threshold: WeightOrRatio::Weight(U128(1)),
+            spam_threshold: U128(0),
+            max_weight_bps: None,
+        };
+        set_default_vote_policy(&mut contract, vote_policy.clone());
+        let overdue = create_proposal(&mut context, &mut contract);
+        let approvable = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(approvable, Action::VoteApprove, None, None);
+        vote_policy.quorum = U128(1);
+        set_default_vote_policy(&mut contract, vote_policy);
+        let recent = create_proposal(&mut context, &mut contract);
+        assert_eq!(contract.refresh_status(recent), ProposalStatus::InProgress);
+
+        testing_env!(context
+            .block_timestamp(1_000_000_000 * 24 * 60 * 60 * 8)
+            .build());
+        let locked = contract.locked_amount;
+        assert_eq!(contract.refresh_status(overdue), ProposalStatus::Expired);
+        assert_eq!(contract.get_proposal(overdue).proposal.bond, U128(0));
+        assert_eq!(contract.locked_amount, locked - to_yocto("1"));
+        // Not executed, so nothing is sent for the approvable transfer.
+        assert_eq!(
+            contract.refresh_status(approvable),
+            ProposalStatus::InProgress
+        );
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .all(|receipt| receipt.receiver_id != accounts(2)));
+        assert_eq!(status_count(&contract, ProposalStatus::Expired), 1);
+    }
+
+    fn lock_stake(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        account_id: AccountId,
+        duration: u64,
+    ) {
+        testing_env!(context
+            .predecessor_account_id(account_id)
+            .attached_deposit(to_yocto("10"))
+            .build());
+        contract.lock_stake(U128(to_yocto("10")), U64(duration));
+    }
+
+    #[test]
+    fn test_lock_multiplier() {
+        assert_eq!(lock_multiplier(0), 100);
+        assert_eq!(lock_multiplier(MAX_LOCK_DURATION / 2), 250);
+        assert_eq!(lock_multiplier(MAX_LOCK_DURATION), MAX_LOCK_MULTIPLIER);
+        assert_eq!(lock_multiplier(2 * MAX_LOCK_DURATION), MAX_LOCK_MULTIPLIER);
+    }
+
+    #[test]
+    fn test_duration_weighted_vote() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::DurationWeight,
+                quorum: U128(to_yocto("20")),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        lock_stake(&mut context, &mut contract, accounts(1), MAX_LOCK_DURATION);
+        lock_stake(&mut context, &mut contract, accounts(2), 10);
+        assert_eq!(contract.locked_amount, to_yocto("20"));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+
+        // The short lock weighs about as much as its amount, not enough to reject.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteReject,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        let counts = proposal.vote_counts.get("council").unwrap();
+        assert_eq!(counts[Vote::Approve as usize], to_yocto("40"));
+        assert!(counts[Vote::Reject as usize] < to_yocto("11"));
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+    }
+
+    fn setup_stake_weighted(context: &mut VMContextBuilder) -> (Contract, u64) {
+        let mut contract = setup_contract_with_council(context, vec![accounts(1), accounts(2)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::StakeWeight,
+                quorum: U128(to_yocto("15")),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        lock_stake(context, &mut contract, accounts(1), 0);
+        lock_stake(context, &mut contract, accounts(2), 0);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("500"))
+            .build());
+        contract.lock_stake(U128(to_yocto("500")), U64(0));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(context, &mut contract);
+        (contract, id)
+    }
+
+    #[test]
+    fn test_stake_weighted_vote() {
+        let mut context = VMContextBuilder::new();
+        let (mut contract, id) = setup_stake_weighted(&mut context);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteApprove,
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        let counts = proposal.vote_counts.get("council").unwrap();
+        assert_eq!(counts[Vote::Approve as usize], to_yocto("20"));
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PERMISSION_DENIED")]
+    fn test_stake_weighted_vote_not_member() {
+        let mut context = VMContextBuilder::new();
+        let (mut contract, id) = setup_stake_weighted(&mut context);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+    }
+
+    fn set_max_weight_bps(contract: &mut Contract, max_weight_bps: u16) {
+        set_default_vote_policy(
+            contract,
+            VotePolicy {
+                weight_kind: WeightKind::StakeWeight,
+                quorum: U128(to_yocto("100")),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: Some(max_weight_bps),
+            },
+        );
+    }
+
+    #[test]
+    fn test_max_weight_bps() {
+        let mut context = VMContextBuilder::new();
+        let (mut contract, id) = setup_stake_weighted(&mut context);
+        // Caps each voter at 50 NEAR of stake.
+        set_max_weight_bps(&mut contract, 500);
+        contract.on_total_supply(U128(to_yocto("1000")));
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(to_yocto("500"))
+            .build());
+        contract.lock_stake(U128(to_yocto("500")), U64(0));
+        assert_eq!(
+            contract.get_effective_weight(id, accounts(1)),
+            U128(to_yocto("50"))
+        );
+        assert_eq!(
+            contract.get_effective_weight(id, accounts(2)),
+            U128(to_yocto("10"))
+        );
+
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteApprove,
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(
+            proposal.vote_counts["council"][Vote::Approve as usize],
+            to_yocto("60")
+        );
+        // Uncapped, the whale alone would have reached the quorum.
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_TOTAL_SUPPLY")]
+    fn test_max_weight_bps_without_total_supply() {
+        let mut context = VMContextBuilder::new();
+        let (mut contract, id) = setup_stake_weighted(&mut context);
+        set_max_weight_bps(&mut contract, 500);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+    }
+
+    #[test]
+    fn test_absolute_weight_threshold() {
+        let mut context = VMContextBuilder::new();
+        let (mut contract, _) = setup_stake_weighted(&mut context);
+        let vote_policy = |threshold| VotePolicy {
+            weight_kind: WeightKind::StakeWeight,
+            quorum: U128(0),
+            threshold,
+            spam_threshold: U128(0),
+            max_weight_bps: None,
+        };
+        // Stake weighted roles have no total weight, so a ratio passes with any vote.
+        set_default_vote_policy(&mut contract, vote_policy(WeightOrRatio::Ratio(1, 2)));
+        let ratio = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            ratio,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(ratio).proposal.status,
+            ProposalStatus::Approved
+        );
+
+        set_default_vote_policy(
+            &mut contract,
+            vote_policy(WeightOrRatio::AbsoluteWeight(U128(to_yocto("15")))),
+        );
+        let absolute = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            absolute,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(absolute).proposal.status,
+            ProposalStatus::InProgress
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            absolute,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(absolute).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_absolute_weight_below_ratio() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_governance_token(&mut contract, accounts(4));
+        contract.on_total_supply(U128(1_000));
+        let vote_policy = |threshold| VotePolicy {
+            weight_kind: WeightKind::TokenWeight,
+            quorum: U128(0),
+            threshold,
+            spam_threshold: U128(0),
+            max_weight_bps: None,
+        };
+        // Half of the supply is needed with a ratio, a single token with an absolute weight.
+        set_default_vote_policy(&mut contract, vote_policy(WeightOrRatio::Ratio(1, 2)));
+        let ratio = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            ratio,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(ratio).proposal.status,
+            ProposalStatus::InProgress
+        );
+
+        set_default_vote_policy(
+            &mut contract,
+            vote_policy(WeightOrRatio::AbsoluteWeight(U128(1))),
+        );
+        let absolute = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            absolute,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(absolute).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_has_voted() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        let id = create_proposal(&mut context, &mut contract);
+        assert!(!contract.has_voted(id, accounts(1)));
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert!(contract.has_voted(id, accounts(1)));
+        assert!(!contract.has_voted(id, accounts(2)));
+        assert!(!contract.has_voted(id + 1, accounts(1)));
+    }
+
+    #[test]
+    fn test_act_proposal_returns_status() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        let cases: [(fn() -> Action, ProposalStatus); 3] = [
+            (|| Action::VoteApprove, ProposalStatus::Approved),
+            (|| Action::VoteReject, ProposalStatus::Rejected),
+            (|| Action::VoteRemove, ProposalStatus::Removed),
+        ];
+        for (action, expected) in cases {
+            let id = create_proposal(&mut context, &mut contract);
+            testing_env!(context.predecessor_account_id(accounts(1)).build());
+            let status = contract.act_proposal(id, action(), None, None);
+            assert_eq!(status, ProposalStatus::InProgress);
+            assert_eq!(contract.get_proposal(id).proposal.status, status);
+            testing_env!(context.predecessor_account_id(accounts(2)).build());
+            let status = contract.act_proposal(id, action(), None, None);
+            assert_eq!(status, expected);
+            assert_eq!(contract.get_proposal(id).proposal.status, status);
+        }
+    }
+
+    #[test]
+    fn test_act_proposal_split_returns_status() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let status = contract.act_proposal_split(id, 10_000, 0, 0);
+        assert_eq!(status, ProposalStatus::Approved);
+        assert_eq!(contract.get_proposal(id).proposal.status, status);
+    }
+
+    #[test]
+    fn test_effective_weight() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::DurationWeight,
+                quorum: U128(to_yocto("100")),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        lock_stake(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            MAX_LOCK_DURATION / 2,
+        );
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+        let approve_count = |contract: &Contract| {
+            contract
+                .get_proposal(id)
+                .proposal
+                .vote_counts
+                .get("council")
+                .map(|counts| counts[Vote::Approve as usize])
+                .unwrap_or(0)
+        };
+
+        for account_id in [accounts(1), accounts(2)] {
+            let weight = contract.get_effective_weight(id, account_id.clone());
+            let before = approve_count(&contract);
+            vote(
+                &mut context,
+                &mut contract,
+                account_id,
+                id,
+                Action::VoteApprove,
+            );
+            assert_eq!(approve_count(&contract) - before, weight.0);
+        }
+        assert_eq!(
+            contract.get_effective_weight(id, accounts(1)).0,
+            to_yocto("25")
+        );
+        assert_eq!(contract.get_effective_weight(id, accounts(2)).0, 0);
+        assert_eq!(contract.get_effective_weight(id, accounts(4)).0, 0);
+    }
+
+    #[test]
+    fn test_split_vote() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::DurationWeight,
+                quorum: U128(to_yocto("100")),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        lock_stake(&mut context, &mut contract, accounts(1), 0);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal_split(id, 6_000, 4_000, 0);
+        let proposal = contract.get_proposal(id).proposal;
+        let counts = proposal.vote_counts.get("council").unwrap();
+        assert_eq!(counts[Vote::Approve as usize], to_yocto("6"));
+        assert_eq!(counts[Vote::Reject as usize], to_yocto("4"));
+        assert_eq!(proposal.split_votes[&accounts(1)], [6_000, 4_000, 0]);
+        assert!(proposal.votes.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_SPLIT")]
+    fn test_split_vote_invalid_shares() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal_split(id, 6_000, 3_000, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_VOTED")]
+    fn test_split_vote_twice() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(2),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal_split(id, 5_000, 0, 5_000);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+    }
+
+    #[test]
+    fn test_token_decimals_weighted_vote() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        // Quorum in whole tokens, the locked stake is in yocto.
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        policy.token_decimals = 24;
+        policy.update_default_vote_policy(&VotePolicy {
+            weight_kind: WeightKind::DurationWeight,
+            quorum: U128(11),
+            threshold: WeightOrRatio::Ratio(1, 2),
+            spam_threshold: U128(0),
+            max_weight_bps: None,
+        });
+        contract.policy.set(&VersionedPolicy::Current(policy));
+        lock_stake(&mut context, &mut contract, accounts(1), 0);
+        lock_stake(&mut context, &mut contract, accounts(2), 0);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+
+        // 10 tokens are short of the quorum of 11 tokens, even though 10^25 yocto are not.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(
+            proposal.vote_counts.get("council").unwrap()[Vote::Approve as usize],
+            10
+        );
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteApprove,
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(
+            proposal.vote_counts.get("council").unwrap()[Vote::Approve as usize],
+            20
+        );
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+    }
+
+    #[test]
+    fn test_unlock_stake() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        lock_stake(&mut context, &mut contract, accounts(2), 10);
+        testing_env!(context.attached_deposit(0).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.unlock_stake();
+        }));
+        assert!(result.is_err());
+
+        testing_env!(context.block_index(10).build());
+        contract.unlock_stake();
+        assert!(contract.get_lockup(accounts(2)).is_none());
+        assert_eq!(contract.locked_amount, 0);
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        for i in 0..3 {
+            testing_env!(context.block_index(i).build());
+            assert_eq!(contract.add_comment(id, format!("comment {}", i)), i);
+        }
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.add_comment(id, "from staker".to_string());
+
+        let comments = contract.get_comments(id, 1, 2);
+        assert_eq!(
+            comments
+                .iter()
+                .map(|comment| comment.text.as_str())
+                .collect::<Vec<_>>(),
+            vec!["comment 1", "comment 2"]
+        );
+        assert_eq!(comments[1].author, accounts(1));
+        assert_eq!(comments[1].block_height, 2);
+        let last = contract.get_comments(id, 3, 10);
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].author, accounts(2));
+        assert!(contract.get_comments(id + 1, 0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_PROPOSAL")]
+    fn test_comment_nonexistent_proposal() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.add_comment(0, "hello".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_MEMBER")]
+    fn test_comment_not_member() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.add_comment(id, "hello".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_COMMENT_TOO_LONG")]
+    fn test_comment_too_long() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        contract.add_comment(id, "a".repeat(MAX_COMMENT_LENGTH + 1));
+    }
+
+    #[test]
+    fn test_get_proposals_sorted() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let ids: Vec<u64> = (0..4)
+            .map(|_| create_proposal(&mut context, &mut contract))
+            .collect();
+        testing_env!(context.block_index(5).build());
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            ids[1],
+            Action::VoteApprove,
+        );
+        testing_env!(context.block_index(6).build());
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            ids[1],
+            Action::VoteApprove,
+        );
+        testing_env!(context.block_index(10).build());
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            ids[2],
+            Action::VoteApprove,
+        );
+
+        let sorted = |from_index, limit, sort| -> Vec<u64> {
+            contract
+                .get_proposals(from_index, limit, sort)
+                .into_iter()
+                .map(|output| output.id)
+                .collect()
+        };
+        assert_eq!(sorted(0, 10, None), vec![0, 1, 2, 3]);
+        assert_eq!(sorted(1, 2, Some(ProposalSort::ByIdAsc)), vec![1, 2]);
+        assert_eq!(
+            sorted(0, 10, Some(ProposalSort::ByIdDesc)),
+            vec![3, 2, 1, 0]
+        );
+        assert_eq!(sorted(1, 2, Some(ProposalSort::ByIdDesc)), vec![2, 1]);
+        assert_eq!(
+            sorted(0, 10, Some(ProposalSort::ByActivity)),
+            vec![2, 1, 3, 0]
+        );
+        assert_eq!(
+            sorted(0, 2, Some(ProposalSort::ByApprovalProgress)),
+            vec![1, 2]
+        );
+        assert_eq!(
+            sorted(2, 10, Some(ProposalSort::ByApprovalProgress)),
+            vec![3, 0]
+        );
+        let policy = contract.get_policy();
+        assert_eq!(
+            policy.approval_progress(&contract.get_proposal(ids[1]).proposal, 0),
+            MAX_APPROVAL_PROGRESS * 2 / 3
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_TREASURY")]
+    fn test_transfer_over_near_treasury() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "too much".to_string(),
+            kind: ProposalKind::Transfer {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(2),
+                amount: U128(to_yocto("1000")),
+                msg: None,
+                register_receiver: false,
+            },
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_transfer_within_ft_treasury() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.refresh_ft_balance(accounts(3));
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(3), "ft_balance_of".to_string()),
+                (env::current_account_id(), "on_ft_balance".to_string()),
+            ]
+        );
+        contract.on_ft_balance(accounts(3), U128(100));
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(100)));
+        create_ft_transfer_proposal(&mut context, &mut contract, false);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOKEN_NOT_TRACKED")]
+    fn test_refresh_untracked_ft_balance() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.refresh_ft_balance(accounts(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_TREASURY")]
+    fn test_transfer_over_ft_treasury() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.on_ft_balance(accounts(3), U128(99));
+        create_ft_transfer_proposal(&mut context, &mut contract, false);
+    }
+
+    fn create_removed_proposal(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        set_default_vote_policy(
+            contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(1),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let id = create_proposal(context, contract);
+        testing_env!(context.block_index(100).build());
+        contract.act_proposal(id, Action::VoteRemove, None, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Removed
+        );
+        id
+    }
+
+    #[test]
+    fn test_restore_removed_proposal() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        let id = create_removed_proposal(&mut context, &mut contract);
+        let grace_period = contract.get_config().removal_grace_period.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_index(100 + grace_period)
+            .build());
+        contract.restore_proposal(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+        assert!(proposal.votes.is_empty());
+        assert_eq!(proposal.bond, U128(to_yocto("1")));
+        assert_eq!(status_count(&contract, ProposalStatus::InProgress), 1);
+        assert_eq!(status_count(&contract, ProposalStatus::Removed), 0);
+
+        // The member who voted to remove can vote again.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_GRACE_PERIOD_OVER")]
+    fn test_restore_removed_proposal_after_grace_period() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_guardian(&mut contract, accounts(3));
+        let id = create_removed_proposal(&mut context, &mut contract);
+        let grace_period = contract.get_config().removal_grace_period.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_index(101 + grace_period)
+            .build());
+        contract.restore_proposal(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GUARDIAN")]
+    fn test_restore_removed_proposal_not_guardian() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_removed_proposal(&mut context, &mut contract);
+        contract.restore_proposal(id);
+    }
+
+    #[test]
+    fn test_delete_removed_proposal() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_removed_proposal(&mut context, &mut contract);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.delete_removed_proposal(id)
+        }));
+        assert!(result.is_err());
+
+        let grace_period = contract.get_config().removal_grace_period.0;
+        testing_env!(context.block_index(101 + grace_period).build());
+        contract.delete_removed_proposal(id);
+        assert!(contract.proposals.get(&id).is_none());
+        assert_eq!(contract.locked_amount, 0);
+        assert_eq!(status_count(&contract, ProposalStatus::Removed), 0);
+    }
+
+    fn transfers_to(account_id: AccountId) -> Vec<Balance> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id == account_id)
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::VmAction::Transfer { deposit } => Some(deposit),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rejected_proposal_returns_bond() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(1),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        assert_eq!(contract.locked_amount, to_yocto("1"));
+        testing_env!(context.build());
+        contract.act_proposal(id, Action::VoteReject, None, None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Rejected);
+        assert_eq!(proposal.bond, U128(0));
+        assert_eq!(contract.locked_amount, 0);
+        assert_eq!(transfers_to(accounts(1)), vec![to_yocto("1")]);
+    }
+
+    #[test]
+    fn test_removed_proposal_forfeits_bond() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_removed_proposal(&mut context, &mut contract);
+        // Still locked during the grace period, in case the proposal is restored.
+        assert_eq!(contract.locked_amount, to_yocto("1"));
+        let grace_period = contract.get_config().removal_grace_period.0;
+        testing_env!(context.block_index(101 + grace_period).build());
+        contract.delete_removed_proposal(id);
+        assert_eq!(contract.locked_amount, 0);
+        assert!(transfers_to(accounts(1)).is_empty());
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        create_proposal(&mut context, &mut contract);
+        let report = contract.reconcile();
+        assert_eq!(report.locked_amount, U128(to_yocto("1")));
+        let near = &report.tokens[OLD_BASE_TOKEN];
+        assert_eq!(near.obligations, U128(to_yocto("1")));
+        assert_eq!(near.surplus.0, near.balance.0 - to_yocto("1"));
+        assert_eq!(near.deficit, U128(0));
+
+        // Claimable tokens the DAO doesn't hold.
+        contract.ft_balances.insert(&accounts(3), &50);
+        contract.ft_tokens.insert(&accounts(3));
+        contract
+            .claimable_totals
+            .insert(accounts(3).to_string(), 80);
+        contract.pending_transfers.insert(accounts(3), 20);
+        let report = contract.reconcile();
+        assert_eq!(
+            report.tokens[&accounts(3).to_string()],
+            TokenReconciliation {
+                obligations: U128(100),
+                balance: U128(50),
+                surplus: U128(0),
+                deficit: U128(50),
+            }
+        );
+
+        // $NEAR leaving the account without going through the ledgers.
+        testing_env!(context.account_balance(0).build());
+        let near = &contract.reconcile().tokens[OLD_BASE_TOKEN];
+        assert_eq!(near.balance, U128(0));
+        assert_eq!(near.deficit, U128(to_yocto("1")));
+    }
+
+    /// One instance of every proposal kind.
+    fn all_proposal_kinds() -> Vec<ProposalKind> {
+        vec![
+            ProposalKind::ChangeConfig {
+                config: Config::test_config(),
+                effective_block: None,
+            },
+            ProposalKind::ChangePolicy {
+                policy: VersionedPolicy::Default {
+                    council: vec![],
+                    stakers: vec![],
+                },
+            },
+            ProposalKind::AddMemberToRole {
+                member_id: accounts(1),
+                role: "council".to_string(),
+                term_end_block: None,
+            },
+            ProposalKind::RemoveMemberFromRole {
+                member_id: accounts(1),
+                role: "council".to_string(),
+            },
+            function_call_kind(vec![]),
+            ProposalKind::UpgradeSelf {
+                hash: blob_hash(&[]),
+            },
+            ProposalKind::UpgradeRemote {
+                receiver_id: accounts(1),
+                method_name: "upgrade".to_string(),
+                hash: blob_hash(&[]),
+            },
+            ProposalKind::Transfer {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(1),
+                amount: U128(1),
+                msg: None,
+                register_receiver: false,
+            },
+            ProposalKind::Vote,
+            ProposalKind::ChangePolicyAddOrUpdateRole {
+                role: RolePermission {
+                    name: "role".to_string(),
+                    kind: RoleKind::Group(HashSet::default()),
+                    permissions: HashSet::default(),
+                    vote_policy: HashMap::default(),
+                },
+            },
+            ProposalKind::ChangePolicyRemoveRole {
+                role: "role".to_string(),
+            },
+            ProposalKind::ChangePolicyUpdateDefaultVotePolicy {
+                vote_policy: VotePolicy::default(),
+            },
+            ProposalKind::ChangePolicyUpdateParameters {
+                parameters: PolicyParameters {
+                    proposal_period: None,
+                    proposal_periods: None,
+                },
+            },
+            ProposalKind::Suggestion {
+                suggestion: "suggestion".to_string(),
+            },
+            ProposalKind::TransferPercent {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(1),
+                bps: 1,
+            },
+            ProposalKind::MultiTokenTransfer {
+                receiver_id: accounts(1),
+                transfers: vec![(String::from(OLD_BASE_TOKEN), U128(1))],
+            },
+            ProposalKind::Poll {
+                options: vec!["a".to_string(), "b".to_string()],
+            },
+            ProposalKind::TokenMint {
+                receiver_id: accounts(1),
+                amount: U128(1),
+            },
+            ProposalKind::TokenBurn { amount: U128(1) },
+            ProposalKind::StakeDelegate {
+                pool_id: accounts(3),
+                amount: U128(1),
+            },
+            ProposalKind::StakeUnstake {
+                pool_id: accounts(3),
+                amount: U128(1),
+            },
+            ProposalKind::StreamCreate {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(1),
+                total: U128(1),
+                start_block: 0,
+                end_block: 1,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_proposal_kind_labels() {
+        let kinds = all_proposal_kinds();
+        // Fails to compile when a kind is added, so it has to be added to `all_proposal_kinds`
+        // and to the labels.
+        let name = |kind: &ProposalKind| match kind {
+            ProposalKind::ChangeConfig { .. } => "ChangeConfig",
+            ProposalKind::ChangePolicy { .. } => "ChangePolicy",
+            ProposalKind::AddMemberToRole { .. } => "AddMemberToRole",
+            ProposalKind::RemoveMemberFromRole { .. } => "RemoveMemberFromRole",
+            ProposalKind::FunctionCall { .. } => "FunctionCall",
+            ProposalKind::UpgradeSelf { .. } => "UpgradeSelf",
+            ProposalKind::UpgradeRemote { .. } => "UpgradeRemote",
+            ProposalKind::Transfer { .. } => "Transfer",
+            ProposalKind::Vote => "Vote",
+            ProposalKind::ChangePolicyAddOrUpdateRole { .. } => "ChangePolicyAddOrUpdateRole",
+            ProposalKind::ChangePolicyRemoveRole { .. } => "ChangePolicyRemoveRole",
+            ProposalKind::ChangePolicyUpdateDefaultVotePolicy { .. } => {
+                "ChangePolicyUpdateDefaultVotePolicy"
+            }
+            ProposalKind::ChangePolicyUpdateParameters { .. } => "ChangePolicyUpdateParameters",
+            ProposalKind::Suggestion { .. } => "Suggestion",
+            ProposalKind::TransferPercent { .. } => "TransferPercent",
+            ProposalKind::MultiTokenTransfer { .. } => "MultiTokenTransfer",
+            ProposalKind::Poll { .. } => "Poll",
+            ProposalKind::TokenMint { .. } => "TokenMint",
+            ProposalKind::TokenBurn { .. } => "TokenBurn",
+            ProposalKind::StakeDelegate { .. } => "StakeDelegate",
+            ProposalKind::StakeUnstake { .. } => "StakeUnstake",
+            ProposalKind::StreamCreate { .. } => "StreamCreate",
+        };
+        let expected: HashMap<String, String> = kinds
+            .iter()
+            .map(|kind| (name(kind).to_string(), kind.to_policy_label().to_string()))
+            .collect();
+        assert_eq!(expected.len(), kinds.len());
+
+        let mut context = VMContextBuilder::new();
+        let contract = setup_contract(&mut context);
+        let labels = contract.get_proposal_kind_labels();
+        assert_eq!(labels, expected);
+        assert_eq!(labels["Suggestion"], "give a suggestion");
+    }
+
+    #[test]
+    fn test_proposal_schema() {
+        let mut context = VMContextBuilder::new();
+        let contract = setup_contract(&mut context);
+        let schema: near_sdk::serde_json::Value =
+            near_sdk::serde_json::from_str(&contract.get_proposal_schema()).unwrap();
+        let variants = schema["oneOf"].as_array().unwrap();
+        assert_eq!(variants.len(), PROPOSAL_KIND_LABELS.len());
+        let fields = |name: &str| -> near_sdk::serde_json::Value {
+            let variant = variants
+                .iter()
+                .find(|variant| variant["title"] == name)
+                .unwrap_or_else(|| panic!("{} is missing from the schema", name));
+            variant["properties"][name].clone()
+        };
+
+        // Every kind serializes to the fields described in the schema.
+        for kind in all_proposal_kinds() {
+            match near_sdk::serde_json::to_value(&kind).unwrap() {
+                near_sdk::serde_json::Value::String(name) => {
+                    assert!(variants.iter().any(|variant| variant["const"] == name));
+                }
+                near_sdk::serde_json::Value::Object(object) => {
+                    let (name, value) = object.iter().next().unwrap();
+                    let schema = fields(name);
+                    let properties = schema["properties"].as_object().unwrap();
+                    let value = value.as_object().unwrap();
+                    assert!(value.keys().all(|key| properties.contains_key(key)));
+                    assert!(schema["required"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .all(|key| value.contains_key(key.as_str().unwrap())));
+                }
+                _ => panic!("unexpected kind encoding"),
+            }
+        }
+
+        let transfer = fields("Transfer");
+        for field in ["token_id", "receiver_id", "amount", "msg"] {
+            assert!(transfer["properties"].get(field).is_some());
+        }
+        assert_eq!(
+            transfer["required"],
+            near_sdk::serde_json::json!(["token_id", "receiver_id", "amount"])
+        );
+    }
+
+    #[test]
+    fn test_proposal_dependency() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(1),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let contractor = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let milestone = contract.add_proposal(ProposalInput {
+            description: "milestone".to_string(),
+            kind: ProposalKind::Transfer {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(2),
+                amount: U128(to_yocto("10")),
+                msg: None,
+                register_receiver: false,
+            },
+            depends_on: Some(contractor),
+            ..Default::default()
+        });
+
+        // The dependency is still in progress, so the approved milestone can't execute.
+        contract.act_proposal(milestone, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(milestone).proposal.status,
+            ProposalStatus::Failed
+        );
+        assert!(near_sdk::test_utils::get_created_receipts().is_empty());
+
+        contract.act_proposal(contractor, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_proposal(contractor).proposal.status,
+            ProposalStatus::Approved
+        );
+        contract.act_proposal(milestone, Action::Finalize, None, None);
+        assert_eq!(
+            contract.get_proposal(milestone).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == accounts(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_DEPENDENCY")]
+    fn test_proposal_missing_dependency() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "milestone".to_string(),
+            kind: ProposalKind::Vote,
+            depends_on: Some(5),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_can_finalize() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(1),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        assert!(!contract.can_finalize(id));
+
+        testing_env!(context
+            .block_timestamp(1_000_000_000 * 24 * 60 * 60 * 8)
+            .build());
+        assert!(contract.can_finalize(id));
+        contract.act_proposal(id, Action::Finalize, None, None);
+        // Expired proposals are done.
+        assert!(!contract.can_finalize(id));
+    }
+
+    #[test]
+    fn test_can_finalize_failed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert!(!contract.can_finalize(id));
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(id);
+        // Still approved, so it can be retried.
+        assert!(contract.can_finalize(id));
+    }
+
+    #[test]
+    fn test_execution_retry_limit() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.max_execution_retries = 1;
+        contract.config.set(&config);
+        let id = create_function_call_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        let fail_execution = |context: &mut VMContextBuilder, contract: &mut Contract| {
+            testing_env!(
+                context.predecessor_account_id(accounts(0)).build(),
+                near_sdk::VMConfig::default(),
+                near_sdk::RuntimeFeesConfig::default(),
+                Default::default(),
+                vec![PromiseResult::Failed],
+            );
+            contract.on_proposal_callback(id);
+        };
+        fail_execution(&mut context, &mut contract);
+        assert_eq!(contract.get_proposal(id).proposal.retry_count, 1);
+
+        // The single retry is executed and fails again.
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        assert!(!created_function_calls().is_empty());
+        fail_execution(&mut context, &mut contract);
+        assert_eq!(contract.get_proposal(id).proposal.retry_count, 2);
+
+        // No more retries, finalizing expires the proposal and returns the bond.
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        assert!(created_function_calls().is_empty());
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Expired
+        );
+        assert_eq!(contract.locked_amount, 0);
+    }
+
+    #[test]
+    fn test_get_approval_progress() {
+        let mut context = VMContextBuilder::new();
+        let council = vec![accounts(1), accounts(2), accounts(3), accounts(4)];
+        let mut contract = setup_contract_with_council(&mut context, council);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        // A role that can't approve transfers is ignored, even though nothing is needed from it.
+        let mut policy = contract.policy.get().unwrap().to_policy();
+        policy.add_or_update_role(&RolePermission {
+            name: "reviewers".to_string(),
+            kind: RoleKind::Group(vec![accounts(5)].into_iter().collect()),
+            permissions: vec!["*:VoteReject".to_string()].into_iter().collect(),
+            vote_policy: vec![(
+                "transfer".to_string(),
+                VotePolicy {
+                    weight_kind: WeightKind::RoleWeight,
+                    quorum: U128(0),
+                    threshold: WeightOrRatio::Weight(U128(0)),
+                    spam_threshold: U128(0),
+                    max_weight_bps: None,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        });
+        contract.policy.set(&VersionedPolicy::Current(policy));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_proposal(&mut context, &mut contract);
+        assert_eq!(contract.get_approval_progress(id), (U128(0), U128(3)));
+
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteReject,
+        );
+        assert_eq!(contract.get_approval_progress(id), (U128(1), U128(3)));
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(contract.get_approval_progress(id), (U128(2), U128(3)));
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+
+        // Reaching the needed weight approves, after which there is no progress to report.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+        assert_eq!(contract.get_approval_progress(id), (U128(0), U128(0)));
+    }
+
+    #[test]
+    fn test_total_supply_quorum() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3)]);
+        set_governance_token(&mut contract, accounts(4));
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::TokenWeight,
+                quorum: U128(0),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        contract.refresh_total_supply();
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(4), "ft_total_supply".to_string()),
+                (env::current_account_id(), "on_total_supply".to_string()),
+            ]
+        );
+        testing_env!(context.block_index(10).build());
+        contract.on_total_supply(U128(1_000));
+        assert_eq!(
+            contract.get_total_supply(),
+            Some(CachedTotalSupply {
+                amount: U128(1_000),
+                block_height: 10,
+            })
+        );
+
+        let id = create_proposal(&mut context, &mut contract);
+        // More than half of the supply is needed.
+        assert_eq!(contract.get_approval_progress(id), (U128(0), U128(501)));
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(contract.get_approval_progress(id), (U128(1), U128(501)));
+        assert!(!near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("total_supply_stale")));
+
+        testing_env!(context
+            .block_index(11 + DEFAULT_TOTAL_SUPPLY_MAX_AGE)
+            .build());
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("total_supply_stale")));
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+    }
+
+    fn create_kind_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        kind: ProposalKind,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "token supply".to_string(),
+            kind,
+            ..Default::default()
+        })
+    }
+
+    fn set_governance_token(contract: &mut Contract, token_id: AccountId) {
+        let mut config = contract.get_config();
+        config.governance_token = Some(token_id);
+        contract.config.set(&config);
+    }
+
+    #[test]
+    fn test_token_mint_and_burn() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_governance_token(&mut contract, accounts(4));
+        let mint = ProposalKind::TokenMint {
+            receiver_id: accounts(2),
+            amount: U128(1_000),
+        };
+        let burn = ProposalKind::TokenBurn { amount: U128(500) };
+        for (kind, method_name) in [(mint, "mint"), (burn, "burn")] {
+            let id = create_kind_proposal(&mut context, &mut contract, kind);
+            testing_env!(context.build());
+            contract.act_proposal(id, Action::VoteApprove, None, None);
+            assert_eq!(
+                created_function_calls(),
+                vec![
+                    (accounts(4), method_name.to_string()),
+                    (
+                        env::current_account_id(),
+                        "on_proposal_callback".to_string()
+                    ),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_token_mint_failed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_governance_token(&mut contract, accounts(4));
+        let id = create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::TokenMint {
+                receiver_id: accounts(2),
+                amount: U128(1_000),
+            },
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(id);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Failed
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_GOVERNANCE_TOKEN")]
+    fn test_token_burn_without_governance_token() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::TokenBurn { amount: U128(500) },
+        );
+    }
+
+    fn execute_staking(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        kind: ProposalKind,
+        method_name: &str,
+    ) {
+        let id = create_kind_proposal(context, contract, kind);
+        testing_env!(context.build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(3), method_name.to_string()),
+                (
+                    env::current_account_id(),
+                    "on_proposal_callback".to_string()
+                ),
+            ]
+        );
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(id);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+    }
+
+    #[test]
+    fn test_stake_delegate_and_unstake() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        for _ in 0..2 {
+            execute_staking(
+                &mut context,
+                &mut contract,
+                ProposalKind::StakeDelegate {
+                    pool_id: accounts(3),
+                    amount: U128(to_yocto("5")),
+                },
+                "deposit_and_stake",
+            );
+        }
+        assert_eq!(
+            contract.get_delegations(),
+            vec![(accounts(3), U128(to_yocto("10")))]
+                .into_iter()
+                .collect()
+        );
+
+        execute_staking(
+            &mut context,
+            &mut contract,
+            ProposalKind::StakeUnstake {
+                pool_id: accounts(3),
+                amount: U128(to_yocto("4")),
+            },
+            "unstake",
+        );
+        assert_eq!(
+            contract.get_delegations()[&accounts(3)],
+            U128(to_yocto("6"))
+        );
+
+        testing_env!(context.build());
+        contract.claim_staking_rewards(accounts(3));
+        assert_eq!(
+            created_function_calls(),
+            vec![(accounts(3), "withdraw_all".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_stake_delegate_failed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_kind_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::StakeDelegate {
+                pool_id: accounts(3),
+                amount: U128(to_yocto("5")),
+            },
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(id);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Failed
+        );
+        assert!(contract.get_delegations().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNKNOWN_STAKING_POOL")]
+    fn test_claim_staking_rewards_unknown_pool() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.claim_staking_rewards(accounts(3));
+    }
+
+    fn create_stream(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        let id = create_kind_proposal(
+            context,
+            contract,
+            ProposalKind::StreamCreate {
+                token_id: String::from(OLD_BASE_TOKEN),
+                receiver_id: accounts(2),
+                total: U128(to_yocto("10")),
+                start_block: 100,
+                end_block: 200,
+            },
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        id
+    }
+
+    #[test]
+    fn test_stream_withdraw() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let locked = contract.locked_amount;
+        let id = create_stream(&mut context, &mut contract);
+        // The bond is returned on approval, the streamed amount stays locked.
+        assert_eq!(contract.locked_amount, locked + to_yocto("10"));
+
+        // Mid-stream, anyone can withdraw what vested so far to the receiver.
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_index(150)
+            .build());
+        contract.withdraw_stream(id);
+        assert_eq!(transfers_to(accounts(2)), vec![to_yocto("5")]);
+        assert_eq!(
+            contract.get_stream(id).unwrap().withdrawn,
+            U128(to_yocto("5"))
+        );
+
+        // Fully vested after the end block.
+        testing_env!(context.block_index(1_000).build());
+        contract.withdraw_stream(id);
+        assert_eq!(transfers_to(accounts(2)), vec![to_yocto("5")]);
+        assert_eq!(
+            contract.get_stream(id).unwrap().withdrawn,
+            U128(to_yocto("10"))
+        );
+        assert_eq!(contract.locked_amount, locked);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOTHING_TO_WITHDRAW")]
+    fn test_stream_withdraw_before_start() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_stream(&mut context, &mut contract);
+        testing_env!(context.block_index(100).build());
+        contract.withdraw_stream(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOTHING_TO_WITHDRAW")]
+    fn test_stream_withdraw_fully_vested_twice() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_stream(&mut context, &mut contract);
+        testing_env!(context.block_index(200).build());
+        contract.withdraw_stream(id);
+        contract.withdraw_stream(id);
+    }
+
+    #[test]
+    fn test_stream_withdraw_failed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_stream(&mut context, &mut contract);
+        let locked = contract.locked_amount;
+        testing_env!(context.block_index(150).build());
+        contract.withdraw_stream(id);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_stream_withdraw_callback(id, U128(to_yocto("5")));
+        assert_eq!(contract.get_stream(id).unwrap().withdrawn, U128(0));
+        assert_eq!(contract.locked_amount, locked);
+    }
+
+    #[test]
+    fn test_ft_on_transfer() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        // Tokens no proposal pays aren't cached.
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let refund = contract.ft_on_transfer(accounts(2), U128(100), "".to_string());
+        assert!(matches!(refund, near_sdk::PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.get_ft_balance(accounts(3)), None);
+        assert!(contract.ft_tokens.is_empty());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create_ft_transfer_proposal(&mut context, &mut contract, false);
+        assert_eq!(contract.ft_tokens.to_vec(), vec![accounts(3)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.ft_on_transfer(accounts(2), U128(100), "".to_string());
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(100)));
+
+        // An earmarked deposit adds to the cached balance the same way.
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.ft_on_transfer(
+            accounts(2),
+            U128(50),
+            format!(r#"{{"proposal_id": "{}"}}"#, id),
+        );
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(150)));
+        assert_eq!(contract.ft_tokens.to_vec(), vec![accounts(3)]);
+    }
+
+    #[test]
+    fn test_ft_on_transfer_unknown_message() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.on_ft_balance(accounts(3), U128(1_000));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        for msg in ["not json", r#"{"proposal_id": "42"}"#, r#"{"other": 1}"#] {
+            let refund = contract.ft_on_transfer(accounts(2), U128(10), msg.to_string());
+            assert!(matches!(refund, near_sdk::PromiseOrValue::Value(U128(0))));
+        }
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(1_030)));
+    }
+
+    #[test]
+    fn test_treasury_summary() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.on_ft_balance(accounts(3), U128(1_000));
+        lock_stake(&mut context, &mut contract, accounts(2), 10);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        // Stays bonded while in progress.
+        create_proposal(&mut context, &mut contract);
+        let mut config = contract.get_config();
+        config.claimable_payouts = true;
+        contract.config.set(&config);
+        let claimable = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(claimable, Action::VoteApprove, None, None);
+        config.claimable_payouts = false;
+        contract.config.set(&config);
+        let ft_payout = create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.act_proposal(ft_payout, Action::VoteApprove, None, None);
+
+        let summary = contract.get_treasury_summary();
+        assert_eq!(summary.total, U128(env::account_balance()));
+        // The bond of the token payout is returned once it's sent.
+        assert_eq!(summary.bonds, U128(to_yocto("2")));
+        assert_eq!(summary.locked_stake, U128(to_yocto("10")));
+        assert_eq!(
+            summary.reserved,
+            vec![
+                (OLD_BASE_TOKEN.to_string(), U128(to_yocto("100"))),
+                (accounts(3).to_string(), U128(100)),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(
+            summary.available[OLD_BASE_TOKEN],
+            U128(summary.total.0 - summary.storage.0 - to_yocto("112"))
+        );
+        assert_eq!(summary.available[accounts(3).as_str()], U128(900));
+
+        // Once sent, the transfer is no longer reserved.
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(ft_payout);
+        let summary = contract.get_treasury_summary();
+        assert_eq!(summary.bonds, U128(to_yocto("1")));
+        assert!(!summary.reserved.contains_key(accounts(3).as_str()));
+        assert_eq!(summary.reserved[OLD_BASE_TOKEN], U128(to_yocto("100")));
+    }
+
+    #[test]
+    fn test_claimable_payouts() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.claimable_payouts = true;
+        contract.config.set(&config);
+        let near_payout = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(near_payout, Action::VoteApprove, None, None);
+        let ft_payout = create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.act_proposal(ft_payout, Action::VoteApprove, None, None);
+        // Nothing is sent to the receiver, bonds are returned.
+        assert!(created_function_calls().is_empty());
+        assert_eq!(contract.locked_amount, to_yocto("100"));
+        let claimable = contract.get_claimable(accounts(2));
+        assert_eq!(claimable[OLD_BASE_TOKEN], U128(to_yocto("100")));
+        assert_eq!(claimable[accounts(3).as_str()], U128(100));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim(String::from(OLD_BASE_TOKEN));
+        assert_eq!(contract.locked_amount, 0);
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == accounts(2)));
+
+        testing_env!(context.build());
+        contract.claim(accounts(3).to_string());
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(3), "ft_transfer".to_string()),
+                (env::current_account_id(), "on_claim_callback".to_string()),
+            ]
+        );
+        assert!(contract.get_claimable(accounts(2)).is_empty());
+
+        // A failed transfer is credited back.
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_claim_callback(accounts(2), accounts(3).to_string(), U128(100));
+        assert_eq!(
+            contract.get_claimable(accounts(2))[accounts(3).as_str()],
+            U128(100)
+        );
+    }
+
+    fn create_transfer_percent_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        token_id: &str,
+        bps: u16,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "percent payout".to_string(),
+            kind: ProposalKind::TransferPercent {
+                token_id: token_id.to_string(),
+                receiver_id: accounts(2),
+                bps,
+            },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_transfer_percent() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.claimable_payouts = true;
+        contract.config.set(&config);
+        contract.on_ft_balance(accounts(3), U128(1_000));
+        let id = create_transfer_percent_proposal(
+            &mut context,
+            &mut contract,
+            accounts(3).as_str(),
+            1_000,
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_claimable(accounts(2))[accounts(3).as_str()],
+            U128(100)
+        );
+
+        // Computed from the balance at execution, less what is already claimable.
+        contract.on_ft_balance(accounts(3), U128(5_000));
+        let id = create_transfer_percent_proposal(
+            &mut context,
+            &mut contract,
+            accounts(3).as_str(),
+            1_000,
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_claimable(accounts(2))[accounts(3).as_str()],
+            U128(590)
+        );
+
+        // Whole balance.
+        let id = create_transfer_percent_proposal(
+            &mut context,
+            &mut contract,
+            accounts(3).as_str(),
+            10_000,
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            contract.get_claimable(accounts(2))[accounts(3).as_str()],
+            U128(5_000)
+        );
+    }
+
+    #[test]
+    fn test_transfer_percent_near() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id =
+            create_transfer_percent_proposal(&mut context, &mut contract, OLD_BASE_TOKEN, 2_500);
+        testing_env!(context.build());
+        let available = contract.get_available_amount().0;
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        let sent: Vec<Balance> = near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id == accounts(2))
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::VmAction::Transfer { deposit } => Some(deposit),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sent.len(), 1);
+        // Storing the vote uses a bit of the balance before the payout.
+        assert!(sent[0] <= available / 4 && sent[0] > available / 4 - to_yocto("1") / 1_000);
+    }
+
+    #[test]
+    fn test_transfer_percent_reserved() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.on_ft_balance(accounts(3), U128(1_000));
+        let id = create_transfer_percent_proposal(
+            &mut context,
+            &mut contract,
+            accounts(3).as_str(),
+            1_000,
+        );
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(contract.pending_transfers[&accounts(3)], 100);
+        assert_eq!(
+            contract.internal_spendable_balance(&accounts(3).to_string()),
+            900
+        );
+
+        // Released once sent, a retry computes the amount again.
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(id);
+        assert!(contract.pending_transfers.is_empty());
+        assert_eq!(contract.get_proposal(id).proposal.percent_amount, None);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        assert_eq!(contract.pending_transfers[&accounts(3)], 100);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(id);
+        assert!(contract.pending_transfers.is_empty());
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(900)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_BPS")]
+    fn test_transfer_percent_invalid_bps() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        create_transfer_percent_proposal(&mut context, &mut contract, OLD_BASE_TOKEN, 10_001);
+    }
+
+    fn create_multi_token_transfer_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        transfers: Vec<(String, U128)>,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "grant package".to_string(),
+            kind: ProposalKind::MultiTokenTransfer {
+                receiver_id: accounts(2),
+                transfers,
+            },
+            ..Default::default()
+        })
+    }
+
+    fn grant_package() -> Vec<(String, U128)> {
+        vec![
+            (String::from(OLD_BASE_TOKEN), U128(to_yocto("10"))),
+            (accounts(3).to_string(), U128(100)),
+        ]
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VOTE_OVERFLOW")]
+    fn test_vote_overflow() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(3)]);
+        let id = create_proposal(&mut context, &mut contract);
+        let mut proposal: Proposal = contract.proposals.get(&id).unwrap().into();
+        proposal
+            .vote_counts
+            .insert("council".to_string(), [u128::MAX, 0, 0]);
+        contract
+            .proposals
+            .insert(&id, &VersionedProposal::V2(proposal));
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            id,
+            Action::VoteApprove,
+        );
+    }
+
+    #[test]
+    fn test_native_payout_event() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        let event = near_sdk::test_utils::get_logs()
+            .into_iter()
+            .find(|log| log.starts_with("EVENT_JSON:") && log.contains("native_payout"))
+            .unwrap();
+        assert!(event.contains(&format!("\"proposal_id\":\"{}\"", id)));
+        assert!(event.contains("\"memo\":\"test\""));
+    }
+
+    #[test]
+    fn test_multi_token_transfer() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_multi_token_transfer_proposal(&mut context, &mut contract, grant_package());
+        testing_env!(context.build());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(3), "ft_transfer".to_string()),
+                (
+                    env::current_account_id(),
+                    "on_proposal_callback".to_string()
+                ),
+            ]
+        );
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id == accounts(2))
+            .flat_map(|receipt| receipt.actions)
+            .any(|action| matches!(
+                action,
+                near_sdk::mock::VmAction::Transfer { deposit } if deposit == to_yocto("10")
+            )));
+        assert_eq!(contract.pending_transfers[&accounts(3)], 100);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful(vec![]),
+                PromiseResult::Successful(vec![]),
+            ],
+        );
+        contract.on_proposal_callback(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+        assert_eq!(proposal.failed_leg, None);
+        assert_eq!(proposal.bond, U128(0));
+        assert!(contract.pending_transfers.is_empty());
+    }
+
+    #[test]
+    fn test_multi_token_transfer_leg_failed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let id = create_multi_token_transfer_proposal(&mut context, &mut contract, grant_package());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Failed);
+        assert_eq!(proposal.failed_leg, Some(1));
+        assert!(contract.pending_transfers.is_empty());
+    }
+
+    #[test]
+    fn test_multi_token_transfer_retry_failed_leg() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.ft_balances.insert(&accounts(3), &1000);
+        let id = create_multi_token_transfer_proposal(&mut context, &mut contract, grant_package());
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(id);
+        assert_eq!(contract.get_proposal(id).proposal.paid_legs, vec![0]);
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(1000)));
+
+        // Only the failed token transfer is sent again, the $NEAR was already paid.
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (accounts(3), "ft_transfer".to_string()),
+                (
+                    env::current_account_id(),
+                    "on_proposal_callback".to_string()
+                ),
+            ]
+        );
+        assert!(!near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == accounts(2)));
+        assert_eq!(contract.pending_transfers[&accounts(3)], 100);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+        assert_eq!(proposal.paid_legs, vec![0, 1]);
+        assert!(contract.pending_transfers.is_empty());
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(900)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TRANSFERS_LENGTH")]
+    fn test_multi_token_transfer_empty() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        create_multi_token_transfer_proposal(&mut context, &mut contract, vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TRANSFERS_LENGTH")]
+    fn test_multi_token_transfer_too_many() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let transfers = (0..=MAX_TRANSFER_LEGS)
+            .map(|i| (format!("token{}.near", i), U128(1)))
+            .collect();
+        create_multi_token_transfer_proposal(&mut context, &mut contract, transfers);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOTHING_TO_CLAIM")]
+    fn test_claim_nothing() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        contract.claim(String::from(OLD_BASE_TOKEN));
+    }
+
+    #[test]
+    fn test_slash_approvers_of_removed_proposal() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract_with_council(
+            &mut context,
+            vec![
+                accounts(1),
+                accounts(2),
+                accounts(3),
+                accounts(4),
+                accounts(5),
+            ],
+        );
+        let mut config = contract.get_config();
+        config.slashing = Some(SlashingConfig {
+            basis_points: 1_000,
+            max_total: U128(to_yocto("15") / 10),
+        });
+        contract.config.set(&config);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        for account_id in [accounts(1), accounts(2), accounts(3)] {
+            lock_stake(&mut context, &mut contract, account_id, 10);
+        }
+        let id = create_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(2),
+            id,
+            Action::VoteApprove,
+        );
+        for account_id in [accounts(3), accounts(4), accounts(5)] {
+            vote(
+                &mut context,
+                &mut contract,
+                account_id,
+                id,
+                Action::VoteRemove,
+            );
+        }
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Removed
+        );
+        let staked = |account_id| contract.get_lockup(account_id).unwrap().amount;
+        assert_eq!(staked(accounts(1)), U128(to_yocto("9")));
+        // The total slash is capped.
+        assert_eq!(staked(accounts(2)), U128(to_yocto("95") / 10));
+        assert_eq!(staked(accounts(3)), U128(to_yocto("10")));
+        // Slashed stake goes to the treasury, the bond is still locked.
+        assert_eq!(contract.locked_amount, to_yocto("295") / 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_SLASHING")]
+    fn test_invalid_slashing_config() {
+        let mut config = Config::test_config();
+        config.slashing = Some(SlashingConfig {
+            basis_points: 10_001,
+            max_total: U128(0),
+        });
+        config.assert_valid();
+    }
+
+    fn native_transfers() -> Vec<(AccountId, Balance, Vec<u64>)> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                let receipt_indices = receipt.receipt_indices;
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        near_sdk::mock::VmAction::Transfer { deposit } => {
+                            Some((receiver_id.clone(), deposit, receipt_indices.clone()))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.transfer_fee_bps = 250;
+        config.fee_receiver = Some(accounts(4));
+        contract.config.set(&config);
+        let id = create_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        let transfers = native_transfers();
+        assert_eq!(transfers.len(), 2);
+        let (fee_receiver, fee, _) = &transfers[0];
+        assert_eq!((fee_receiver, *fee), (&accounts(4), to_yocto("100") / 40));
+        let (receiver_id, amount, after) = &transfers[1];
+        assert_eq!(
+            (receiver_id, *amount),
+            (&accounts(2), to_yocto("100") / 40 * 39)
+        );
+        // Both are sent at once, so the callback gets the result of each.
+        assert!(after.is_empty());
+    }
+
+    fn set_transfer_fee(contract: &mut Contract) {
+        let mut config = contract.get_config();
+        config.transfer_fee_bps = 250;
+        config.fee_receiver = Some(accounts(4));
+        contract.config.set(&config);
+    }
+
+    #[test]
+    fn test_transfer_fee_not_paid_again_on_retry() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_transfer_fee(&mut contract);
+        let id = create_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(native_transfers().len(), 2);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed],
+        );
+        contract.on_proposal_callback(id);
+        assert!(contract.get_proposal(id).proposal.fee_paid);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        let transfers = native_transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(
+            (&transfers[0].0, transfers[0].1),
+            (&accounts(2), to_yocto("100") / 40 * 39)
+        );
+    }
+
+    #[test]
+    fn test_transfer_fee_with_registration() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_transfer_fee(&mut contract);
+        let id = create_ft_transfer_proposal(&mut context, &mut contract, true);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        let calls: Vec<(AccountId, String, Vec<u64>)> = receipts
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                let receipt_indices = receipt.receipt_indices;
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        near_sdk::mock::VmAction::FunctionCall { method_name, .. } => {
+                            Some((receiver_id.clone(), method_name, receipt_indices.clone()))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect();
+        // The transfer waits for the registration, the callback for the fee and the transfer.
+        assert_eq!(
+            calls,
+            vec![
+                (accounts(3), "ft_transfer".to_string(), vec![]),
+                (accounts(3), "storage_deposit".to_string(), vec![]),
+                (accounts(3), "ft_transfer".to_string(), vec![1]),
+                (
+                    env::current_account_id(),
+                    "on_proposal_callback".to_string(),
+                    vec![0, 2]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ft_balance_spent_on_payout() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_transfer_fee(&mut contract);
+        contract.ft_balances.insert(&accounts(3), &1000);
+        let id = create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        // The fee and the rest are taken off once their transfers succeeded.
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(1000)));
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful(vec![]),
+                PromiseResult::Successful(vec![]),
+            ],
+        );
+        contract.on_proposal_callback(id);
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(900)));
+
+        let mut config = contract.get_config();
+        config.claimable_payouts = true;
+        config.fee_receiver = None;
+        contract.config.set(&config);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let id = create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(900)));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim(accounts(3).to_string());
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(800)));
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_claim_callback(accounts(2), accounts(3).to_string(), U128(100));
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(900)));
+    }
+
+    #[test]
+    fn test_transfer_fee_failed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        set_transfer_fee(&mut contract);
+        contract.ft_balances.insert(&accounts(3), &1000);
+        let id = create_ft_transfer_proposal(&mut context, &mut contract, false);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed, PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(id);
+        // Only the transfer to the receiver left the treasury.
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Failed);
+        assert!(!proposal.fee_paid);
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(902)));
+
+        // A retry only sends the fee, as it was when first executed.
+        let mut config = contract.get_config();
+        config.transfer_fee_bps = 500;
+        config.fee_receiver = Some(accounts(5));
+        contract.config.set(&config);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None, None);
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        let transfers: Vec<String> = receipts
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id == accounts(3))
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::VmAction::FunctionCall { args, .. } => {
+                    Some(String::from_utf8(args).unwrap())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(transfers.len(), 1);
+        assert!(transfers[0].contains(accounts(4).as_str()));
+        assert!(transfers[0].contains("\"2\""));
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_proposal_callback(id);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+        assert!(proposal.fee_paid);
+        assert_eq!(contract.get_ft_balance(accounts(3)), Some(U128(900)));
+    }
+
+    #[test]
+    fn test_no_transfer_fee() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        let mut config = contract.get_config();
+        config.fee_receiver = Some(accounts(4));
+        contract.config.set(&config);
+        let id = create_proposal(&mut context, &mut contract);
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        let transfers = native_transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].0, accounts(2));
+        assert_eq!(transfers[0].1, to_yocto("100"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TRANSFER_FEE")]
+    fn test_transfer_fee_without_receiver() {
+        let mut config = Config::test_config();
+        config.transfer_fee_bps = 100;
+        config.assert_valid();
+    }
+
+    #[test]
+    fn test_get_proposals_by_proposer() {
+        let mut context = VMContextBuilder::new();
+        let mut contract =
+            setup_contract_with_council(&mut context, vec![accounts(1), accounts(2)]);
+        add_council_permission(&mut contract, "*:RemoveProposal");
+        let own: Vec<u64> = (0..4)
+            .map(|_| create_proposal(&mut context, &mut contract))
+            .collect();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let other = create_tagged_proposal(&mut context, &mut contract, vec![]);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(own[1], Action::RemoveProposal, None, None);
+
+        let ids = |account_id, from_index, limit| -> Vec<u64> {
+            contract
+                .get_proposals_by_proposer(account_id, from_index, limit)
+                .into_iter()
+                .map(|output| output.id)
+                .collect()
+        };
+        assert_eq!(ids(accounts(1), 0, 10), vec![own[0], own[2], own[3]]);
+        assert_eq!(ids(accounts(1), 1, 1), vec![own[2]]);
+        assert_eq!(ids(accounts(1), 3, 10), Vec::<u64>::new());
+        assert_eq!(ids(accounts(2), 0, 10), vec![other]);
+        assert!(ids(accounts(3), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_get_proposals_by_kind() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        add_council_permission(&mut contract, "*:RemoveProposal");
+        let first = create_proposal(&mut context, &mut contract);
+        create_tagged_proposal(&mut context, &mut contract, vec![]);
+        let removed = create_proposal(&mut context, &mut contract);
+        create_function_call_proposal(&mut context, &mut contract);
+        let last = create_proposal(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(removed, Action::RemoveProposal, None, None);
+
+        let ids = |label: &str, from_index, limit| -> Vec<u64> {
+            contract
+                .get_proposals_by_kind(label.to_string(), from_index, limit)
+                .into_iter()
+                .map(|output| output.id)
+                .collect()
+        };
+        assert_eq!(ids("transfer", 0, 10), vec![first, last]);
+        assert_eq!(ids("transfer", 1, 10), vec![last]);
+        assert_eq!(ids("vote", 0, 10).len(), 1);
+        assert!(ids("policy", 0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNKNOWN_KIND_LABEL")]
+    fn test_get_proposals_by_unknown_kind() {
+        let mut context = VMContextBuilder::new();
+        let contract = setup_contract(&mut context);
+        contract.get_proposals_by_kind("transfers".to_string(), 0, 10);
+    }
+
+    /// Vote keys and signatures of `alice.near:0:<voter>:VoteApprove:1`, signed off-chain.
+    const SIGNED_APPROVALS: [(&str, &str, &str); 3] = [
+        (
+            "bob",
+            "8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c",
+            "605e27f1e2aed36b812889143ee41af176ce2571ef4c4f14b15e6b6320e9f9ac907d45d9589f028986aa647e77155d90ecc15014f4baefcb3c12be4755dab608",
+        ),
+        (
+            "charlie",
+            "8139770ea87d175f56a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394",
+            "f57e6bf1f3558f5d095e631f93be69f8114cefedd978bd3a64d462731d48fc894e4db2693b85357cf59516de7af544b0fff0d6b3edf24f7702fdafa2ff27dc0d",
+        ),
+        (
+            "danny",
+            "ed4928c628d1c2c6eae90338905995612959273a5c63f93636c14614ac8737d1",
+            "221dae8babfec8843c14070598610f63cc031eca4fb7853280c649effabd05cf35a5b5c5f8ed1d006694d71322ffbe004d48de724edf48a85de94444037bb102",
+        ),
+    ];
+
+    /// Sets up a council of the signers of `SIGNED_APPROVALS`, with every voter's key registered.
+    fn setup_vote_keys(context: &mut VMContextBuilder) -> Contract {
+        let mut contract =
+            setup_contract_with_council(context, vec![accounts(1), accounts(2), accounts(3)]);
+        set_default_vote_policy(
+            &mut contract,
+            VotePolicy {
+                weight_kind: WeightKind::RoleWeight,
+                quorum: U128(3),
+                threshold: WeightOrRatio::Ratio(1, 2),
+                spam_threshold: U128(0),
+                max_weight_bps: None,
+            },
+        );
+        for (account_id, public_key, _) in SIGNED_APPROVALS {
+            testing_env!(context
+                .predecessor_account_id(account_id.parse().unwrap())
+                .build());
+            let public_key = [vec![0], hex::decode(public_key).unwrap()].concat();
+            contract.register_vote_key(PublicKey::try_from(public_key).unwrap());
+        }
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract
+    }
+
+    /// Sets up a proposal voted on by signed approvals, with every voter's key registered.
+    fn setup_signed_voting(context: &mut VMContextBuilder) -> Contract {
+        let mut contract = setup_vote_keys(context);
+        create_proposal(context, &mut contract);
+        // Submitted by a relayer that is not a member.
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract
+    }
+
+    fn signed_approval(index: usize) -> SignedVote {
+        let (account_id, _, signature) = SIGNED_APPROVALS[index];
+        SignedVote {
+            account_id: account_id.parse().unwrap(),
+            action: Action::VoteApprove,
+            nonce: U64(1),
+            signature: near_sdk::json_types::Base64VecU8(hex::decode(signature).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_act_proposal_signed() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_signed_voting(&mut context);
+        assert_eq!(
+            contract.act_proposal_signed(0, (0..3).map(signed_approval).collect()),
+            vec![true, true, true]
+        );
+        let proposal = contract.get_proposal(0).proposal;
+        assert_eq!(proposal.votes.len(), 3);
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+    }
+
+    #[test]
+    fn test_signed_vote_gas() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_signed_voting(&mut context);
+        let used = env::used_gas();
+        contract.act_proposal_signed(0, vec![signed_approval(0)]);
+        // Only host calls are counted here, the signature verification takes about 7 Tgas.
+        let used = env::used_gas().0 - used.0;
+        assert!(used > 0 && used + 7_000_000_000_000 < GAS_PER_SIGNED_VOTE.0);
+        assert!(GAS_PER_SIGNED_VOTE.0 * MAX_SIGNED_VOTES as u64 <= 250_000_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOO_MANY_SIGNED_VOTES")]
+    fn test_act_proposal_signed_too_many() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_signed_voting(&mut context);
+        contract.act_proposal_signed(
+            0,
+            (0..=MAX_SIGNED_VOTES).map(|_| signed_approval(0)).collect(),
+        );
+    }
+
+    #[test]
+    fn test_act_proposal_signed_bad_signature() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_signed_voting(&mut context);
+        let mut tampered = signed_approval(1);
+        tampered.signature.0[10] ^= 1;
+        // Signed by someone else.
+        let mut forged = signed_approval(0);
+        forged.account_id = accounts(3);
+        assert_eq!(
+            contract.act_proposal_signed(0, vec![signed_approval(0), tampered, forged]),
+            vec![true, false, false]
+        );
+        let proposal = contract.get_proposal(0).proposal;
+        assert_eq!(proposal.votes.len(), 1);
+        assert!(proposal.votes.contains_key(&accounts(1)));
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+    }
+
+    #[test]
+    fn test_act_proposal_signed_replayed_nonce() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_signed_voting(&mut context);
+        assert_eq!(
+            contract.act_proposal_signed(0, vec![signed_approval(0), signed_approval(0)]),
+            vec![true, false]
+        );
+        assert_eq!(
+            contract.act_proposal_signed(0, vec![signed_approval(0), signed_approval(1)]),
+            vec![false, true]
+        );
+        let proposal = contract.get_proposal(0).proposal;
+        assert_eq!(proposal.votes.len(), 2);
+        assert_eq!(
+            proposal.vote_counts.get("council").unwrap()[Vote::Approve as usize],
+            2
+        );
+    }
+
+    fn approved_input() -> ProposalInput {
+        ProposalInput {
+            description: "pre-approved".to_string(),
+            kind: ProposalKind::Vote,
+            ..Default::default()
+        }
+    }
+
+    /// Signatures of `proposal_approval_message(0, &approved_input())` by the keys of
+    /// `SIGNED_APPROVALS`, made off-chain.
+    const PROPOSAL_APPROVALS: [&str; 3] = [
+        "28e19df1bdfcbc8af189f8cd4c7115e830501bd084ed39a5390b0a279c87df87698d1e4fd1b6e3cd110cafc4a714d56a94fe39896ab1ce64c533d4c91f524601",
+        "b2b9d76d4844eb2f101625043945dfe8afff71bf707e1c679717a8c11c091a7dbc8eeca6f5ea2370f9d699d61f58fb84afb6f2ea8445dbe9c851c7fb547e180a",
+        "7493fea9ff8d43124db9e51eea4b4c3722ca7dc3f48c4c92aa0678543bd1c88e6cad9ccaf7b70e1dff60a7bce96bd3a7c6ac1ecee68045d00c9c7e80ce91ff03",
+    ];
+
+    fn proposal_approval(index: usize) -> SignedApproval {
+        SignedApproval {
+            account_id: SIGNED_APPROVALS[index].0.parse().unwrap(),
+            signature: near_sdk::json_types::Base64VecU8(
+                hex::decode(PROPOSAL_APPROVALS[index]).unwrap(),
+            ),
+        }
+    }
+
+    #[test]
+    fn test_add_proposal_with_approvals() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_vote_keys(&mut context);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let id = contract.add_proposal_with_approvals(
+            approved_input(),
+            vec![proposal_approval(1), proposal_approval(2)],
+        );
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.votes.len(), 2);
+        assert!(proposal.votes.contains_key(&accounts(2)));
+        assert!(proposal.votes.contains_key(&accounts(3)));
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+
+        // The proposer approves as usual to reach the quorum.
+        vote(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            id,
+            Action::VoteApprove,
+        );
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_APPROVAL")]
+    fn test_add_proposal_with_tampered_approval() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_vote_keys(&mut context);
+        let mut input = approved_input();
+        input.description = "changed after approval".to_string();
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal_with_approvals(input, vec![proposal_approval(1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_APPROVAL")]
+    fn test_add_proposal_with_replayed_approval() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_vote_keys(&mut context);
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal_with_approvals(approved_input(), vec![proposal_approval(1)]);
+        // Signed for the id the first proposal got.
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal_with_approvals(approved_input(), vec![proposal_approval(1)]);
+    }
 }
     /*#[test]
     fn test_basics() {
@@ -113,7 +7318,7 @@ mod tests {
         assert_eq!(contract.get_proposals(0, 10).len(), 1);
 
         let id = create_proposal(&mut context, &mut contract);
-        contract.act_proposal(id, Action::VoteApprove, None);
+        contract.act_proposal(id, Action::VoteApprove, None, None);
         assert_eq!(
             contract.get_proposal(id).proposal.status,
             ProposalStatus::Approved
@@ -124,7 +7329,7 @@ mod tests {
         testing_env!(context
             .block_timestamp(1_000_000_000 * 24 * 60 * 60 * 8)
             .build());
-        contract.act_proposal(id, Action::Finalize, None);
+        contract.act_proposal(id, Action::Finalize, None, None);
         assert_eq!(
             contract.get_proposal(id).proposal.status,
             ProposalStatus::Expired
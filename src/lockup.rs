@@ -0,0 +1,129 @@
+//! Stake locked for a fixed duration, granting more voting power to longer locks.
+
+use near_sdk::serde_json::json;
+use near_sdk::BlockHeight;
+
+use crate::events::emit_event;
+use crate::*;
+
+/// Longest lock that still increases the voting power, about four years of blocks.
+pub const MAX_LOCK_DURATION: BlockHeight = 126_144_000;
+
+/// Voting power multiplier of the longest lock, in percent.
+pub const MAX_LOCK_MULTIPLIER: u128 = 400;
+
+/// Stake locked by an account until given block.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeLockup {
+    pub amount: U128,
+    pub unlock_block: BlockHeight,
+}
+
+impl StakeLockup {
+    /// Voting power of this lockup at the current block.
+    pub fn weight(&self) -> Balance {
+        let remaining = self.unlock_block.saturating_sub(env::block_height());
+        self.amount.0 * lock_multiplier(remaining) / 100
+    }
+}
+
+/// Multiplier in percent for a lock with `remaining` blocks left.
+/// Grows linearly from 1x for an expired lock to `MAX_LOCK_MULTIPLIER` for `MAX_LOCK_DURATION`.
+pub fn lock_multiplier(remaining: BlockHeight) -> u128 {
+    let remaining = std::cmp::min(remaining, MAX_LOCK_DURATION) as u128;
+    100 + (MAX_LOCK_MULTIPLIER - 100) * remaining / MAX_LOCK_DURATION as u128
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Locks attached `amount` of $NEAR for `duration` blocks.
+    /// Locking again adds to the amount and keeps the later unlock block.
+    #[payable]
+    pub fn lock_stake(&mut self, amount: U128, duration: U64) -> StakeLockup {
+        assert!(amount.0 > 0, "ERR_INVALID_AMOUNT");
+        assert_eq!(env::attached_deposit(), amount.0, "ERR_INVALID_DEPOSIT");
+        let account_id = env::predecessor_account_id();
+        let unlock_block = env::block_height() + duration.0;
+        let lockup = match self.lockups.get(&account_id) {
+            Some(lockup) => StakeLockup {
+                amount: U128(lockup.amount.0 + amount.0),
+                unlock_block: std::cmp::max(lockup.unlock_block, unlock_block),
+            },
+            None => StakeLockup {
+                amount,
+                unlock_block,
+            },
+        };
+        self.lockups.insert(&account_id, &lockup);
+        self.locked_amount += amount.0;
+        self.locked_stake += amount.0;
+        lockup
+    }
+
+    /// Returns the whole locked stake of the caller once the lock has ended.
+    pub fn unlock_stake(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let lockup = self.lockups.get(&account_id).expect("ERR_NO_LOCKUP");
+        assert!(
+            env::block_height() >= lockup.unlock_block,
+            "ERR_STAKE_LOCKED"
+        );
+        self.lockups.remove(&account_id);
+        self.locked_amount -= lockup.amount.0;
+        self.locked_stake -= lockup.amount.0;
+        Promise::new(account_id).transfer(lockup.amount.0)
+    }
+}
+
+impl Contract {
+    /// Slashes the locked stake of the accounts that voted to approve given removed proposal,
+    /// if slashing is enabled. Slashed stake goes to the treasury. Returns the total slashed.
+    pub(crate) fn internal_slash_approvers(
+        &mut self,
+        proposal_id: u64,
+        proposal: &Proposal,
+    ) -> Balance {
+        let slashing = match self.config.get().unwrap().slashing {
+            Some(slashing) => slashing,
+            None => return 0,
+        };
+        let mut approvers: Vec<&AccountId> = proposal
+            .votes
+            .iter()
+            .filter(|(_, vote)| matches!(vote.vote, Vote::Approve))
+            .map(|(account_id, _)| account_id)
+            .collect();
+        // Deterministic order for when the cap is reached.
+        approvers.sort();
+        let mut total = 0;
+        for account_id in approvers {
+            let mut lockup = match self.lockups.get(account_id) {
+                Some(lockup) => lockup,
+                None => continue,
+            };
+            let amount = std::cmp::min(
+                lockup.amount.0 * slashing.basis_points as u128 / 10_000,
+                slashing.max_total.0 - total,
+            );
+            if amount == 0 {
+                continue;
+            }
+            lockup.amount = U128(lockup.amount.0 - amount);
+            self.lockups.insert(account_id, &lockup);
+            self.locked_amount -= amount;
+            self.locked_stake -= amount;
+            total += amount;
+            emit_event(
+                "stake_slashed",
+                json!({
+                    "proposal_id": U64(proposal_id),
+                    "account_id": account_id,
+                    "amount": U128(amount),
+                }),
+            );
+        }
+        total
+    }
+}
@@ -0,0 +1,306 @@
+//! Migration of the state written by the first deployed version of the contract.
+//!
+//! That version stored only `config`, `policy`, `last_proposal_id` and `proposals`, in this
+//! order, with the same storage keys as now. Its config and policy lacked the fields added
+//! since, so they are read with their old layout below and rewritten with defaults for the new
+//! fields. Proposals are kept in place: they are stored as `VersionedProposal::Default` and
+//! upgraded lazily when read. Their kinds are read as `OldProposalKind`, which keeps the layout
+//! of that version for the variants and the config and policy types they embed.
+//! Blobs are stored under their hash and need no migration. The factory info is kept.
+
+use std::collections::{HashMap, HashSet};
+
+use near_sdk::json_types::{Base58CryptoHash, Base64VecU8};
+
+use crate::types::{
+    default_auto_execute, default_callback_gas, default_high_risk_kinds,
+    default_max_execution_retries, default_max_function_call_deposit,
+    default_member_voting_delay_blocks, default_min_proposal_stake, default_min_voting_period,
+    default_proposer_can_vote, default_removal_grace_period, default_total_supply_max_age,
+};
+use crate::proposals::ActionCall;
+use crate::upgrade::internal_get_factory_info;
+use crate::*;
+
+/// Config of the first version.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct OldConfig {
+    pub name: String,
+    pub purpose: String,
+    pub metadata: Base64VecU8,
+}
+
+/// Vote policy of the first version, without spam threshold.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct OldVotePolicy {
+    pub weight_kind: WeightKind,
+    pub quorum: U128,
+    pub threshold: WeightOrRatio,
+}
+
+/// Role of the first version, with old vote policies.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct OldRolePermission {
+    pub name: String,
+    pub kind: RoleKind,
+    pub permissions: HashSet<String>,
+    pub vote_policy: HashMap<String, OldVotePolicy>,
+}
+
+/// Policy of the first version, without bond, per kind periods and token decimals.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct OldPolicy {
+    pub roles: Vec<OldRolePermission>,
+    pub default_vote_policy: OldVotePolicy,
+    pub proposal_period: U64,
+}
+
+/// Policy parameters of the first version, without per kind periods.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct OldPolicyParameters {
+    pub proposal_period: Option<U64>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum OldVersionedPolicy {
+    Default {
+        council: Vec<AccountId>,
+        stakers: Vec<AccountId>,
+    },
+    Current(OldPolicy),
+}
+
+/// Proposal kinds of the first version, with the old layout of the variants and of the
+/// config and policy types they embed. Variants added since are never stored as this.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum OldProposalKind {
+    ChangeConfig {
+        config: OldConfig,
+    },
+    ChangePolicy {
+        policy: OldVersionedPolicy,
+    },
+    AddMemberToRole {
+        member_id: AccountId,
+        role: String,
+    },
+    RemoveMemberFromRole {
+        member_id: AccountId,
+        role: String,
+    },
+    FunctionCall {
+        receiver_id: AccountId,
+        actions: Vec<ActionCall>,
+    },
+    UpgradeSelf {
+        hash: Base58CryptoHash,
+    },
+    UpgradeRemote {
+        receiver_id: AccountId,
+        method_name: String,
+        hash: Base58CryptoHash,
+    },
+    Transfer {
+        token_id: OldAccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        msg: Option<String>,
+    },
+    Vote,
+    ChangePolicyAddOrUpdateRole {
+        role: OldRolePermission,
+    },
+    ChangePolicyRemoveRole {
+        role: String,
+    },
+    ChangePolicyUpdateDefaultVotePolicy {
+        vote_policy: OldVotePolicy,
+    },
+    ChangePolicyUpdateParameters {
+        parameters: OldPolicyParameters,
+    },
+    Suggestion {
+        suggestion: String,
+    },
+}
+
+/// Contract state of the first version.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct OldContract {
+    pub config: LazyOption<OldConfig>,
+    pub policy: LazyOption<OldVersionedPolicy>,
+    pub last_proposal_id: u64,
+    pub proposals: LookupMap<u64, VersionedProposal>,
+}
+
+impl From<OldConfig> for Config {
+    fn from(old: OldConfig) -> Self {
+        Self {
+            name: old.name,
+            purpose: old.purpose,
+            metadata: old.metadata,
+            upgrade_from_blob: false,
+            callback_gas: default_callback_gas(),
+            min_proposal_stake: default_min_proposal_stake(),
+            removal_grace_period: default_removal_grace_period(),
+            claimable_payouts: false,
+            slashing: None,
+            max_function_call_deposit: default_max_function_call_deposit(),
+            rate_limit: None,
+            disabled_kinds: vec![],
+            max_execution_retries: default_max_execution_retries(),
+            allow_force_execute: false,
+            member_voting_delay_blocks: default_member_voting_delay_blocks(),
+            governance_token: None,
+            total_supply_max_age: default_total_supply_max_age(),
+            min_voting_period: default_min_voting_period(),
+            observer: None,
+            tie_break: TieBreak::default(),
+            auto_execute: default_auto_execute(),
+            proposer_can_vote: default_proposer_can_vote(),
+            suggestion_pruning: None,
+            ft_transfer_gas: HashMap::default(),
+            min_description_length: 0,
+            high_risk_kinds: default_high_risk_kinds(),
+            transfer_fee_bps: 0,
+            fee_receiver: None,
+            vote_cooldown: None,
+            voting_power_source: VotingPowerSource::default(),
+        }
+    }
+}
+
+impl From<OldVotePolicy> for VotePolicy {
+    fn from(old: OldVotePolicy) -> Self {
+        Self {
+            weight_kind: old.weight_kind,
+            quorum: old.quorum,
+            threshold: old.threshold,
+            spam_threshold: U128(0),
+            max_weight_bps: None,
+        }
+    }
+}
+
+impl From<OldRolePermission> for RolePermission {
+    fn from(old: OldRolePermission) -> Self {
+        Self {
+            name: old.name,
+            kind: old.kind,
+            permissions: old.permissions,
+            vote_policy: old
+                .vote_policy
+                .into_iter()
+                .map(|(label, vote_policy)| (label, vote_policy.into()))
+                .collect(),
+        }
+    }
+}
+
+impl From<OldVersionedPolicy> for VersionedPolicy {
+    fn from(old: OldVersionedPolicy) -> Self {
+        match old {
+            OldVersionedPolicy::Default { council, stakers } => {
+                VersionedPolicy::Default { council, stakers }.upgrade()
+            }
+            OldVersionedPolicy::Current(policy) => VersionedPolicy::Current(Policy {
+                roles: policy.roles.into_iter().map(RolePermission::from).collect(),
+                default_vote_policy: policy.default_vote_policy.into(),
+                proposal_bond: U128(0),
+                proposal_period: policy.proposal_period,
+                proposal_periods: HashMap::default(),
+                token_decimals: 0,
+            }),
+        }
+    }
+}
+
+impl From<OldProposalKind> for ProposalKind {
+    fn from(old: OldProposalKind) -> Self {
+        match old {
+            OldProposalKind::ChangeConfig { config } => ProposalKind::ChangeConfig {
+                config: config.into(),
+                effective_block: None,
+            },
+            OldProposalKind::ChangePolicy { policy } => ProposalKind::ChangePolicy {
+                policy: policy.into(),
+            },
+            OldProposalKind::AddMemberToRole { member_id, role } => ProposalKind::AddMemberToRole {
+                member_id,
+                role,
+                term_end_block: None,
+            },
+            OldProposalKind::RemoveMemberFromRole { member_id, role } => {
+                ProposalKind::RemoveMemberFromRole { member_id, role }
+            }
+            OldProposalKind::FunctionCall {
+                receiver_id,
+                actions,
+            } => ProposalKind::FunctionCall {
+                receiver_id,
+                actions,
+            },
+            OldProposalKind::UpgradeSelf { hash } => ProposalKind::UpgradeSelf { hash },
+            OldProposalKind::UpgradeRemote {
+                receiver_id,
+                method_name,
+                hash,
+            } => ProposalKind::UpgradeRemote {
+                receiver_id,
+                method_name,
+                hash,
+            },
+            OldProposalKind::Transfer {
+                token_id,
+                receiver_id,
+                amount,
+                msg,
+            } => ProposalKind::Transfer {
+                token_id,
+                receiver_id,
+                amount,
+                msg,
+                register_receiver: false,
+            },
+            OldProposalKind::Vote => ProposalKind::Vote,
+            OldProposalKind::ChangePolicyAddOrUpdateRole { role } => {
+                ProposalKind::ChangePolicyAddOrUpdateRole { role: role.into() }
+            }
+            OldProposalKind::ChangePolicyRemoveRole { role } => {
+                ProposalKind::ChangePolicyRemoveRole { role }
+            }
+            OldProposalKind::ChangePolicyUpdateDefaultVotePolicy { vote_policy } => {
+                ProposalKind::ChangePolicyUpdateDefaultVotePolicy {
+                    vote_policy: vote_policy.into(),
+                }
+            }
+            OldProposalKind::ChangePolicyUpdateParameters { parameters } => {
+                ProposalKind::ChangePolicyUpdateParameters {
+                    parameters: PolicyParameters {
+                        proposal_period: parameters.proposal_period,
+                        proposal_periods: None,
+                    },
+                }
+            }
+            OldProposalKind::Suggestion { suggestion } => ProposalKind::Suggestion { suggestion },
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Rewrites the state of the first version in the current layout, see the module docs.
+    /// Called by `update` after deploying new code, so only this account or the factory can call it.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let caller = env::predecessor_account_id();
+        assert!(
+            caller == env::current_account_id() || caller == internal_get_factory_info().factory_id,
+            "ERR_MUST_BE_SELF_OR_FACTORY"
+        );
+        let old: OldContract = env::state_read().expect("ERR_NO_STATE");
+        let config: Config = old.config.get().expect("ERR_NO_CONFIG").into();
+        let policy: VersionedPolicy = old.policy.get().expect("ERR_NO_POLICY").into();
+        Self::internal_new(&config, policy, old.last_proposal_id)
+    }
+}
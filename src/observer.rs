@@ -0,0 +1,36 @@
+//! Notifications pushed to an observer contract on key proposal events.
+
+use near_sdk::Gas;
+
+use crate::*;
+
+/// Gas attached to each notification of the observer.
+pub const GAS_FOR_OBSERVER: Gas = Gas(5_000_000_000_000);
+
+#[ext_contract(ext_observer)]
+pub trait ProposalObserver {
+    /// Called with the name of the event, e.g. `proposal_added`, and the new status.
+    fn on_proposal_event(&mut self, proposal_id: U64, event: String, status: ProposalStatus);
+}
+
+impl Contract {
+    /// Notifies the observer set in the config, if any, of given proposal event.
+    /// The call is not awaited, so a failing observer doesn't affect the DAO.
+    pub(crate) fn internal_notify_observer(
+        &self,
+        proposal_id: u64,
+        event: &str,
+        status: &ProposalStatus,
+    ) {
+        if let Some(observer) = self.config.get().unwrap().observer {
+            ext_observer::on_proposal_event(
+                U64(proposal_id),
+                event.to_string(),
+                status.clone(),
+                observer,
+                0,
+                GAS_FOR_OBSERVER,
+            );
+        }
+    }
+}
@@ -6,11 +6,11 @@ use std::collections::{HashMap, HashSet};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::{U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, AccountId, Balance};
+use near_sdk::{env, AccountId, Balance, BlockHeight};
 //use near_sdk_sim::lazy_static_include::syn::Member; 
 
 use crate::proposals::{PolicyParameters, Proposal, ProposalKind, ProposalStatus, Vote};
-use crate::types::Action;
+use crate::types::{Action, TieBreak};
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
@@ -31,6 +31,13 @@ impl RoleKind {
         }
     }
 
+    /// Accounts in this role.
+    pub fn accounts(&self) -> &HashSet<AccountId> {
+        match self {
+            RoleKind::Member(accounts) | RoleKind::Group(accounts) => accounts,
+        }
+    }
+
     /// Returns the number of people in the this role or None if not supported role kind.
     pub fn get_role_size(&self) -> Option<usize> {
         match self {
@@ -106,6 +113,8 @@ pub struct RolePermission {
 pub struct UserInfo {
     pub account_id: AccountId,
     pub stake: Balance,
+    /// Roles whose term of this user ended, treated as if the user wasn't a member.
+    pub expired_roles: Vec<String>,
 }
 
 /// Direct weight or ratio to total weight, used for the voting policy.
@@ -116,6 +125,30 @@ pub struct UserInfo {
 pub enum WeightOrRatio {
     Weight(U128),
     Ratio(u64, u64),
+    /// Weight needed regardless of the total weight, unlike `Weight` which is capped by it.
+    /// Written as `{"absolute": "<weight>"}` in JSON.
+    #[serde(with = "absolute_weight")]
+    AbsoluteWeight(U128),
+}
+
+/// JSON form of `WeightOrRatio::AbsoluteWeight`, telling it apart from `Weight`.
+mod absolute_weight {
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct Absolute {
+        absolute: U128,
+    }
+
+    pub fn serialize<S: Serializer>(weight: &U128, serializer: S) -> Result<S::Ok, S::Error> {
+        Absolute { absolute: *weight }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U128, D::Error> {
+        Absolute::deserialize(deserializer).map(|absolute| absolute.absolute)
+    }
 }
 
 impl WeightOrRatio {
@@ -123,6 +156,7 @@ impl WeightOrRatio {
     pub fn to_weight(&self, total_weight: Balance) -> Balance {
         match self {
             WeightOrRatio::Weight(weight) => min(weight.0, total_weight),
+            WeightOrRatio::AbsoluteWeight(weight) => weight.0,
             WeightOrRatio::Ratio(num, denom) => min(
                 (*num as u128 * total_weight) / *denom as u128 + 1,
                 total_weight,
@@ -140,6 +174,11 @@ pub enum WeightKind {
     TokenWeight,
     /// Weight of the group role. Roles that don't have scoped group are not supported.
     RoleWeight,
+    /// Using locked stake, multiplied by the remaining duration of the lock.
+    DurationWeight,
+    /// Using the locked stake of the members of the role. Only members can vote, so the stake
+    /// of other accounts counts for nothing.
+    StakeWeight,
 }
 
 /// Defines configuration of the vote.
@@ -157,7 +196,14 @@ pub struct VotePolicy {
     pub quorum: U128,
     /// How many votes to pass this vote.
     pub threshold: WeightOrRatio,
-
+    /// Minimum weight of remove votes required to remove the proposal as spam.
+    /// Removal always requires at least `threshold` as well, so `0` means the same threshold as other decisions.
+    #[serde(default = "default_spam_threshold")]
+    pub spam_threshold: U128,
+    /// If set, caps the weight of each account at this many basis points of the total
+    /// supply of the governance token, which has to be fetched first. Not applied to RoleWeight.
+    #[serde(default)]
+    pub max_weight_bps: Option<u16>,
 }
 
 impl Default for VotePolicy {
@@ -166,10 +212,20 @@ impl Default for VotePolicy {
             weight_kind: WeightKind::RoleWeight,
             quorum: U128(0),
             threshold: WeightOrRatio::Ratio(1, 2),
+            spam_threshold: U128(0),
+            max_weight_bps: None,
         }
     }
 }
 
+fn default_spam_threshold() -> U128 {
+    U128(0)
+}
+
+fn default_proposal_bond() -> U128 {
+    U128(0)
+}
+
 /// Defines voting / decision making policy of this DAO.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
@@ -179,8 +235,18 @@ pub struct Policy {
     pub roles: Vec<RolePermission>,
     /// Default vote policy. Used when given proposal kind doesn't have special policy.
     pub default_vote_policy: VotePolicy,
+    /// Bond for a proposal.
+    #[serde(default = "default_proposal_bond")]
+    pub proposal_bond: U128,
     /// Expiration period for proposals.
     pub proposal_period: U64,
+    /// Expiration periods overriding `proposal_period` for given proposal kind labels.
+    #[serde(default)]
+    pub proposal_periods: HashMap<String, U64>,
+    /// Decimals of the token used for token and duration weighted votes.
+    /// Such votes are tallied in whole tokens, so quorums and weight thresholds are too.
+    #[serde(default)]
+    pub token_decimals: u8,
 }
 
 /// Versioned policy.
@@ -195,6 +261,31 @@ pub enum VersionedPolicy {
     Current(Policy),
 }
 
+/// Name of the role allowed to take emergency actions, like pausing the contract.
+pub const GUARDIAN_ROLE: &str = "guardian";
+
+/// Name of the role allowed to force the execution of proposals, if enabled in the config.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Maximum number of previous policies kept in the policy history.
+pub const MAX_POLICY_HISTORY: usize = 10;
+
+/// Approval progress of a proposal that has enough votes to pass, in basis points.
+pub const MAX_APPROVAL_PROGRESS: u128 = 10_000;
+
+/// Policy that was replaced by a policy changing proposal.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PolicyHistoryEntry {
+    /// Block at which the policy was replaced.
+    pub block_height: BlockHeight,
+    /// Proposal that replaced the policy.
+    pub proposal_id: u64,
+    /// Policy before the change.
+    pub policy: VersionedPolicy,
+}
+
 /// Defines default policy:
 ///     - everyone can add proposals
 ///     - group consisting of the call can do all actions, consists of caller.
@@ -219,6 +310,8 @@ pub fn default_policy(council: Vec<AccountId>, stakers: Vec<AccountId>) -> Polic
                     "*:VoteApprove".to_string(),
                     "*:VoteReject".to_string(),
                     "*:VoteRemove".to_string(),
+                    "*:VoteOption".to_string(),
+                    "*:VoteSplit".to_string(),
                     "*:Finalize".to_string(),
                 ]
                 .into_iter()
@@ -227,7 +320,10 @@ pub fn default_policy(council: Vec<AccountId>, stakers: Vec<AccountId>) -> Polic
             },
         ],
         default_vote_policy: VotePolicy::default(),
+        proposal_bond: U128(0),
         proposal_period: U64::from(1_000_000_000 * 60 * 60 * 24 * 7),
+        proposal_periods: HashMap::default(),
+        token_decimals: 0,
     }
 }
 
@@ -293,6 +389,9 @@ impl Policy {
         if parameters.proposal_period.is_some() {
             self.proposal_period = parameters.proposal_period.unwrap();
         }
+        if let Some(proposal_periods) = &parameters.proposal_periods {
+            self.proposal_periods = proposal_periods.clone();
+        }
         env::log_str("Successfully updated the policy parameters.");
     }
 
@@ -352,7 +451,7 @@ impl Policy {
     fn get_user_roles(&self, user: UserInfo) -> HashMap<String, &HashSet<String>> {
         let mut roles = HashMap::default();
         for role in self.roles.iter() {
-            if role.kind.match_user(&user) {
+            if role.kind.match_user(&user) && !user.expired_roles.contains(&role.name) {
                 roles.insert(role.name.clone(), &role.permissions);
             }
         }
@@ -361,6 +460,8 @@ impl Policy {
 
     /// Can given user execute given action on this proposal.
     /// Returns all roles that allow this action.
+    /// Each action is its own permission, e.g. `transfer:AddProposal` allows submitting transfers
+    /// and `transfer:VoteApprove` voting on them; only `transfer:*` or `*:*` allow both.
     pub fn can_execute_action(
         &self,
         user: UserInfo,
@@ -395,18 +496,60 @@ impl Policy {
         true
     }
 
-    /// Returns if given proposal kind is token weighted.
-    pub fn is_token_weighted(&self, role: &String, proposal_kind_label: &String) -> bool {
+    /// Returns the vote policy of given role for given proposal kind.
+    pub fn vote_policy(&self, role: &String, proposal_kind_label: &String) -> &VotePolicy {
         let role_info = self.internal_get_role(role).expect("ERR_ROLE_NOT_FOUND");
-        match role_info
+        role_info
             .vote_policy
             .get(proposal_kind_label)
             .unwrap_or(&self.default_vote_policy)
-            .weight_kind
-        {
-            WeightKind::TokenWeight => true,
-            _ => false,
-        }
+    }
+
+    /// Returns how votes of given role on given proposal kind are weighted.
+    pub fn weight_kind(&self, role: &String, proposal_kind_label: &String) -> &WeightKind {
+        &self.vote_policy(role, proposal_kind_label).weight_kind
+    }
+
+    /// Returns if given proposal kind is token weighted.
+    pub fn is_token_weighted(&self, role: &String, proposal_kind_label: &String) -> bool {
+        *self.weight_kind(role, proposal_kind_label) == WeightKind::TokenWeight
+    }
+
+    /// Converts a raw token balance into whole tokens used for tallying votes.
+    /// Fractions of a token are not counted.
+    pub fn to_token_units(&self, amount: Balance) -> Balance {
+        10u128
+            .checked_pow(self.token_decimals as u32)
+            .map(|unit| amount / unit)
+            .unwrap_or(0)
+    }
+
+    /// Returns if given proposal kind is weighted by the duration of locked stake.
+    pub fn is_duration_weighted(&self, role: &String, proposal_kind_label: &String) -> bool {
+        *self.weight_kind(role, proposal_kind_label) == WeightKind::DurationWeight
+    }
+
+    /// Returns if given account is a member of any role.
+    pub fn is_member(&self, account_id: &AccountId) -> bool {
+        let user = UserInfo {
+            account_id: account_id.clone(),
+            stake: 0,
+            expired_roles: vec![],
+        };
+        self.roles.iter().any(|role| role.kind.match_user(&user))
+    }
+
+    /// Returns if given account is a member of the role with given name.
+    pub fn is_role_member(&self, role: &str, account_id: &AccountId) -> bool {
+        self.internal_get_role(&role.to_string())
+            .map(|role_info| {
+                role_info.kind.match_user(&UserInfo {
+                    account_id: account_id.clone(),
+                    stake: 0,
+                    expired_roles: vec![],
+                })
+            })
+            .unwrap_or(false)
     }
 
     fn internal_get_role(&self, name: &String) -> Option<&RolePermission> {
@@ -418,12 +561,36 @@ impl Policy {
         None
     }
 
+    /// Expiration period for proposals of given kind label.
+    /// Falls back to `proposal_period` if the label has no period of its own.
+    pub fn proposal_period_for(&self, label: &str) -> u64 {
+        self.proposal_periods
+            .get(label)
+            .unwrap_or(&self.proposal_period)
+            .0
+    }
+
+    /// Returns the timestamp after which given proposal is considered expired,
+    /// including any extension granted to it.
+    pub fn proposal_deadline(&self, proposal: &Proposal) -> u64 {
+        proposal
+            .submission_time
+            .0
+            .saturating_add(self.proposal_period_for(proposal.kind.to_policy_label()))
+            .saturating_add(proposal.period_extension.0)
+    }
+
     /// Get proposal status for given proposal.
     /// Usually is called after changing it's state.
+    /// Past the deadline, ties of approve and reject weight are decided by `tie_break`.
+    /// Before it, proposals the remaining voters can't approve anymore are rejected right away,
+    /// see `can_avoid_rejection`.
     pub fn proposal_status(
         &self,
         proposal: &Proposal,
         roles: Vec<String>,
+        total_supply: Balance,
+        tie_break: &TieBreak,
     ) -> ProposalStatus {
         assert!(
             matches!(
@@ -432,31 +599,89 @@ impl Policy {
             ),
             "ERR_PROPOSAL_NOT_IN_PROGRESS"
         );
-        if proposal.submission_time.0 + self.proposal_period.0 < env::block_timestamp() {
+        if self.proposal_deadline(proposal) < env::block_timestamp() {
             // Proposal expired.
+            return self.expired_status(proposal, &roles, tie_break);
+        };
+        let status = self.proposal_vote_status(proposal, roles, total_supply);
+        if status == ProposalStatus::InProgress
+            && !self.can_avoid_rejection(proposal, total_supply, tie_break)
+        {
+            return ProposalStatus::Rejected;
+        }
+        status
+    }
+
+    /// Status of given proposal once past its deadline: decided by `tie_break` if a role has as
+    /// much approve as reject weight, expired otherwise.
+    pub fn expired_status(
+        &self,
+        proposal: &Proposal,
+        roles: &[String],
+        tie_break: &TieBreak,
+    ) -> ProposalStatus {
+        let tied = roles.iter().any(|role| {
+            proposal
+                .vote_counts
+                .get(role)
+                .map(|counts| {
+                    counts[Vote::Approve as usize] > 0
+                        && counts[Vote::Approve as usize] == counts[Vote::Reject as usize]
+                })
+                .unwrap_or(false)
+        });
+        if !tied {
             return ProposalStatus::Expired;
+        }
+        let approved = match tie_break {
+            TieBreak::RejectOnTie => false,
+            TieBreak::ApproveOnTie => true,
+            TieBreak::ProposerWins => proposal
+                .votes
+                .get(&proposal.proposer)
+                .map(|vote| matches!(vote.vote, Vote::Approve))
+                .unwrap_or(false),
         };
+        if approved {
+            ProposalStatus::Approved
+        } else {
+            ProposalStatus::Rejected
+        }
+    }
+
+    /// Returns the status the votes of given roles lead to, regardless of the deadline.
+    /// `total_supply` of the governance token is used for the thresholds of token weighted roles.
+    /// Proposals with required roles are only approved once each of them reaches its threshold.
+    pub fn proposal_vote_status(
+        &self,
+        proposal: &Proposal,
+        roles: Vec<String>,
+        total_supply: Balance,
+    ) -> ProposalStatus {
+        // With required roles, all of them must approve, whichever roles the voters are in.
+        let required = &proposal.required_roles;
+        if !required.is_empty()
+            && required.iter().all(|role| {
+                let (_, threshold) = self.vote_threshold(role, proposal, total_supply);
+                let vote_counts = proposal.vote_counts.get(role).unwrap_or(&[0u128; 3]);
+                vote_counts[Vote::Approve as usize] >= threshold
+            })
+        {
+            return ProposalStatus::Approved;
+        }
          for role in roles {
-            let role_info = self.internal_get_role(&role).expect("ERR_MISSING_ROLE");
-            let vote_policy = role_info
-                .vote_policy
-                .get(&proposal.kind.to_policy_label().to_string())
-                .unwrap_or(&self.default_vote_policy);
-            
-            let total_weight = 0;
-            let threshold = std::cmp::max(
-                vote_policy.quorum.0,
-                vote_policy.threshold.to_weight(total_weight),
-            );
+            let (vote_policy, threshold) = self.vote_threshold(&role, proposal, total_supply);
             // Check if there is anything voted above the threshold specified by policy for given role.
             let vote_counts = proposal.vote_counts.get(&role).unwrap_or(&[0u128; 3]);
             println!("vote_counts: {:?}", vote_counts[Vote::Approve as usize]);
             println!("threshold: {:?}", threshold);
-            if vote_counts[Vote::Approve as usize] >= threshold {
+            if required.is_empty() && vote_counts[Vote::Approve as usize] >= threshold {
                 return ProposalStatus::Approved;
             } else if vote_counts[Vote::Reject as usize] >= threshold {
                 return ProposalStatus::Rejected;
-            } else if vote_counts[Vote::Remove as usize] >= threshold {
+            } else if vote_counts[Vote::Remove as usize]
+                >= std::cmp::max(threshold, vote_policy.spam_threshold.0)
+            {
                 return ProposalStatus::Removed;
             } else {
                 // continue to next role.
@@ -464,6 +689,171 @@ impl Policy {
         }
         proposal.status.clone()
     }
+
+    /// Returns whether the members who haven't voted on given proposal yet can still keep it from
+    /// being rejected: by reaching the approval threshold of a role allowed to approve it, or of
+    /// each required role if it has any, by a tie `tie_break` decides in favor, or by removing it.
+    /// Only the remaining weight of role weighted groups is known, other roles and polls can
+    /// always do so.
+    pub fn can_avoid_rejection(
+        &self,
+        proposal: &Proposal,
+        total_supply: Balance,
+        tie_break: &TieBreak,
+    ) -> bool {
+        if matches!(proposal.kind, ProposalKind::Poll { .. }) {
+            return true;
+        }
+        let tie_approves = match tie_break {
+            TieBreak::RejectOnTie => false,
+            TieBreak::ApproveOnTie => true,
+            TieBreak::ProposerWins => proposal
+                .votes
+                .get(&proposal.proposer)
+                .map(|vote| matches!(vote.vote, Vote::Approve))
+                .unwrap_or(true),
+        };
+        let label = proposal.kind.to_policy_label();
+        let allows = |role: &RolePermission, action: &str| {
+            role.permissions.contains(&format!("{}:{}", label, action))
+                || role.permissions.contains(&format!("{}:*", label))
+                || role.permissions.contains(&format!("*:{}", action))
+                || role.permissions.contains("*:*")
+        };
+        // Whether given role can still approve, tie or remove the proposal, if its remaining
+        // weight is known.
+        let outcomes = |role: &RolePermission| -> Option<[bool; 3]> {
+            let (vote_policy, threshold) = self.vote_threshold(&role.name, proposal, total_supply);
+            let members = match vote_policy.weight_kind {
+                WeightKind::RoleWeight => role.kind.accounts(),
+                _ => return None,
+            };
+            let [approved, rejected, removed] =
+                *proposal.vote_counts.get(&role.name).unwrap_or(&[0u128; 3]);
+            let remaining = members
+                .iter()
+                .filter(|member| {
+                    !proposal.votes.contains_key(*member)
+                        && !proposal.split_votes.contains_key(*member)
+                })
+                .count() as Balance;
+            Some([
+                approved + remaining >= threshold,
+                std::cmp::max(approved, rejected) - std::cmp::min(approved, rejected) <= remaining,
+                removed + remaining >= std::cmp::max(threshold, vote_policy.spam_threshold.0),
+            ])
+        };
+        let can = |role: &RolePermission, outcome: usize| {
+            outcomes(role)
+                .map(|outcomes| outcomes[outcome])
+                .unwrap_or(true)
+        };
+        let approvable = if proposal.required_roles.is_empty() {
+            self.roles
+                .iter()
+                .any(|role| allows(role, "VoteApprove") && can(role, 0))
+        } else {
+            proposal.required_roles.iter().all(|name| {
+                self.internal_get_role(name)
+                    .map(|role| can(role, 0))
+                    .unwrap_or(true)
+            })
+        };
+        approvable
+            || tie_approves
+                && self
+                    .roles
+                    .iter()
+                    .any(|role| allows(role, "VoteApprove") && can(role, 1))
+            || self
+                .roles
+                .iter()
+                .any(|role| allows(role, "VoteRemove") && can(role, 2))
+    }
+
+    /// Returns the vote policy of given role for given proposal and the weight needed to pass it.
+    /// Ratio thresholds of token weighted roles are taken of `total_supply`, the ones of role
+    /// weighted roles of their current number of members.
+    fn vote_threshold(
+        &self,
+        role: &String,
+        proposal: &Proposal,
+        total_supply: Balance,
+    ) -> (&VotePolicy, Balance) {
+        let role_info = self.internal_get_role(role).expect("ERR_MISSING_ROLE");
+        let vote_policy = role_info
+            .vote_policy
+            .get(&proposal.kind.to_policy_label().to_string())
+            .unwrap_or(&self.default_vote_policy);
+        let total_weight = match vote_policy.weight_kind {
+            WeightKind::TokenWeight => self.to_token_units(total_supply),
+            // Current members, so ratios follow members added or removed while voting.
+            WeightKind::RoleWeight => role_info.kind.accounts().len() as Balance,
+            _ => 0,
+        };
+        let threshold = std::cmp::max(
+            vote_policy.quorum.0,
+            vote_policy.threshold.to_weight(total_weight),
+        );
+        (vote_policy, threshold)
+    }
+
+    /// Returns how close given proposal is to approval in basis points,
+    /// taking the role closest to approve it.
+    pub fn approval_progress(&self, proposal: &Proposal, total_supply: Balance) -> u128 {
+        self.roles
+            .iter()
+            .map(|role| {
+                let (_, threshold) = self.vote_threshold(&role.name, proposal, total_supply);
+                let approved = proposal
+                    .vote_counts
+                    .get(&role.name)
+                    .map(|counts| counts[Vote::Approve as usize])
+                    .unwrap_or(0);
+                if approved >= threshold {
+                    MAX_APPROVAL_PROGRESS
+                } else {
+                    approved * MAX_APPROVAL_PROGRESS / threshold
+                }
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the approve weight of given proposal and the weight it needs to pass.
+    /// Only roles that can vote to approve the proposal kind count, the one closest to approval is used.
+    pub fn approval_weights(
+        &self,
+        proposal: &Proposal,
+        total_supply: Balance,
+    ) -> (Balance, Balance) {
+        let label = proposal.kind.to_policy_label();
+        self.roles
+            .iter()
+            .filter(|role| {
+                role.permissions.contains(&format!("{}:VoteApprove", label))
+                    || role.permissions.contains(&format!("{}:*", label))
+                    || role.permissions.contains("*:VoteApprove")
+                    || role.permissions.contains("*:*")
+            })
+            .map(|role| {
+                let (_, threshold) = self.vote_threshold(&role.name, proposal, total_supply);
+                let approved = proposal
+                    .vote_counts
+                    .get(&role.name)
+                    .map(|counts| counts[Vote::Approve as usize])
+                    .unwrap_or(0);
+                (approved, threshold)
+            })
+            .max_by_key(|(approved, threshold)| {
+                if approved >= threshold {
+                    MAX_APPROVAL_PROGRESS
+                } else {
+                    approved * MAX_APPROVAL_PROGRESS / threshold
+                }
+            })
+            .unwrap_or((0, 0))
+    }
 }
 
 #[cfg(test)]
@@ -484,6 +874,38 @@ mod tests {
         assert_eq!(r2.to_weight(5), 5);
     }
 
+    #[test]
+    fn test_absolute_weight() {
+        let absolute = WeightOrRatio::AbsoluteWeight(U128(100));
+        assert_eq!(absolute.to_weight(10), 100);
+        assert_eq!(WeightOrRatio::Weight(U128(100)).to_weight(10), 10);
+
+        let json = near_sdk::serde_json::json!({ "absolute": "100" });
+        assert_eq!(near_sdk::serde_json::to_value(&absolute).unwrap(), json);
+        assert_eq!(
+            near_sdk::serde_json::from_value::<WeightOrRatio>(json).unwrap(),
+            absolute
+        );
+        assert_eq!(
+            near_sdk::serde_json::from_str::<WeightOrRatio>("\"100\"").unwrap(),
+            WeightOrRatio::Weight(U128(100))
+        );
+    }
+
+    #[test]
+    fn test_policy_without_bond_and_spam_threshold() {
+        let policy = default_policy(vec![accounts(0)], vec![]);
+        let mut json = near_sdk::serde_json::to_value(&policy).unwrap();
+        json.as_object_mut().unwrap().remove("proposal_bond");
+        json["default_vote_policy"]
+            .as_object_mut()
+            .unwrap()
+            .remove("spam_threshold");
+        let policy = near_sdk::serde_json::from_value::<Policy>(json).unwrap();
+        assert_eq!(policy.proposal_bond, U128(0));
+        assert_eq!(policy.default_vote_policy.spam_threshold, U128(0));
+    }
+
     #[test]
     fn test_add_role() {
         let council = vec![accounts(0), accounts(1)];
@@ -530,6 +952,8 @@ mod tests {
             "*:VoteApprove".to_string(),
             "*:VoteReject".to_string(),
             "*:VoteRemove".to_string(),
+            "*:VoteOption".to_string(),
+            "*:VoteSplit".to_string(),
             "*:Finalize".to_string(),
         ]
         .into_iter()
@@ -605,6 +1029,8 @@ mod tests {
             weight_kind: WeightKind::TokenWeight,
             quorum: U128(100),
             threshold: WeightOrRatio::Ratio(1, 4),
+            spam_threshold: U128(0),
+            max_weight_bps: None,
         };
         policy.update_default_vote_policy(&new_default_vote_policy);
         assert_eq!(
@@ -620,4 +1046,18 @@ mod tests {
             policy.default_vote_policy.threshold
         );
     }
+
+    #[test]
+    fn test_to_token_units() {
+        let council = vec![accounts(0), accounts(1)];
+        let mut policy = default_policy(council, vec![]);
+        let balance = 1_500_000_000_000_000_000_000_000;
+        assert_eq!(policy.to_token_units(balance), balance);
+        policy.token_decimals = 24;
+        assert_eq!(policy.to_token_units(balance), 1);
+        assert_eq!(policy.to_token_units(u128::MAX), u128::MAX / 10u128.pow(24));
+        // More decimals than fit into a balance.
+        policy.token_decimals = 39;
+        assert_eq!(policy.to_token_units(u128::MAX), 0);
+    }
 }
@@ -3,18 +3,97 @@ use std::collections::HashMap;
 use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::serde_json::json;
 use near_sdk::{log, AccountId, Balance, Gas, PromiseOrValue, BlockHeight};
 
+use crate::events::emit_event;
+use crate::migrate::OldProposalKind;
 use crate::types::{
-    convert_old_to_new_token, Action, Config, OldAccountId, GAS_FOR_FT_TRANSFER, OLD_BASE_TOKEN,
-    ONE_YOCTO_NEAR,
+    convert_old_to_new_token, Action, Config, ConfigHistoryEntry, OldAccountId,
+    GAS_FOR_MINT_OR_BURN, GAS_FOR_STORAGE_DEPOSIT, OLD_BASE_TOKEN, ONE_YOCTO_NEAR,
+    STORAGE_DEPOSIT_FOR_REGISTRATION,
 };
-use crate::upgrade::{upgrade_remote, upgrade_using_factory};
+use crate::staking::{ext_staking_pool, GAS_FOR_STAKING};
+use crate::upgrade::{upgrade_remote, upgrade_self, upgrade_using_factory};
 use crate::policy::*;
 use crate::*;
 
+/// Storage registration on fungible token contracts.
+#[ext_contract(ext_storage_management)]
+pub trait StorageManagement {
+    fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>);
+}
+
+/// Privileged methods of the governance token, callable by this DAO.
+#[ext_contract(ext_governance_token)]
+pub trait GovernanceToken {
+    fn mint(&mut self, account_id: AccountId, amount: U128);
+    fn burn(&mut self, amount: U128);
+}
+
+/// Maximum number of tags that can be attached to a proposal.
+pub const MAX_PROPOSAL_TAGS: usize = 5;
+
+/// Maximum length of a single tag or category.
+pub const MAX_TAG_LENGTH: usize = 32;
+
+/// Maximum length of the url of the off-chain document of a proposal.
+pub const MAX_REFERENCE_URL_LENGTH: usize = 512;
+
+/// Length of the hash of the off-chain document of a proposal, a sha256 digest.
+pub const REFERENCE_HASH_LENGTH: usize = 32;
+
+/// Maximum length of the idempotency key of a proposal.
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 64;
+
+/// Number of blocks an idempotency key is remembered for.
+pub const IDEMPOTENCY_KEY_TTL: BlockHeight = 1_000;
+
+/// Maximum number of co-sponsors of a proposal.
+pub const MAX_COSPONSORS: usize = 10;
+
+/// Maximum number of idempotency keys remembered per proposer, older keys are dropped first.
+pub const MAX_IDEMPOTENCY_KEYS: usize = 10;
+
+/// Maximum number of options of a poll.
+pub const MAX_POLL_OPTIONS: usize = 10;
+
+/// Maximum length of a single option of a poll.
+pub const MAX_POLL_OPTION_LENGTH: usize = 64;
+
+/// Maximum number of transfers in a single `MultiTokenTransfer`.
+pub const MAX_TRANSFER_LEGS: usize = 8;
+
+/// Maximum length of the rationale attached to a vote.
+pub const MAX_VOTE_REASON_LENGTH: usize = 280;
+
+/// Maximum number of actions applied by a single `act_proposals` call.
+pub const MAX_BULK_ACTIONS: usize = 20;
+
+/// Outcome of one of the actions given to `act_proposals`.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActionOutcome {
+    /// Id of the proposal acted on.
+    pub id: u64,
+    /// Why the action was skipped, if it was.
+    pub error: Option<String>,
+}
+
 /// Status of a proposal.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Hash,
+    Debug,
+)]
 #[serde(crate = "near_sdk::serde")]
 pub enum ProposalStatus {
     InProgress,
@@ -24,6 +103,7 @@ pub enum ProposalStatus {
     Rejected,
     /// If quorum voted to remove (e.g. spam), this proposal is rejected.
     /// Interfaces shouldn't show removed proposals.
+    /// Removed proposals can be restored during the removal grace period and deleted after it.
     Removed,
     /// Expired after period of time.
     Expired,
@@ -31,11 +111,15 @@ pub enum ProposalStatus {
     Moved,
     /// If proposal has failed when finalizing. Allowed to re-finalize again to either expire or approved.
     Failed,
+    /// Waiting for all its co-sponsors to confirm with `confirm_cosponsorship` before it can be
+    /// voted on. A draft that isn't confirmed within the voting period from its submission is
+    /// expired by `finalize_expired` or `refresh_status`, returning its bond.
+    Draft,
 }
 
 /// Function call arguments.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[cfg_attr(not(target_arch = "wasm32"), derive(Clone, Debug))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
 #[serde(crate = "near_sdk::serde")]
 pub struct ActionCall {
     method_name: String,
@@ -45,33 +129,52 @@ pub struct ActionCall {
 }
 
 /// Function call arguments.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[cfg_attr(not(target_arch = "wasm32"), derive(Clone, Debug))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
 #[serde(crate = "near_sdk::serde")]
 pub struct PolicyParameters {
     pub proposal_period: Option<U64>,
+    /// Replaces all the per kind label expiration periods, if set.
+    #[serde(default)]
+    pub proposal_periods: Option<HashMap<String, U64>>,
 }
 
 /// Kinds of proposals, doing different action.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[cfg_attr(not(target_arch = "wasm32"), derive(Clone, Debug))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
 #[serde(crate = "near_sdk::serde")]
+#[allow(clippy::large_enum_variant)]
 pub enum ProposalKind {
-    /// Change the DAO config.
-    ChangeConfig { config: Config },
+    /// Change the DAO config. If `effective_block` is set, the config is applied by
+    /// `poke_scheduled_config` from that block on instead of on approval.
+    ChangeConfig {
+        config: Config,
+        #[serde(default)]
+        effective_block: Option<BlockHeight>,
+    },
     /// Change the full policy.
     ChangePolicy { policy: VersionedPolicy },
     /// Add member to given role in the policy. This is short cut to updating the whole policy.
-    AddMemberToRole { member_id: AccountId, role: String },
+    /// If `term_end_block` is set, the membership is treated as absent from that block on and can
+    /// be pruned with `prune_expired_members`.
+    AddMemberToRole {
+        member_id: AccountId,
+        role: String,
+        #[serde(default)]
+        term_end_block: Option<BlockHeight>,
+    },
     /// Remove member to given role in the policy. This is short cut to updating the whole policy.
     RemoveMemberFromRole { member_id: AccountId, role: String },
     /// Calls `receiver_id` with list of method names in a single promise.
     /// Allows this contract to execute any arbitrary set of actions in other contracts.
+    /// All actions run in one batch, so the total gas of the actions plus the gas for the
+    /// proposal callback must fit into the prepaid gas, which is checked on submission.
     FunctionCall {
         receiver_id: AccountId,
         actions: Vec<ActionCall>,
     },
     /// Upgrade this contract with given hash from blob store.
+    /// Goes through the factory unless `Config::upgrade_from_blob` is set.
     UpgradeSelf { hash: Base58CryptoHash },
     /// Upgrade another contract, by calling method with the code from given hash from blob store.
     UpgradeRemote {
@@ -82,12 +185,18 @@ pub enum ProposalKind {
     /// Transfers given amount of `token_id` from this DAO to `receiver_id`.
     /// If `msg` is not None, calls `ft_transfer_call` with given `msg`. Fails if this base token.
     /// For `ft_transfer` and `ft_transfer_call` `memo` is the `description` of the proposal.
+    /// If `register_receiver` is set, registers the receiver on the token first with `storage_deposit`,
+    /// paid from the DAO's balance. Fails if this base token.
+    /// If `Config::claimable_payouts` is set and there is no `msg`, the amount is credited to
+    /// the receiver to `claim` instead.
     Transfer {
         /// Can be "" for $NEAR or a valid account id.
         token_id: OldAccountId,
         receiver_id: AccountId,
         amount: U128,
         msg: Option<String>,
+        #[serde(default)]
+        register_receiver: bool,
     },
     /// Just a signaling vote, with no execution.
     Vote,
@@ -101,6 +210,48 @@ pub enum ProposalKind {
     ChangePolicyUpdateParameters { parameters: PolicyParameters },
     /// Suggestion to be seen by councils and proposed by members
     Suggestion{ suggestion: String },
+    /// Transfers `bps` basis points of the spendable balance of `token_id` at execution time
+    /// from this DAO to `receiver_id`. Tokens use the balance last fetched with `refresh_ft_balance`,
+    /// and the computed amount stays reserved until the transfer is done.
+    /// If `Config::claimable_payouts` is set, the amount is credited to the receiver to `claim` instead.
+    TransferPercent {
+        /// Can be "" for $NEAR or a valid account id.
+        token_id: OldAccountId,
+        receiver_id: AccountId,
+        bps: u16,
+    },
+    /// Transfers each (token, amount) of `transfers` from this DAO to `receiver_id`.
+    /// Token can be "" for $NEAR. The proposal fails if any of the transfers fails, retrying it
+    /// only sends the transfers that failed.
+    MultiTokenTransfer {
+        receiver_id: AccountId,
+        transfers: Vec<(OldAccountId, U128)>,
+    },
+    /// Poll among several options, voted on with `VoteOption`. Informational, nothing is
+    /// executed: the option with the most weight is stored once the voting period is over.
+    Poll { options: Vec<String> },
+    /// Mints `amount` of the governance token to `receiver_id`.
+    TokenMint {
+        receiver_id: AccountId,
+        amount: U128,
+    },
+    /// Burns `amount` of the governance token held by this DAO.
+    TokenBurn { amount: U128 },
+    /// Stakes `amount` of $NEAR from the treasury with the staking pool `pool_id`.
+    StakeDelegate { pool_id: AccountId, amount: U128 },
+    /// Unstakes `amount` of $NEAR from the staking pool `pool_id`, to be withdrawn later with
+    /// `claim_staking_rewards`.
+    StakeUnstake { pool_id: AccountId, amount: U128 },
+    /// Streams `total` of `token_id` to `receiver_id`, vested linearly from `start_block` to
+    /// `end_block` and withdrawn with `withdraw_stream`. Streamed $NEAR is locked on approval.
+    StreamCreate {
+        /// Can be "" for $NEAR or a valid account id.
+        token_id: OldAccountId,
+        receiver_id: AccountId,
+        total: U128,
+        start_block: BlockHeight,
+        end_block: BlockHeight,
+    },
 }
 
 
@@ -124,10 +275,155 @@ impl ProposalKind {
             }
             ProposalKind::ChangePolicyUpdateParameters { .. } => "policy_update_parameters",
             ProposalKind::Suggestion { .. } => "give a suggestion",
+            ProposalKind::TransferPercent { .. } => "transfer_percent",
+            ProposalKind::MultiTokenTransfer { .. } => "multi_token_transfer",
+            ProposalKind::Poll { .. } => "poll",
+            ProposalKind::TokenMint { .. } => "token_mint",
+            ProposalKind::TokenBurn { .. } => "token_burn",
+            ProposalKind::StakeDelegate { .. } => "stake_delegate",
+            ProposalKind::StakeUnstake { .. } => "stake_unstake",
+            ProposalKind::StreamCreate { .. } => "stream_create",
         }
     }
 }
 
+/// Names of all proposal kinds with their policy labels, matching `ProposalKind::to_policy_label`.
+pub const PROPOSAL_KIND_LABELS: &[(&str, &str)] = &[
+    ("ChangeConfig", "config"),
+    ("ChangePolicy", "policy"),
+    ("AddMemberToRole", "add_member_to_role"),
+    ("RemoveMemberFromRole", "remove_member_from_role"),
+    ("FunctionCall", "call"),
+    ("UpgradeSelf", "upgrade_self"),
+    ("UpgradeRemote", "upgrade_remote"),
+    ("Transfer", "transfer"),
+    ("Vote", "vote"),
+    ("ChangePolicyAddOrUpdateRole", "policy_add_or_update_role"),
+    ("ChangePolicyRemoveRole", "policy_remove_role"),
+    (
+        "ChangePolicyUpdateDefaultVotePolicy",
+        "policy_update_default_vote_policy",
+    ),
+    ("ChangePolicyUpdateParameters", "policy_update_parameters"),
+    ("Suggestion", "give a suggestion"),
+    ("TransferPercent", "transfer_percent"),
+    ("MultiTokenTransfer", "multi_token_transfer"),
+    ("Poll", "poll"),
+    ("TokenMint", "token_mint"),
+    ("TokenBurn", "token_burn"),
+    ("StakeDelegate", "stake_delegate"),
+    ("StakeUnstake", "stake_unstake"),
+    ("StreamCreate", "stream_create"),
+];
+
+/// JSON schema of `ProposalKind` as accepted in `ProposalInput::kind`, for form generators.
+/// Nested config and policy types are only described as objects, see their own docs.
+/// Has to be kept in sync with the enum: the tests check every kind against it.
+pub const PROPOSAL_KIND_SCHEMA: &str = r##"{
+  "$schema": "http://json-schema.org/draft-07/schema#",
+  "title": "ProposalKind",
+  "definitions": {
+    "AccountId": { "type": "string" },
+    "TokenId": { "type": "string", "description": "\"\" for $NEAR or a token account id" },
+    "U128": { "type": "string", "pattern": "^[0-9]+$" },
+    "Base58CryptoHash": { "type": "string" },
+    "Config": { "type": "object" },
+    "VersionedPolicy": { "type": "object" },
+    "ActionCall": { "type": "object" },
+    "RolePermission": { "type": "object" },
+    "VotePolicy": { "type": "object" },
+    "PolicyParameters": { "type": "object" }
+  },
+  "oneOf": [
+    { "title": "ChangeConfig", "type": "object", "required": ["ChangeConfig"], "properties": { "ChangeConfig": {
+      "type": "object", "required": ["config"], "properties": {
+        "config": { "$ref": "#/definitions/Config" },
+        "effective_block": { "type": ["integer", "null"] } } } } },
+    { "title": "ChangePolicy", "type": "object", "required": ["ChangePolicy"], "properties": { "ChangePolicy": {
+      "type": "object", "required": ["policy"], "properties": {
+        "policy": { "$ref": "#/definitions/VersionedPolicy" } } } } },
+    { "title": "AddMemberToRole", "type": "object", "required": ["AddMemberToRole"], "properties": { "AddMemberToRole": {
+      "type": "object", "required": ["member_id", "role"], "properties": {
+        "member_id": { "$ref": "#/definitions/AccountId" },
+        "role": { "type": "string" },
+        "term_end_block": { "type": ["integer", "null"] } } } } },
+    { "title": "RemoveMemberFromRole", "type": "object", "required": ["RemoveMemberFromRole"], "properties": { "RemoveMemberFromRole": {
+      "type": "object", "required": ["member_id", "role"], "properties": {
+        "member_id": { "$ref": "#/definitions/AccountId" },
+        "role": { "type": "string" } } } } },
+    { "title": "FunctionCall", "type": "object", "required": ["FunctionCall"], "properties": { "FunctionCall": {
+      "type": "object", "required": ["receiver_id", "actions"], "properties": {
+        "receiver_id": { "$ref": "#/definitions/AccountId" },
+        "actions": { "type": "array", "items": { "$ref": "#/definitions/ActionCall" } } } } } },
+    { "title": "UpgradeSelf", "type": "object", "required": ["UpgradeSelf"], "properties": { "UpgradeSelf": {
+      "type": "object", "required": ["hash"], "properties": {
+        "hash": { "$ref": "#/definitions/Base58CryptoHash" } } } } },
+    { "title": "UpgradeRemote", "type": "object", "required": ["UpgradeRemote"], "properties": { "UpgradeRemote": {
+      "type": "object", "required": ["receiver_id", "method_name", "hash"], "properties": {
+        "receiver_id": { "$ref": "#/definitions/AccountId" },
+        "method_name": { "type": "string" },
+        "hash": { "$ref": "#/definitions/Base58CryptoHash" } } } } },
+    { "title": "Transfer", "type": "object", "required": ["Transfer"], "properties": { "Transfer": {
+      "type": "object", "required": ["token_id", "receiver_id", "amount"], "properties": {
+        "token_id": { "$ref": "#/definitions/TokenId" },
+        "receiver_id": { "$ref": "#/definitions/AccountId" },
+        "amount": { "$ref": "#/definitions/U128" },
+        "msg": { "type": ["string", "null"] },
+        "register_receiver": { "type": "boolean" } } } } },
+    { "title": "Vote", "const": "Vote" },
+    { "title": "ChangePolicyAddOrUpdateRole", "type": "object", "required": ["ChangePolicyAddOrUpdateRole"], "properties": { "ChangePolicyAddOrUpdateRole": {
+      "type": "object", "required": ["role"], "properties": {
+        "role": { "$ref": "#/definitions/RolePermission" } } } } },
+    { "title": "ChangePolicyRemoveRole", "type": "object", "required": ["ChangePolicyRemoveRole"], "properties": { "ChangePolicyRemoveRole": {
+      "type": "object", "required": ["role"], "properties": {
+        "role": { "type": "string" } } } } },
+    { "title": "ChangePolicyUpdateDefaultVotePolicy", "type": "object", "required": ["ChangePolicyUpdateDefaultVotePolicy"], "properties": { "ChangePolicyUpdateDefaultVotePolicy": {
+      "type": "object", "required": ["vote_policy"], "properties": {
+        "vote_policy": { "$ref": "#/definitions/VotePolicy" } } } } },
+    { "title": "ChangePolicyUpdateParameters", "type": "object", "required": ["ChangePolicyUpdateParameters"], "properties": { "ChangePolicyUpdateParameters": {
+      "type": "object", "required": ["parameters"], "properties": {
+        "parameters": { "$ref": "#/definitions/PolicyParameters" } } } } },
+    { "title": "Suggestion", "type": "object", "required": ["Suggestion"], "properties": { "Suggestion": {
+      "type": "object", "required": ["suggestion"], "properties": {
+        "suggestion": { "type": "string" } } } } },
+    { "title": "TransferPercent", "type": "object", "required": ["TransferPercent"], "properties": { "TransferPercent": {
+      "type": "object", "required": ["token_id", "receiver_id", "bps"], "properties": {
+        "token_id": { "$ref": "#/definitions/TokenId" },
+        "receiver_id": { "$ref": "#/definitions/AccountId" },
+        "bps": { "type": "integer", "minimum": 1, "maximum": 10000 } } } } },
+    { "title": "MultiTokenTransfer", "type": "object", "required": ["MultiTokenTransfer"], "properties": { "MultiTokenTransfer": {
+      "type": "object", "required": ["receiver_id", "transfers"], "properties": {
+        "receiver_id": { "$ref": "#/definitions/AccountId" },
+        "transfers": { "type": "array", "items": { "type": "array", "items": [
+          { "$ref": "#/definitions/TokenId" }, { "$ref": "#/definitions/U128" } ] } } } } } },
+    { "title": "Poll", "type": "object", "required": ["Poll"], "properties": { "Poll": {
+      "type": "object", "required": ["options"], "properties": {
+        "options": { "type": "array", "items": { "type": "string" } } } } } },
+    { "title": "TokenMint", "type": "object", "required": ["TokenMint"], "properties": { "TokenMint": {
+      "type": "object", "required": ["receiver_id", "amount"], "properties": {
+        "receiver_id": { "$ref": "#/definitions/AccountId" },
+        "amount": { "$ref": "#/definitions/U128" } } } } },
+    { "title": "TokenBurn", "type": "object", "required": ["TokenBurn"], "properties": { "TokenBurn": {
+      "type": "object", "required": ["amount"], "properties": {
+        "amount": { "$ref": "#/definitions/U128" } } } } },
+    { "title": "StakeDelegate", "type": "object", "required": ["StakeDelegate"], "properties": { "StakeDelegate": {
+      "type": "object", "required": ["pool_id", "amount"], "properties": {
+        "pool_id": { "$ref": "#/definitions/AccountId" },
+        "amount": { "$ref": "#/definitions/U128" } } } } },
+    { "title": "StakeUnstake", "type": "object", "required": ["StakeUnstake"], "properties": { "StakeUnstake": {
+      "type": "object", "required": ["pool_id", "amount"], "properties": {
+        "pool_id": { "$ref": "#/definitions/AccountId" },
+        "amount": { "$ref": "#/definitions/U128" } } } } },
+    { "title": "StreamCreate", "type": "object", "required": ["StreamCreate"], "properties": { "StreamCreate": {
+      "type": "object", "required": ["token_id", "receiver_id", "total", "start_block", "end_block"], "properties": {
+        "token_id": { "$ref": "#/definitions/TokenId" },
+        "receiver_id": { "$ref": "#/definitions/AccountId" },
+        "total": { "$ref": "#/definitions/U128" },
+        "start_block": { "type": "integer" },
+        "end_block": { "type": "integer" } } } } }
+  ]
+}"##;
+
 /// Votes recorded in the proposal.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
@@ -137,12 +433,15 @@ pub enum Vote {
     Remove = 0x2,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
 #[serde(crate = "near_sdk::serde")]
 pub struct VoteWithTimestamp {
     pub vote: Vote,
     pub blocknumber: BlockHeight,
+    /// Public rationale given by the voter.
+    #[serde(default)]
+    pub reason: Option<String>,
 }
 
 impl From<Action> for Vote {
@@ -157,7 +456,7 @@ impl From<Action> for Vote {
 }
 
 /// Proposal that are sent to this DAO.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
 #[serde(crate = "near_sdk::serde")]
 pub struct Proposal {
@@ -173,60 +472,475 @@ pub struct Proposal {
     pub vote_counts: HashMap<String, [Balance; 3]>,
     /// Map of who voted and how.
     pub votes: HashMap<AccountId, VoteWithTimestamp>,
+    /// Tags used by interfaces to group proposals.
+    pub tags: Vec<String>,
+    /// Optional category of this proposal.
+    pub category: Option<String>,
+    /// Bond attached by the proposer. Reset to zero once returned or forfeited.
+    pub bond: U128,
     /// The cutoff for when a submitted vote will be rewarded
     pub threshold_block: Option<BlockHeight>,
     /// Submission time (for voting period).
     pub submission_time: U64,
+    /// Whether the voting period of this proposal has already been extended.
+    pub extended: bool,
+    /// Extra time added to the voting period by an extension.
+    pub period_extension: U64,
+    /// Proposal that must be approved before this one can be executed.
+    pub depends_on: Option<u64>,
+    /// Number of times the execution of this proposal has failed.
+    pub retry_count: u32,
+    /// Hash of the off-chain document of this proposal.
+    pub reference_hash: Option<Base64VecU8>,
+    /// Where the off-chain document of this proposal can be found.
+    pub reference_url: Option<String>,
+    /// Index of the transfer that failed in the last execution of a `MultiTokenTransfer`.
+    pub failed_leg: Option<u32>,
+    /// Weight given to each option of a `Poll`.
+    pub poll_counts: HashMap<usize, Balance>,
+    /// Option each member voted for in a `Poll`.
+    pub poll_votes: HashMap<AccountId, usize>,
+    /// Option of a `Poll` with the most weight, once the voting period is over.
+    pub poll_winner: Option<usize>,
+    /// Only account allowed to trigger the execution of this proposal, if any.
+    pub executor: Option<AccountId>,
+    /// End of the commit phase if votes are committed and revealed, as a timestamp.
+    pub commit_end: Option<U64>,
+    /// Vote hashes committed to and not revealed yet, per voter.
+    pub commits: HashMap<AccountId, Base64VecU8>,
+    /// Approve, reject and abstain shares of the weight of each split voter, in basis points.
+    pub split_votes: HashMap<AccountId, [u16; 3]>,
+    /// Roles that must each reach their approval threshold for this proposal to be approved.
+    /// Any role can approve it if empty.
+    pub required_roles: Vec<String>,
+    /// Accounts that must confirm backing this proposal before it opens for voting.
+    pub cosponsors: Vec<AccountId>,
+    /// Co-sponsors that confirmed, in confirmation order.
+    pub confirmed_cosponsors: Vec<AccountId>,
+    /// Only accounts allowed to vote on this proposal, on top of the role checks.
+    /// Anyone with the permission can vote if empty.
+    pub voter_whitelist: Vec<AccountId>,
+    /// Indexes of the transfers of a `MultiTokenTransfer` already paid, skipped when it's retried.
+    /// A `Transfer` has its receiver as transfer `0`.
+    pub paid_legs: Vec<u32>,
+    /// Whether the transfer fee of this proposal was already paid, so a retry doesn't send it
+    /// again. A fee that fails to transfer fails the execution and is sent again by a retry.
+    pub fee_paid: bool,
+    /// Amount of tokens a `TransferPercent` is sending, reserved until its callback.
+    pub percent_amount: Option<U128>,
+    /// Receiver and amount of the fee taken off a `Transfer`, fixed when it's first executed so
+    /// config changes don't apply to its callback and retries.
+    pub transfer_fee: Option<(AccountId, U128)>,
+    /// Block at which this proposal was submitted.
+    pub submission_block: BlockHeight,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
-#[serde(crate = "near_sdk::serde")]
+/// Vote as it was stored before votes had a rationale.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct VoteWithTimestampV1 {
+    pub vote: Vote,
+    pub blocknumber: BlockHeight,
+}
+
+/// Proposal as it was stored in the first version of the contract.
+/// Only used to read old proposals, new proposals are always stored as the latest version.
+/// Its kind keeps the layout of that version, so later changes to `ProposalKind` don't affect it.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ProposalV1 {
+    pub proposer: AccountId,
+    pub description: String,
+    pub kind: OldProposalKind,
+    pub status: ProposalStatus,
+    pub vote_counts: HashMap<String, [Balance; 3]>,
+    pub votes: HashMap<AccountId, VoteWithTimestampV1>,
+    pub threshold_block: Option<BlockHeight>,
+    pub submission_time: U64,
+}
+
+impl From<ProposalV1> for Proposal {
+    fn from(v1: ProposalV1) -> Self {
+        Self {
+            proposer: v1.proposer,
+            description: v1.description,
+            kind: v1.kind.into(),
+            status: v1.status,
+            vote_counts: v1.vote_counts,
+            votes: v1
+                .votes
+                .into_iter()
+                .map(|(account_id, vote)| {
+                    (
+                        account_id,
+                        VoteWithTimestamp {
+                            vote: vote.vote,
+                            blocknumber: vote.blocknumber,
+                            reason: None,
+                        },
+                    )
+                })
+                .collect(),
+            tags: vec![],
+            category: None,
+            bond: U128(0),
+            threshold_block: v1.threshold_block,
+            submission_time: v1.submission_time,
+            extended: false,
+            period_extension: U64(0),
+            depends_on: None,
+            retry_count: 0,
+            reference_hash: None,
+            reference_url: None,
+            failed_leg: None,
+            poll_counts: HashMap::default(),
+            poll_votes: HashMap::default(),
+            poll_winner: None,
+            executor: None,
+            commit_end: None,
+            commits: HashMap::default(),
+            split_votes: HashMap::default(),
+            required_roles: vec![],
+            cosponsors: vec![],
+            confirmed_cosponsors: vec![],
+            voter_whitelist: vec![],
+            paid_legs: vec![],
+            fee_paid: false,
+            percent_amount: None,
+            transfer_fee: None,
+            submission_block: 0,
+        }
+    }
+}
+
+/// Versioned proposal, as stored in the state.
+/// `Default` proposals are upgraded to the latest version when read and rewritten as `V2`
+/// the next time they are acted on.
+#[derive(BorshSerialize, BorshDeserialize)]
+// Only read from the state and converted right away, so the size difference doesn't matter.
+#[allow(clippy::large_enum_variant)]
 pub enum VersionedProposal {
-    Default(Proposal),
+    Default(ProposalV1),
+    V2(Proposal),
+}
+
+impl VersionedProposal {
+    /// Returns if this proposal is stored in the latest version.
+    pub fn is_latest(&self) -> bool {
+        matches!(self, VersionedProposal::V2(_))
+    }
 }
 
 impl From<VersionedProposal> for Proposal {
     fn from(v: VersionedProposal) -> Self {
         match v {
-            VersionedProposal::Default(p) => p,
+            VersionedProposal::Default(p) => p.into(),
+            VersionedProposal::V2(p) => p,
         }
     }
 }
 
 impl Proposal {
+    /// Amounts a `Transfer` still has to send to its receiver and as fee, zero once paid.
+    /// Zero for other kinds.
+    pub fn unpaid_transfer(&self) -> (Balance, Balance) {
+        let amount = match &self.kind {
+            ProposalKind::Transfer { amount, .. } => amount.0,
+            _ => return (0, 0),
+        };
+        let fee = self
+            .transfer_fee
+            .as_ref()
+            .map(|(_, fee)| fee.0)
+            .unwrap_or(0);
+        let receiver_amount = if self.paid_legs.contains(&0) {
+            0
+        } else {
+            amount - fee
+        };
+        (receiver_amount, if self.fee_paid { 0 } else { fee })
+    }
+
+    /// Transfers of a `MultiTokenTransfer` that haven't been paid yet, with their index.
+    /// Empty for other kinds.
+    pub fn unpaid_legs(&self) -> Vec<(u32, (OldAccountId, U128))> {
+        match &self.kind {
+            ProposalKind::MultiTokenTransfer { transfers, .. } => transfers
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(leg, transfer)| (leg as u32, transfer))
+                .filter(|(leg, _)| !self.paid_legs.contains(leg))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether given account passes the voter whitelist of this proposal, if it has one.
+    pub fn is_whitelisted_voter(&self, account_id: &AccountId) -> bool {
+        self.voter_whitelist.is_empty() || self.voter_whitelist.contains(account_id)
+    }
+
+    /// Whether a voter with given stake adds any weight through given roles. Voters without stake
+    /// can only vote through roles that aren't token weighted for the kind of this proposal.
+    pub fn has_voting_power(&self, roles: &[String], policy: &Policy, stake: Balance) -> bool {
+        let label = self.kind.to_policy_label().to_string();
+        stake > 0
+            || roles
+                .iter()
+                .any(|role| !policy.is_token_weighted(role, &label))
+    }
+
     /// Adds vote of the given user If user already voted, fails.
+    /// Fails with `ERR_VOTE_OVERFLOW` if a tally would overflow.
+    #[allow(clippy::too_many_arguments)]
       pub fn update_votes(
         &mut self,
         account_id: &AccountId,
         roles: &[String],
         vote: Vote,
         policy: &Policy,
+        reason: Option<String>,
+        stake: Balance,
+        lockup: Option<StakeLockup>,
+        total_supply: Balance,
     ) {
+        assert!(
+            !self.poll_votes.contains_key(account_id) && !self.split_votes.contains_key(account_id),
+            "ERR_ALREADY_VOTED"
+        );
         for role in roles {
-            let amount = if policy.is_token_weighted(role, &self.kind.to_policy_label().to_string())
-            {
-                1
-            } else {
-                1
-            };
-            self.vote_counts.entry(role.clone()).or_insert([0u128; 3])[vote.clone() as usize] +=
-                amount;
+            let amount = self.vote_weight(role, policy, stake, lockup.as_ref(), total_supply);
+            let count = &mut self.vote_counts.entry(role.clone()).or_insert([0u128; 3])
+                [vote.clone() as usize];
+            *count = count.checked_add(amount).expect("ERR_VOTE_OVERFLOW");
+        }
+        assert!(
+            self.votes
+                .insert(
+                    account_id.clone(),
+                    VoteWithTimestamp {
+                        vote,
+                        blocknumber: env::block_height(),
+                        reason,
+                    }
+                )
+                .is_none(),
+            "ERR_ALREADY_VOTED"
+        );
+    }
+
+    /// Adds the weight of given user split in `shares` of approve, reject and abstain basis
+    /// points, rounded down. Abstaining weight isn't counted. If user already voted, fails.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_split_vote(
+        &mut self,
+        account_id: &AccountId,
+        roles: &[String],
+        shares: [u16; 3],
+        policy: &Policy,
+        stake: Balance,
+        lockup: Option<StakeLockup>,
+        total_supply: Balance,
+    ) {
+        assert_eq!(
+            shares.iter().map(|share| *share as u32).sum::<u32>(),
+            10_000,
+            "ERR_INVALID_SPLIT"
+        );
+        assert!(
+            !self.votes.contains_key(account_id)
+                && !self.poll_votes.contains_key(account_id)
+                && self
+                    .split_votes
+                    .insert(account_id.clone(), shares)
+                    .is_none(),
+            "ERR_ALREADY_VOTED"
+        );
+        for role in roles {
+            let amount = self.vote_weight(role, policy, stake, lockup.as_ref(), total_supply);
+            let counts = self.vote_counts.entry(role.clone()).or_insert([0u128; 3]);
+            for (vote, share) in [(Vote::Approve, shares[0]), (Vote::Reject, shares[1])] {
+                let share = share as Balance;
+                let part = amount / 10_000 * share + amount % 10_000 * share / 10_000;
+                let count = &mut counts[vote as usize];
+                *count = count.checked_add(part).expect("ERR_VOTE_OVERFLOW");
+            }
+        }
+    }
+
+    /// Weight of a vote through given role. Being a member of the role makes a voter eligible,
+    /// its weight only depends on the weight kind of the role, capped by its `max_weight_bps`
+    /// of `total_supply`.
+    pub(crate) fn vote_weight(
+        &self,
+        role: &String,
+        policy: &Policy,
+        stake: Balance,
+        lockup: Option<&StakeLockup>,
+        total_supply: Balance,
+    ) -> Balance {
+        let label = self.kind.to_policy_label().to_string();
+        let vote_policy = policy.vote_policy(role, &label);
+        let weight = match vote_policy.weight_kind {
+            WeightKind::TokenWeight => policy.to_token_units(stake),
+            WeightKind::RoleWeight => 1,
+            WeightKind::DurationWeight => {
+                policy.to_token_units(lockup.map(|lockup| lockup.weight()).unwrap_or(0))
+            }
+            WeightKind::StakeWeight => {
+                policy.to_token_units(lockup.map(|lockup| lockup.amount.0).unwrap_or(0))
+            }
+        };
+        match vote_policy.max_weight_bps {
+            Some(bps) if vote_policy.weight_kind != WeightKind::RoleWeight => {
+                assert!(total_supply > 0, "ERR_NO_TOTAL_SUPPLY");
+                let cap = policy.to_token_units(total_supply) * bps as Balance / 10_000;
+                std::cmp::min(weight, cap)
+            }
+            _ => weight,
         }
+    }
+
+    /// Adds the weight of given voter to given option of this poll. A member of several roles
+    /// is counted once, with the largest of its weights. Fails with `ERR_VOTE_OVERFLOW` if the
+    /// tally would overflow.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_poll_vote(
+        &mut self,
+        account_id: &AccountId,
+        roles: &[String],
+        option: usize,
+        policy: &Policy,
+        stake: Balance,
+        lockup: Option<StakeLockup>,
+        total_supply: Balance,
+    ) {
         assert!(
-            self.votes.insert(account_id.clone(), VoteWithTimestamp { vote: vote, blocknumber: env::block_height() }).is_none(),
+            !self.votes.contains_key(account_id)
+                && self.poll_votes.insert(account_id.clone(), option).is_none(),
             "ERR_ALREADY_VOTED"
         );
+        let amount = roles
+            .iter()
+            .map(|role| self.vote_weight(role, policy, stake, lockup.as_ref(), total_supply))
+            .max()
+            .unwrap_or(0);
+        let count = self.poll_counts.entry(option).or_insert(0);
+        *count = count.checked_add(amount).expect("ERR_VOTE_OVERFLOW");
+    }
+
+    /// Option of this poll with the most weight, the lowest index on a tie.
+    /// None if no option has been voted for.
+    pub fn leading_option(&self) -> Option<usize> {
+        self.poll_counts
+            .iter()
+            .filter(|(_, weight)| **weight > 0)
+            .max_by(|(a, a_weight), (b, b_weight)| a_weight.cmp(b_weight).then(b.cmp(a)))
+            .map(|(option, _)| *option)
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct ProposalInput {
     /// Description of this proposal.
     pub description: String,
     /// Kind of proposal with relevant information.
     pub kind: ProposalKind,
+    /// Tags used by interfaces to group proposals.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Optional category of this proposal.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Proposal that must be approved before this one can be executed.
+    #[serde(default)]
+    pub depends_on: Option<u64>,
+    /// sha256 hash of the off-chain document of this proposal, for clients to verify it.
+    #[serde(default)]
+    pub reference_hash: Option<Base64VecU8>,
+    /// Where the off-chain document of this proposal can be found, e.g. an IPFS url.
+    #[serde(default)]
+    pub reference_url: Option<String>,
+    /// Key chosen by the client to safely retry adding this proposal. Adding a proposal with
+    /// a key recently used by the same proposer returns the existing proposal instead.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// Only account allowed to trigger the execution once approved. Anyone acting can if unset.
+    #[serde(default)]
+    pub executor: Option<AccountId>,
+    /// If set, votes are committed as hashes until this timestamp and revealed after it,
+    /// up to the deadline. See `commit_vote` and `reveal_vote`.
+    #[serde(default)]
+    pub commit_end: Option<U64>,
+    /// Roles that must each approve, e.g. both a finance and an engineering role for a joint
+    /// decision. Any role reaching its threshold approves if empty.
+    #[serde(default)]
+    pub required_roles: Vec<String>,
+    /// Accounts that must each confirm with `confirm_cosponsorship` before the proposal opens
+    /// for voting. It starts as a `Draft` until then, or right away in progress if empty.
+    #[serde(default)]
+    pub cosponsors: Vec<AccountId>,
+    /// If not empty, only these accounts can vote, and still only with the role permission.
+    #[serde(default)]
+    pub voter_whitelist: Vec<AccountId>,
+}
+
+/// Vote proposal without any of the optional fields, for tests to only set the fields they need.
+#[cfg(test)]
+impl Default for ProposalInput {
+    fn default() -> Self {
+        ProposalInput {
+            description: "test".to_string(),
+            kind: ProposalKind::Vote,
+            tags: vec![],
+            category: None,
+            depends_on: None,
+            reference_hash: None,
+            reference_url: None,
+            idempotency_key: None,
+            executor: None,
+            commit_end: None,
+            required_roles: vec![],
+            cosponsors: vec![],
+            voter_whitelist: vec![],
+        }
+    }
+}
+
+impl ProposalInput {
+    /// Checks that tags and category are within the allowed bounds.
+    pub fn assert_valid_tags(&self) {
+        assert!(self.tags.len() <= MAX_PROPOSAL_TAGS, "ERR_TOO_MANY_TAGS");
+        for tag in self.tags.iter() {
+            assert!(
+                !tag.is_empty() && tag.len() <= MAX_TAG_LENGTH,
+                "ERR_INVALID_TAG_LENGTH"
+            );
+        }
+        if let Some(category) = &self.category {
+            assert!(
+                !category.is_empty() && category.len() <= MAX_TAG_LENGTH,
+                "ERR_INVALID_CATEGORY_LENGTH"
+            );
+        }
+    }
+
+    /// Checks that the reference to the off-chain document is well formed.
+    pub fn assert_valid_reference(&self) {
+        if let Some(reference_hash) = &self.reference_hash {
+            assert_eq!(
+                reference_hash.0.len(),
+                REFERENCE_HASH_LENGTH,
+                "ERR_INVALID_REFERENCE_HASH"
+            );
+        }
+        if let Some(reference_url) = &self.reference_url {
+            assert!(
+                !reference_url.is_empty() && reference_url.len() <= MAX_REFERENCE_URL_LENGTH,
+                "ERR_INVALID_REFERENCE_URL_LENGTH"
+            );
+        }
+    }
 }
 
 impl From<ProposalInput> for Proposal {
@@ -235,29 +949,104 @@ impl From<ProposalInput> for Proposal {
             proposer: env::predecessor_account_id(),
             description: input.description,
             kind: input.kind,
-            status: ProposalStatus::InProgress,
+            status: if input.cosponsors.is_empty() {
+                ProposalStatus::InProgress
+            } else {
+                ProposalStatus::Draft
+            },
             vote_counts: HashMap::default(),
             votes: HashMap::default(),
+            tags: input.tags,
+            category: input.category,
+            bond: U128(env::attached_deposit()),
             threshold_block: None,
-            submission_time: U64::from(env::block_timestamp())
+            submission_time: U64::from(env::block_timestamp()),
+            extended: false,
+            period_extension: U64(0),
+            depends_on: input.depends_on,
+            retry_count: 0,
+            reference_hash: input.reference_hash,
+            reference_url: input.reference_url,
+            failed_leg: None,
+            poll_counts: HashMap::default(),
+            poll_votes: HashMap::default(),
+            poll_winner: None,
+            executor: input.executor,
+            commit_end: input.commit_end,
+            commits: HashMap::default(),
+            split_votes: HashMap::default(),
+            required_roles: input.required_roles,
+            cosponsors: input.cosponsors,
+            confirmed_cosponsors: vec![],
+            voter_whitelist: input.voter_whitelist,
+            paid_legs: vec![],
+            fee_paid: false,
+            percent_amount: None,
+            transfer_fee: None,
+            submission_block: env::block_height(),
+        }
+    }
+}
+
+/// Registers given account on given token with `storage_deposit`, paid from the DAO's balance.
+fn internal_register_receiver(token_id: &AccountId, receiver_id: &AccountId) -> Promise {
+    ext_storage_management::storage_deposit(
+        Some(receiver_id.clone()),
+        Some(true),
+        token_id.clone(),
+        STORAGE_DEPOSIT_FOR_REGISTRATION,
+        GAS_FOR_STORAGE_DEPOSIT,
+    )
+}
+
+/// Checks that a vote reason is only given to votes and isn't too long.
+fn check_reason(action: &Action, reason: &Option<String>) -> Result<(), &'static str> {
+    match reason {
+        Some(_)
+            if !matches!(
+                action,
+                Action::VoteApprove | Action::VoteReject | Action::VoteRemove
+            ) =>
+        {
+            Err("ERR_REASON_ONLY_FOR_VOTES")
         }
+        Some(reason) if reason.len() > MAX_VOTE_REASON_LENGTH => Err("ERR_REASON_TOO_LONG"),
+        _ => Ok(()),
     }
 }
 
 impl Contract {
     /// Execute payout of given token to given user.
+    /// $NEAR transfers carry no memo, so an event with the memo is emitted instead.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn internal_payout(
         &mut self,
+        proposal_id: u64,
         token_id: &Option<AccountId>,
         receiver_id: &AccountId,
         amount: Balance,
         memo: String,
         msg: Option<String>,
+        register_receiver: bool,
     ) -> PromiseOrValue<()> {
         if token_id.is_none() {
+            emit_event(
+                "native_payout",
+                json!({
+                    "proposal_id": U64(proposal_id),
+                    "receiver_id": receiver_id,
+                    "amount": U128(amount),
+                    "memo": memo,
+                }),
+            );
             Promise::new(receiver_id.clone()).transfer(amount).into()
         } else {
-            if let Some(msg) = msg {
+            let gas = self
+                .config
+                .get()
+                .unwrap()
+                .ft_transfer_gas(token_id.as_ref().unwrap());
+            let transfer = if let Some(msg) = msg {
                 ext_fungible_token::ft_transfer_call(
                     receiver_id.clone(),
                     U128(amount),
@@ -265,7 +1054,7 @@ impl Contract {
                     msg,
                     token_id.as_ref().unwrap().clone(),
                     ONE_YOCTO_NEAR,
-                    GAS_FOR_FT_TRANSFER,
+                    gas,
                 )
             } else {
                 ext_fungible_token::ft_transfer(
@@ -274,40 +1063,129 @@ impl Contract {
                     Some(memo),
                     token_id.as_ref().unwrap().clone(),
                     ONE_YOCTO_NEAR,
-                    GAS_FOR_FT_TRANSFER,
+                    gas,
                 )
+            };
+            if register_receiver {
+                internal_register_receiver(token_id.as_ref().unwrap(), receiver_id)
+                    .then(transfer)
+                    .into()
+            } else {
+                transfer.into()
             }
-            .into()
         }
     }
   
     /// Executes given proposal and updates the contract's state.
-    fn internal_execute_proposal(
+    pub(crate) fn internal_execute_proposal(
         &mut self,
         policy: &Policy,
         proposal: &mut Proposal,
         proposal_id: u64,
     ) -> PromiseOrValue<()> {
-    
+        self.assert_not_paused();
+        // Guard against executing the same proposal twice: the stored proposal must not be past
+        // `InProgress` (or `Failed`, which is allowed to re-execute).
+        let stored: Proposal = self
+            .proposals
+            .get(&proposal_id)
+            .expect("ERR_NO_PROPOSAL")
+            .into();
+        // Approved proposals waiting for `execute` are executed once.
+        let awaiting_execution = self.awaiting_execution.remove(&proposal_id);
+        assert!(
+            matches!(
+                stored.status,
+                ProposalStatus::InProgress | ProposalStatus::Failed
+            ) || awaiting_execution,
+            "ERR_PROPOSAL_ALREADY_EXECUTED"
+        );
+        if let Some(dependency) = proposal.depends_on {
+            if !self.internal_is_approved(dependency) {
+                // Can be finalized again once the dependency is approved.
+                proposal.status = ProposalStatus::Failed;
+                self.proposals
+                    .insert(&proposal_id, &VersionedProposal::V2(proposal.clone()));
+                emit_event(
+                    "proposal_failed",
+                    json!({
+                        "proposal_id": U64(proposal_id),
+                        "reason": "ERR_DEPENDENCY_NOT_APPROVED",
+                    }),
+                );
+                return PromiseOrValue::Value(());
+            }
+        }
+        // Persist the new status before scheduling any external calls.
+        self.proposals
+            .insert(&proposal_id, &VersionedProposal::V2(proposal.clone()));
+        self.internal_notify_observer(proposal_id, "proposal_approved", &proposal.status);
+        let mut percent_amount = None;
+        if let ProposalKind::Transfer { amount, .. } = &proposal.kind {
+            if proposal.transfer_fee.is_none() {
+                let config = self.config.get().unwrap();
+                let fee = config.transfer_fee(amount.0);
+                proposal.transfer_fee = config
+                    .fee_receiver
+                    .filter(|_| fee > 0)
+                    .map(|fee_receiver| (fee_receiver, U128(fee)));
+            }
+        }
         let result = match &proposal.kind {
-            ProposalKind::ChangeConfig { config } => {
-                self.config.set(config);
+            ProposalKind::ChangeConfig {
+                config,
+                effective_block,
+            } => {
+                match effective_block {
+                    Some(block) if env::block_height() < *block => {
+                        self.scheduled_configs.insert(&proposal_id);
+                        log!("Config scheduled for block {}", block);
+                    }
+                    _ => {
+                        self.internal_set_config(config, proposal_id, &proposal.proposer);
+                    }
+                }
                 PromiseOrValue::Value(())
             }
             ProposalKind::ChangePolicy { policy } => {
-                self.policy.set(policy);
+                self.internal_set_policy(policy, proposal_id, proposal.kind.to_policy_label());
                 PromiseOrValue::Value(())
             }
-            ProposalKind::AddMemberToRole { member_id, role } => {
+            ProposalKind::AddMemberToRole {
+                member_id,
+                role,
+                term_end_block,
+            } => {
                 let mut new_policy = policy.clone();
                 new_policy.add_member_to_role(role, &member_id.clone().into());
-                self.policy.set(&VersionedPolicy::Current(new_policy));
+                self.member_since
+                    .insert(&(role.clone(), member_id.clone()), &env::block_height());
+                let membership = (role.clone(), member_id.clone());
+                match term_end_block {
+                    Some(term_end_block) => {
+                        self.member_terms.insert(&membership, term_end_block);
+                    }
+                    None => {
+                        self.member_terms.remove(&membership);
+                    }
+                }
+                self.internal_set_policy(
+                    &VersionedPolicy::Current(new_policy),
+                    proposal_id,
+                    proposal.kind.to_policy_label(),
+                );
                 PromiseOrValue::Value(())
             }
             ProposalKind::RemoveMemberFromRole { member_id, role } => {
                 let mut new_policy = policy.clone();
                 new_policy.remove_member_from_role(role, &member_id.clone().into());
-                self.policy.set(&VersionedPolicy::Current(new_policy));
+                self.member_since.remove(&(role.clone(), member_id.clone()));
+                self.member_terms.remove(&(role.clone(), member_id.clone()));
+                self.internal_set_policy(
+                    &VersionedPolicy::Current(new_policy),
+                    proposal_id,
+                    proposal.kind.to_policy_label(),
+                );
                 PromiseOrValue::Value(())
             }
             ProposalKind::FunctionCall {
@@ -326,7 +1204,11 @@ impl Contract {
                 promise.into()
             }
             ProposalKind::UpgradeSelf { hash } => {
-                upgrade_using_factory(hash.clone());
+                if self.config.get().unwrap().upgrade_from_blob {
+                    upgrade_self(&CryptoHash::from(*hash));
+                } else {
+                    upgrade_using_factory(hash.clone());
+                }
                 PromiseOrValue::Value(())
             }
             ProposalKind::UpgradeRemote {
@@ -340,62 +1222,366 @@ impl Contract {
             ProposalKind::Transfer {
                 token_id,
                 receiver_id,
-                amount,
                 msg,
-            } => self.internal_payout(
-                &convert_old_to_new_token(token_id),
-                &receiver_id,
-                amount.0,
-                proposal.description.clone(),
-                msg.clone(),
-            ),
-            ProposalKind::Vote => PromiseOrValue::Value(()),
+                register_receiver,
+                ..
+            } => {
+                let (receiver_amount, fee) = proposal.unpaid_transfer();
+                let fee_receiver = proposal
+                    .transfer_fee
+                    .as_ref()
+                    .map(|(fee_receiver, _)| fee_receiver);
+                if msg.is_none() && self.config.get().unwrap().claimable_payouts {
+                    if let Some(fee_receiver) = fee_receiver.filter(|_| fee > 0) {
+                        self.internal_credit_claimable(fee_receiver, token_id, fee);
+                    }
+                    self.internal_credit_claimable(receiver_id, token_id, receiver_amount);
+                    PromiseOrValue::Value(())
+                } else {
+                    let token_id = convert_old_to_new_token(token_id);
+                    if let Some(token_id) = &token_id {
+                        self.internal_add_pending_transfer(token_id, receiver_amount + fee);
+                    }
+                    // Joined so the callback gets the result of the fee and of the transfer to
+                    // the receiver. A retry only sends the ones that failed.
+                    let fee_transfer = match fee_receiver.filter(|_| fee > 0) {
+                        Some(fee_receiver) => match self.internal_payout(
+                            proposal_id,
+                            &token_id,
+                            fee_receiver,
+                            fee,
+                            proposal.description.clone(),
+                            None,
+                            false,
+                        ) {
+                            PromiseOrValue::Promise(fee_transfer) => Some(fee_transfer),
+                            PromiseOrValue::Value(()) => unreachable!(),
+                        },
+                        None => None,
+                    };
+                    let transfer = if receiver_amount > 0 {
+                        let transfer = match self.internal_payout(
+                            proposal_id,
+                            &token_id,
+                            receiver_id,
+                            receiver_amount,
+                            proposal.description.clone(),
+                            msg.clone(),
+                            false,
+                        ) {
+                            PromiseOrValue::Promise(transfer) => transfer,
+                            PromiseOrValue::Value(()) => unreachable!(),
+                        };
+                        Some(if *register_receiver {
+                            internal_register_receiver(token_id.as_ref().unwrap(), receiver_id)
+                                .then(transfer)
+                        } else {
+                            transfer
+                        })
+                    } else {
+                        None
+                    };
+                    match (fee_transfer, transfer) {
+                        (Some(fee_transfer), Some(transfer)) => fee_transfer.and(transfer).into(),
+                        (Some(promise), None) | (None, Some(promise)) => promise.into(),
+                        (None, None) => PromiseOrValue::Value(()),
+                    }
+                }
+            }
+            ProposalKind::TransferPercent {
+                token_id,
+                receiver_id,
+                bps,
+            } => {
+                let balance = self.internal_spendable_balance(token_id);
+                // Split to not overflow on large token balances.
+                let amount = balance / 10_000 * *bps as Balance
+                    + balance % 10_000 * *bps as Balance / 10_000;
+                if self.config.get().unwrap().claimable_payouts {
+                    self.internal_credit_claimable(receiver_id, token_id, amount);
+                    PromiseOrValue::Value(())
+                } else {
+                    if let Some(ft_token_id) = convert_old_to_new_token(token_id) {
+                        self.internal_add_pending_transfer(&ft_token_id, amount);
+                        percent_amount = Some(U128(amount));
+                    }
+                    self.internal_payout(
+                        proposal_id,
+                        &convert_old_to_new_token(token_id),
+                        receiver_id,
+                        amount,
+                        proposal.description.clone(),
+                        None,
+                        false,
+                    )
+                }
+            }
+            ProposalKind::MultiTokenTransfer { receiver_id, .. } => {
+                if self.config.get().unwrap().claimable_payouts {
+                    for (_, (token_id, amount)) in proposal.unpaid_legs() {
+                        self.internal_credit_claimable(receiver_id, &token_id, amount.0);
+                    }
+                    PromiseOrValue::Value(())
+                } else {
+                    // Joined so the callback gets the result of each transfer. Legs paid by
+                    // a previous execution are skipped, so a retry only pays the failed ones.
+                    let mut joint: Option<Promise> = None;
+                    for (_, (token_id, amount)) in proposal.unpaid_legs() {
+                        let token_id = convert_old_to_new_token(&token_id);
+                        if let Some(token_id) = &token_id {
+                            self.internal_add_pending_transfer(token_id, amount.0);
+                        }
+                        let leg = match self.internal_payout(
+                            proposal_id,
+                            &token_id,
+                            receiver_id,
+                            amount.0,
+                            proposal.description.clone(),
+                            None,
+                            false,
+                        ) {
+                            PromiseOrValue::Promise(promise) => promise,
+                            PromiseOrValue::Value(()) => unreachable!(),
+                        };
+                        joint = Some(match joint {
+                            Some(joint) => joint.and(leg),
+                            None => leg,
+                        });
+                    }
+                    joint.expect("ERR_NO_TRANSFERS").into()
+                }
+            }
+            ProposalKind::TokenMint {
+                receiver_id,
+                amount,
+            } => ext_governance_token::mint(
+                receiver_id.clone(),
+                *amount,
+                self.internal_governance_token(),
+                0,
+                GAS_FOR_MINT_OR_BURN,
+            )
+            .into(),
+            ProposalKind::TokenBurn { amount } => ext_governance_token::burn(
+                *amount,
+                self.internal_governance_token(),
+                0,
+                GAS_FOR_MINT_OR_BURN,
+            )
+            .into(),
+            ProposalKind::StakeDelegate { pool_id, amount } => {
+                ext_staking_pool::deposit_and_stake(pool_id.clone(), amount.0, GAS_FOR_STAKING)
+                    .into()
+            }
+            ProposalKind::StakeUnstake { pool_id, amount } => {
+                ext_staking_pool::unstake(*amount, pool_id.clone(), 0, GAS_FOR_STAKING).into()
+            }
+            ProposalKind::StreamCreate {
+                token_id,
+                receiver_id,
+                total,
+                start_block,
+                end_block,
+            } => {
+                self.internal_create_stream(
+                    proposal_id,
+                    Stream {
+                        token_id: token_id.clone(),
+                        receiver_id: receiver_id.clone(),
+                        total: *total,
+                        start_block: *start_block,
+                        end_block: *end_block,
+                        withdrawn: U128(0),
+                    },
+                );
+                PromiseOrValue::Value(())
+            }
+            ProposalKind::Vote | ProposalKind::Poll { .. } => PromiseOrValue::Value(()),
             ProposalKind::ChangePolicyAddOrUpdateRole { role } => {
                 let mut new_policy = policy.clone();
                 new_policy.add_or_update_role(role);
-                self.policy.set(&VersionedPolicy::Current(new_policy));
+                self.internal_set_policy(
+                    &VersionedPolicy::Current(new_policy),
+                    proposal_id,
+                    proposal.kind.to_policy_label(),
+                );
                 PromiseOrValue::Value(())
             }
             ProposalKind::ChangePolicyRemoveRole { role } => {
                 let mut new_policy = policy.clone();
                 new_policy.remove_role(role);
-                self.policy.set(&VersionedPolicy::Current(new_policy));
+                self.internal_set_policy(
+                    &VersionedPolicy::Current(new_policy),
+                    proposal_id,
+                    proposal.kind.to_policy_label(),
+                );
                 PromiseOrValue::Value(())
             }
             ProposalKind::ChangePolicyUpdateDefaultVotePolicy { vote_policy } => {
                 let mut new_policy = policy.clone();
                 new_policy.update_default_vote_policy(vote_policy);
-                self.policy.set(&VersionedPolicy::Current(new_policy));
+                self.internal_set_policy(
+                    &VersionedPolicy::Current(new_policy),
+                    proposal_id,
+                    proposal.kind.to_policy_label(),
+                );
                 PromiseOrValue::Value(())
             }
             ProposalKind::ChangePolicyUpdateParameters { parameters } => {
                 let mut new_policy = policy.clone();
                 new_policy.update_parameters(parameters);
-                self.policy.set(&VersionedPolicy::Current(new_policy));
+                self.internal_set_policy(
+                    &VersionedPolicy::Current(new_policy),
+                    proposal_id,
+                    proposal.kind.to_policy_label(),
+                );
                 PromiseOrValue::Value(())
             }
             ProposalKind::Suggestion { suggestion } => {
                 log!("{}", &suggestion);
                 PromiseOrValue::Value(())}
         };
+        if percent_amount.is_some() {
+            proposal.percent_amount = percent_amount;
+        }
         match result {
-            PromiseOrValue::Promise(promise) => promise
-                .then(ext_self::on_proposal_callback(
-                    proposal_id,
-                    env::current_account_id(),
-                    0,
-                    GAS_FOR_FT_TRANSFER,
-                ))
-                .into(),
-            PromiseOrValue::Value(()) => PromiseOrValue::Value(()),
+            PromiseOrValue::Promise(promise) => {
+                self.pending_executions.insert(&proposal_id);
+                promise
+                    .then(ext_self::on_proposal_callback(
+                        proposal_id,
+                        env::current_account_id(),
+                        0,
+                        Gas(self.config.get().unwrap().callback_gas.0),
+                    ))
+                    .into()
+            }
+            PromiseOrValue::Value(()) => {
+                self.internal_return_bond(proposal);
+                self.internal_notify_observer(proposal_id, "proposal_executed", &proposal.status);
+                PromiseOrValue::Value(())
+            }
+        }
+    }
+
+    /// Replaces the policy, recording the previous one in the policy history.
+    fn internal_set_policy(&mut self, policy: &VersionedPolicy, proposal_id: u64, label: &str) {
+        let mut history = self.policy_history.get().unwrap_or_default();
+        if history.len() >= MAX_POLICY_HISTORY {
+            history.remove(0);
+        }
+        history.push(PolicyHistoryEntry {
+            block_height: env::block_height(),
+            proposal_id,
+            policy: self.policy.get().unwrap(),
+        });
+        self.policy_history.set(&history);
+        self.policy.set(policy);
+        emit_event(
+            "policy_change",
+            json!({ "proposal_id": U64(proposal_id), "kind": label }),
+        );
+    }
+
+    /// Panics if given proposal is of a high risk kind and its description is too short.
+    fn assert_valid_description(&self, proposal: &ProposalInput) {
+        let config = self.config.get().unwrap();
+        let label = proposal.kind.to_policy_label();
+        let signaling = matches!(
+            proposal.kind,
+            ProposalKind::Vote | ProposalKind::Suggestion { .. }
+        );
+        if !signaling && config.high_risk_kinds.iter().any(|kind| kind == label) {
+            assert!(
+                proposal.description.len() >= config.min_description_length as usize,
+                "ERR_DESCRIPTION_TOO_SHORT"
+            );
         }
     }
+
+    /// Replaces the config, recording the change in the config history.
+    fn internal_set_config(&mut self, config: &Config, proposal_id: u64, proposer: &AccountId) {
+        let mut history = self.config_history.get().unwrap_or_default();
+        if history.len() >= MAX_CONFIG_HISTORY {
+            history.remove(0);
+        }
+        history.push(ConfigHistoryEntry {
+            block_height: env::block_height(),
+            proposal_id,
+            proposer: proposer.clone(),
+            old_config: self.config.get().unwrap(),
+            new_config: config.clone(),
+        });
+        self.config_history.set(&history);
+        self.config.set(config);
+        emit_event(
+            "config_change",
+            json!({ "proposal_id": U64(proposal_id), "proposer": proposer }),
+        );
+    }
+
+    /// Whether given proposal is approved, including approved proposals deleted since.
+    fn internal_is_approved(&self, proposal_id: u64) -> bool {
+        self.pruned_approved.contains(&proposal_id)
+            || self
+                .proposals
+                .get(&proposal_id)
+                .map(|proposal| Proposal::from(proposal).status == ProposalStatus::Approved)
+                .unwrap_or(false)
+    }
+
+    /// Removes deleted proposal from the indexes of its proposer's proposals, of its kind and of
+    /// its tags.
+    fn internal_remove_from_indexes(&mut self, proposal: &Proposal, proposal_id: u64) {
+        let proposer = &proposal.proposer;
+        let mut proposer_ids = self.proposals_by_proposer.get(proposer).unwrap_or_default();
+        proposer_ids.retain(|id| *id != proposal_id);
+        if proposer_ids.is_empty() {
+            self.proposals_by_proposer.remove(proposer);
+        } else {
+            self.proposals_by_proposer.insert(proposer, &proposer_ids);
+        }
+        let label = proposal.kind.to_policy_label().to_string();
+        let mut kind_ids = self.proposals_by_kind.get(&label).unwrap_or_default();
+        kind_ids.retain(|id| *id != proposal_id);
+        if kind_ids.is_empty() {
+            self.proposals_by_kind.remove(&label);
+        } else {
+            self.proposals_by_kind.insert(&label, &kind_ids);
+        }
+        for tag in proposal.tags.iter() {
+            let mut tag_ids = self.proposals_by_tag.get(tag).unwrap_or_default();
+            tag_ids.retain(|id| *id != proposal_id);
+            if tag_ids.is_empty() {
+                self.proposals_by_tag.remove(tag);
+            } else {
+                self.proposals_by_tag.insert(tag, &tag_ids);
+            }
+        }
+    }
+
+    /// Returns the bond to the proposer of given proposal.
+    fn internal_return_bond(&mut self, proposal: &mut Proposal) {
+        let bond = proposal.bond.0;
+        if bond > 0 {
+            self.locked_amount -= bond;
+            proposal.bond = U128(0);
+            Promise::new(proposal.proposer.clone()).transfer(bond);
+        }
+    }
+
+    /// Keeps the bond of given proposal in the treasury instead of returning it.
+    fn internal_forfeit_bond(&mut self, proposal: &mut Proposal) {
+        self.locked_amount -= proposal.bond.0;
+        proposal.bond = U128(0);
+    }
+
     pub(crate) fn internal_callback_proposal_success(
         &mut self,
         proposal: &mut Proposal,
     ) -> PromiseOrValue<()> {
-        // let policy = self.policy.get().unwrap().to_policy();
         proposal.status = ProposalStatus::Approved;
+        self.internal_return_bond(proposal);
         PromiseOrValue::Value(())
     }
 
@@ -404,38 +1590,455 @@ impl Contract {
         proposal: &mut Proposal,
     ) -> PromiseOrValue<()> {
         proposal.status = ProposalStatus::Failed;
+        proposal.retry_count += 1;
         PromiseOrValue::Value(())
     }
 
     pub(crate) fn internal_user_info(&self) -> UserInfo {
-        let account_id = env::predecessor_account_id();
-        UserInfo {
-            account_id,
-            stake:U128(1).0,
-        }
+        self.internal_account_info(env::predecessor_account_id())
     }
-}
 
-#[near_bindgen]
-impl Contract {
-    /// Add proposal to this DAO.
-    #[payable]
-    pub fn add_proposal(&mut self, proposal: ProposalInput) -> u64 {
-        let policy = self.policy.get().unwrap().to_policy();
+    /// Roles of given account it has been a member of for long enough to vote through them.
+    /// Members that weren't added by `AddMemberToRole`, like the initial council, can always vote.
+    pub(crate) fn internal_voting_roles(
+        &self,
+        account_id: &AccountId,
+        roles: Vec<String>,
+    ) -> Vec<String> {
+        let delay = self.config.get().unwrap().member_voting_delay_blocks.0;
+        roles
+            .into_iter()
+            .filter(|role| {
+                self.member_since
+                    .get(&(role.clone(), account_id.clone()))
+                    .map(|since| env::block_height() >= since + delay)
+                    .unwrap_or(true)
+            })
+            .collect()
+    }
+
+    /// Records a proposal added by given account.
+    /// Panics if the account already added the maximum number of proposals within the rate limit window.
+    fn internal_record_submission(&mut self, policy: &Policy, account_id: &AccountId) {
+        let rate_limit = match self.config.get().unwrap().rate_limit {
+            Some(rate_limit) => rate_limit,
+            None => return,
+        };
+        if rate_limit
+            .exempt_roles
+            .iter()
+            .any(|role| policy.is_role_member(role, account_id))
+        {
+            return;
+        }
+        let now = env::block_height();
+        let mut blocks = self.recent_submissions.get(account_id).unwrap_or_default();
+        blocks.retain(|block| block + rate_limit.window.0 > now);
+        assert!(
+            blocks.len() < rate_limit.max_proposals as usize,
+            "ERR_RATE_LIMITED"
+        );
+        blocks.push(now);
+        self.recent_submissions.insert(account_id, &blocks);
+    }
+
+    /// Returns whether given account voted less than the vote cooldown ago and isn't exempt.
+    fn internal_in_vote_cooldown(&self, policy: &Policy, account_id: &AccountId) -> bool {
+        let cooldown = match self.config.get().unwrap().vote_cooldown {
+            Some(cooldown) => cooldown,
+            None => return false,
+        };
+        !cooldown
+            .exempt_roles
+            .iter()
+            .any(|role| policy.is_role_member(role, account_id))
+            && self
+                .last_votes
+                .get(account_id)
+                .map(|block| env::block_height() < block + cooldown.blocks.0)
+                .unwrap_or(false)
+    }
+
+    /// Records a vote cast by given account.
+    /// Panics if the account is still in the vote cooldown of its previous vote.
+    fn internal_record_vote(&mut self, policy: &Policy, account_id: &AccountId) {
+        if self.config.get().unwrap().vote_cooldown.is_none() {
+            return;
+        }
+        assert!(
+            !self.internal_in_vote_cooldown(policy, account_id),
+            "ERR_VOTE_COOLDOWN"
+        );
+        self.last_votes.insert(account_id, &env::block_height());
+    }
+
+    /// Expires given proposal if it's in progress past its deadline without reaching approval,
+    /// returning its bond. Returns whether it was expired.
+    fn internal_expire_if_overdue(&mut self, policy: &Policy, roles: &[String], id: u64) -> bool {
+        let versioned_proposal = match self.proposals.get(&id) {
+            Some(proposal) => proposal,
+            None => return false,
+        };
+        let needs_migration = !versioned_proposal.is_latest();
+        let mut proposal: Proposal = versioned_proposal.into();
+        if proposal.status == ProposalStatus::Draft {
+            // Co-sponsors that never confirmed can't keep the bond locked.
+            if policy.proposal_deadline(&proposal) >= env::block_timestamp() {
+                return false;
+            }
+            proposal.status = ProposalStatus::Expired;
+            self.internal_return_bond(&mut proposal);
+            self.internal_update_status_counts(
+                Some(&ProposalStatus::Draft),
+                Some(&ProposalStatus::Expired),
+            );
+            self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+            return true;
+        }
+        if proposal.status != ProposalStatus::InProgress
+            || policy.proposal_deadline(&proposal) >= env::block_timestamp()
+            || policy.proposal_vote_status(&proposal, roles.to_vec(), self.internal_total_supply())
+                == ProposalStatus::Approved
+        {
+            return false;
+        }
+        let status = policy.expired_status(&proposal, roles, &self.config.get().unwrap().tie_break);
+        if status == ProposalStatus::Approved {
+            // Won on a tie, needs to be finalized to be executed.
+            return false;
+        }
+        if needs_migration {
+            // Old proposals predate the status counters.
+            self.internal_update_status_counts(None, Some(&ProposalStatus::InProgress));
+        }
+        proposal.status = status;
+        self.internal_resolve_poll(id, &mut proposal);
+        self.internal_return_bond(&mut proposal);
+        self.internal_update_status_counts(
+            Some(&ProposalStatus::InProgress),
+            Some(&proposal.status),
+        );
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+        true
+    }
+
+    /// Governance token configured for this DAO. Panics if there is none.
+    fn internal_governance_token(&self) -> AccountId {
+        self.config
+            .get()
+            .unwrap()
+            .governance_token
+            .expect("ERR_NO_GOVERNANCE_TOKEN")
+    }
+
+    /// Whether the caller can trigger the execution of given proposal.
+    fn internal_is_executor(&self, proposal: &Proposal) -> bool {
+        proposal
+            .executor
+            .as_ref()
+            .map(|executor| *executor == env::predecessor_account_id())
+            .unwrap_or(true)
+    }
+
+    /// Stores the winning option of given poll once its voting period is over.
+    fn internal_resolve_poll(&self, id: u64, proposal: &mut Proposal) {
+        let options = match &proposal.kind {
+            ProposalKind::Poll { options } => options,
+            _ => return,
+        };
+        proposal.poll_winner = proposal.leading_option();
+        emit_event(
+            "poll_resolved",
+            json!({
+                "proposal_id": U64(id),
+                "winner": proposal.poll_winner,
+                "option": proposal.poll_winner.map(|winner| options[winner].clone()),
+            }),
+        );
+    }
+
+    /// Proposal recently added by given proposer with given idempotency key, if still stored.
+    fn internal_idempotent_proposal(&self, proposer: &AccountId, key: &str) -> Option<u64> {
+        let now = env::block_height();
+        self.idempotency_keys
+            .get(proposer)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|(other, _, block)| other == key && block + IDEMPOTENCY_KEY_TTL > now)
+            .map(|(_, id, _)| id)
+            .filter(|id| self.proposals.contains_key(id))
+    }
+
+    /// Remembers the idempotency key of a proposal, dropping expired keys of the proposer.
+    fn internal_record_idempotency_key(&mut self, proposer: &AccountId, key: String, id: u64) {
+        let now = env::block_height();
+        let mut keys = self.idempotency_keys.get(proposer).unwrap_or_default();
+        keys.retain(|(other, _, block)| *other != key && block + IDEMPOTENCY_KEY_TTL > now);
+        if keys.len() >= MAX_IDEMPOTENCY_KEYS {
+            keys.remove(0);
+        }
+        keys.push((key, id, now));
+        self.idempotency_keys.insert(proposer, &keys);
+    }
+
+    /// Panics if given proposal period is below `Config::min_voting_period`.
+    fn assert_min_voting_period(&self, period: U64) {
+        assert!(
+            period.0 >= self.config.get().unwrap().min_voting_period.0,
+            "ERR_VOTING_PERIOD_TOO_SHORT"
+        );
+    }
+
+    /// Whether the term of given account in given role has ended, if it was added with one.
+    fn internal_term_ended(&self, role: &str, account_id: &AccountId) -> bool {
+        self.member_terms
+            .get(&(role.to_string(), account_id.clone()))
+            .map(|term_end_block| env::block_height() >= term_end_block)
+            .unwrap_or(false)
+    }
+
+    /// Info of given account, who might not be the caller.
+    pub(crate) fn internal_account_info(&self, account_id: AccountId) -> UserInfo {
+        let expired_roles = self
+            .policy
+            .get()
+            .unwrap()
+            .to_policy()
+            .roles
+            .into_iter()
+            .filter(|role| role.kind.accounts().contains(&account_id))
+            .map(|role| role.name)
+            .filter(|role| self.internal_term_ended(role, &account_id))
+            .collect();
+        let stake = match self.config.get().unwrap().voting_power_source {
+            VotingPowerSource::OnePerMember => 1,
+            VotingPowerSource::FtBalance(token_id) => {
+                self.internal_voting_balance(&token_id, &account_id)
+            }
+            VotingPowerSource::StakedBalance => self
+                .lockups
+                .get(&account_id)
+                .map(|lockup| lockup.amount.0)
+                .unwrap_or(0),
+        };
+        UserInfo {
+            account_id,
+            stake,
+            expired_roles,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Add proposal to this DAO.
+    #[payable]
+    pub fn add_proposal(&mut self, proposal: ProposalInput) -> u64 {
+        self.assert_not_paused();
+        let proposer = env::predecessor_account_id();
+        if let Some(key) = &proposal.idempotency_key {
+            assert!(
+                !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH,
+                "ERR_INVALID_IDEMPOTENCY_KEY"
+            );
+            if let Some(id) = self.internal_idempotent_proposal(&proposer, key) {
+                // Already added, the bond attached again is returned.
+                if env::attached_deposit() > 0 {
+                    Promise::new(proposer).transfer(env::attached_deposit());
+                }
+                return id;
+            }
+        }
+        let policy = self.policy.get().unwrap().to_policy();
         // 1. Validate proposal.
+        assert!(
+            !self
+                .config
+                .get()
+                .unwrap()
+                .disabled_kinds
+                .iter()
+                .any(|label| label == proposal.kind.to_policy_label()),
+            "ERR_KIND_DISABLED"
+        );
+        self.assert_valid_description(&proposal);
         match &proposal.kind {
             ProposalKind::ChangePolicy { policy } => match policy {
-                VersionedPolicy::Current(_) => {}
+                VersionedPolicy::Current(policy) => {
+                    self.assert_min_voting_period(policy.proposal_period);
+                    for period in policy.proposal_periods.values() {
+                        self.assert_min_voting_period(*period);
+                    }
+                }
                 _ => panic!("ERR_INVALID_POLICY"),
             },
-            ProposalKind::Transfer { token_id, msg, .. } => {
+            ProposalKind::ChangePolicyUpdateParameters { parameters } => {
+                if let Some(period) = parameters.proposal_period {
+                    self.assert_min_voting_period(period);
+                }
+                for period in parameters.proposal_periods.iter().flat_map(|p| p.values()) {
+                    self.assert_min_voting_period(*period);
+                }
+            }
+            ProposalKind::Transfer {
+                token_id,
+                amount,
+                msg,
+                register_receiver,
+                ..
+            } => {
                 assert!(
                     !(token_id == OLD_BASE_TOKEN) || msg.is_none(),
                     "ERR_BASE_TOKEN_NO_MSG"
                 );
+                assert!(
+                    token_id != OLD_BASE_TOKEN || !register_receiver,
+                    "ERR_BASE_TOKEN_NO_REGISTRATION"
+                );
+                self.assert_treasury_covers(&convert_old_to_new_token(token_id), amount.0);
+                self.internal_track_ft_token(token_id);
+            }
+            ProposalKind::TransferPercent { token_id, bps, .. } => {
+                assert!(*bps <= 10_000, "ERR_INVALID_BPS");
+                self.internal_track_ft_token(token_id);
+            }
+            ProposalKind::TokenMint { .. } | ProposalKind::TokenBurn { .. } => {
+                self.internal_governance_token();
+            }
+            ProposalKind::AddMemberToRole {
+                term_end_block: Some(term_end_block),
+                ..
+            } => {
+                assert!(
+                    *term_end_block > env::block_height(),
+                    "ERR_INVALID_TERM_END"
+                );
+            }
+            ProposalKind::StakeDelegate { amount, .. } => {
+                self.assert_treasury_covers(&None, amount.0);
+            }
+            ProposalKind::StreamCreate {
+                token_id,
+                total,
+                start_block,
+                end_block,
+                ..
+            } => {
+                assert!(total.0 > 0 && start_block < end_block, "ERR_INVALID_STREAM");
+                self.assert_treasury_covers(&convert_old_to_new_token(token_id), total.0);
+                self.internal_track_ft_token(token_id);
+            }
+            ProposalKind::Poll { options } => {
+                assert!(
+                    options.len() >= 2 && options.len() <= MAX_POLL_OPTIONS,
+                    "ERR_INVALID_POLL_OPTIONS"
+                );
+                assert!(
+                    options
+                        .iter()
+                        .all(|option| !option.is_empty() && option.len() <= MAX_POLL_OPTION_LENGTH),
+                    "ERR_INVALID_POLL_OPTION_LENGTH"
+                );
+            }
+            ProposalKind::MultiTokenTransfer { transfers, .. } => {
+                assert!(
+                    !transfers.is_empty() && transfers.len() <= MAX_TRANSFER_LEGS,
+                    "ERR_INVALID_TRANSFERS_LENGTH"
+                );
+                for (i, (token_id, amount)) in transfers.iter().enumerate() {
+                    assert!(
+                        transfers[..i].iter().all(|(other, _)| other != token_id),
+                        "ERR_DUPLICATE_TOKEN"
+                    );
+                    self.assert_treasury_covers(&convert_old_to_new_token(token_id), amount.0);
+                    self.internal_track_ft_token(token_id);
+                }
+            }
+            ProposalKind::UpgradeSelf { hash } => {
+                assert!(
+                    !self.config.get().unwrap().upgrade_from_blob
+                        || self.blobs.contains_key(&CryptoHash::from(*hash)),
+                    "ERR_NO_BLOB"
+                );
+            }
+            ProposalKind::UpgradeRemote { receiver_id, .. } => {
+                // Upgrading this DAO goes through `UpgradeSelf`, calling some other method on
+                // itself with new code could leave it unusable.
+                assert_ne!(
+                    receiver_id,
+                    &env::current_account_id(),
+                    "ERR_USE_UPGRADE_SELF"
+                );
+            }
+            ProposalKind::ChangeConfig { config, .. } => {
+                config.assert_valid();
+            }
+            ProposalKind::FunctionCall { actions, .. } => {
+                let callback_gas = self.config.get().unwrap().callback_gas.0;
+                let required_gas = actions.iter().fold(callback_gas, |total, action| {
+                    total.saturating_add(action.gas.0)
+                });
+                assert!(
+                    required_gas <= (env::prepaid_gas() - env::used_gas()).0,
+                    "ERR_INSUFFICIENT_GAS"
+                );
+                let total_deposit = actions.iter().fold(0, |total: Balance, action| {
+                    total.saturating_add(action.deposit.0)
+                });
+                assert!(
+                    total_deposit == 0
+                        || total_deposit <= self.config.get().unwrap().max_function_call_deposit.0,
+                    "ERR_DEPOSIT_OVER_CAP"
+                );
             }
             _ => {}
         };
+        proposal.assert_valid_tags();
+        proposal.assert_valid_reference();
+        if let Some(commit_end) = proposal.commit_end {
+            assert!(
+                !matches!(proposal.kind, ProposalKind::Poll { .. }),
+                "ERR_COMMIT_REVEAL_POLL"
+            );
+            let now = env::block_timestamp();
+            assert!(
+                commit_end.0 > now
+                    && commit_end.0
+                        < now + policy.proposal_period_for(proposal.kind.to_policy_label()),
+                "ERR_INVALID_COMMIT_END"
+            );
+        }
+        for role in &proposal.required_roles {
+            assert!(
+                policy.roles.iter().any(|r| &r.name == role),
+                "ERR_ROLE_NOT_FOUND"
+            );
+        }
+        assert!(
+            proposal.cosponsors.len() <= MAX_COSPONSORS,
+            "ERR_TOO_MANY_COSPONSORS"
+        );
+        for (i, cosponsor) in proposal.cosponsors.iter().enumerate() {
+            assert!(
+                *cosponsor != proposer && !proposal.cosponsors[..i].contains(cosponsor),
+                "ERR_INVALID_COSPONSORS"
+            );
+        }
+        if let Some(dependency) = proposal.depends_on {
+            assert!(
+                self.proposals.contains_key(&dependency)
+                    || self.pruned_approved.contains(&dependency),
+                "ERR_NO_DEPENDENCY"
+            );
+        }
+        assert!(
+            env::attached_deposit() >= policy.proposal_bond.0,
+            "ERR_MIN_BOND"
+        );
+        assert!(
+            self.internal_user_info().stake >= self.config.get().unwrap().min_proposal_stake.0,
+            "ERR_INSUFFICIENT_STAKE"
+        );
         // 2. Check permission of caller to add this type of proposal.
         assert!(
             policy
@@ -447,60 +2050,411 @@ impl Contract {
                 .1,
             "ERR_PERMISSION_DENIED"
         );
+        self.internal_record_submission(&policy, &proposer);
         // 3. Actually add proposal to the current list of proposals.
         let id = self.last_proposal_id;
-        self.proposals
-            .insert(&id, &VersionedProposal::Default(proposal.into()));
+        let label = proposal.kind.to_policy_label().to_string();
+        let tags = proposal.tags.clone();
+        if let Some(key) = proposal.idempotency_key.clone() {
+            self.internal_record_idempotency_key(&proposer, key, id);
+        }
+        let proposal: Proposal = proposal.into();
+        let status = proposal.status.clone();
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
         self.last_proposal_id += 1;
-        // self.locked_amount += env::attached_deposit();
+        self.locked_amount += env::attached_deposit();
+        self.internal_update_status_counts(None, Some(&status));
+        let mut proposer_ids = self
+            .proposals_by_proposer
+            .get(&proposer)
+            .unwrap_or_default();
+        proposer_ids.push(id);
+        self.proposals_by_proposer.insert(&proposer, &proposer_ids);
+        let mut kind_ids = self.proposals_by_kind.get(&label).unwrap_or_default();
+        kind_ids.push(id);
+        self.proposals_by_kind.insert(&label, &kind_ids);
+        for tag in tags.iter() {
+            let mut tag_ids = self.proposals_by_tag.get(tag).unwrap_or_default();
+            // A tag given twice is indexed once.
+            if tag_ids.last() != Some(&id) {
+                tag_ids.push(id);
+                self.proposals_by_tag.insert(tag, &tag_ids);
+            }
+        }
+        self.internal_notify_observer(id, "proposal_added", &status);
         id
     }
 
     /// Act on given proposal by id, if permissions allow.
     /// Memo is logged but not stored in the state. Can be used to leave notes or explain the action.
-    pub fn act_proposal(&mut self, id: u64, action: Action, memo: Option<String>) {
-        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+    /// Reason is stored with the vote as its public rationale and is only allowed for votes.
+    /// Returns the status of the proposal after the action, e.g. `Approved` once a vote reaches
+    /// the threshold, so callers don't need to query it again. `Removed` if it was deleted.
+    pub fn act_proposal(
+        &mut self,
+        id: u64,
+        action: Action,
+        memo: Option<String>,
+        reason: Option<String>,
+    ) -> ProposalStatus {
+        let user = self.internal_user_info();
+        self.internal_act_proposal(id, action, memo, reason, user, false)
+    }
+
+    /// Applies up to `MAX_BULK_ACTIONS` actions in order, like `act_proposal`. The text of each
+    /// entry is the reason of a vote and the memo of other actions. Entries that would fail the
+    /// checks of `act_proposal` are skipped instead of failing the whole batch, only a failure
+    /// while executing an approved proposal still does.
+    pub fn act_proposals(
+        &mut self,
+        actions: Vec<(u64, Action, Option<String>)>,
+    ) -> Vec<ActionOutcome> {
+        assert!(actions.len() <= MAX_BULK_ACTIONS, "ERR_TOO_MANY_ACTIONS");
+        actions
+            .into_iter()
+            .map(|(id, action, text)| {
+                let (memo, reason) = match action {
+                    Action::VoteApprove | Action::VoteReject | Action::VoteRemove => (None, text),
+                    _ => (text, None),
+                };
+                let error = self.internal_check_action(id, &action, &reason).err();
+                if error.is_none() {
+                    let user = self.internal_user_info();
+                    self.internal_act_proposal(id, action, memo, reason, user, false);
+                }
+                ActionOutcome {
+                    id,
+                    error: error.map(|error| error.to_string()),
+                }
+            })
+            .collect()
+    }
+
+    /// Votes on given proposal splitting the caller's weight between approve, reject and
+    /// abstain. The shares are in basis points and must sum to 10000.
+    /// Returns the resulting status of the proposal, like `act_proposal`.
+    pub fn act_proposal_split(
+        &mut self,
+        id: u64,
+        approve_bps: u16,
+        reject_bps: u16,
+        abstain_bps: u16,
+    ) -> ProposalStatus {
+        let user = self.internal_user_info();
+        self.internal_act_proposal(
+            id,
+            Action::VoteSplit(approve_bps, reject_bps, abstain_bps),
+            None,
+            None,
+            user,
+            false,
+        )
+    }
+
+    /// Checks the caller's action on given proposal the way `internal_act_proposal` does before
+    /// changing anything, so `act_proposals` can skip it.
+    fn internal_check_action(
+        &self,
+        id: u64,
+        action: &Action,
+        reason: &Option<String>,
+    ) -> Result<(), &'static str> {
+        let proposal: Proposal = self.proposals.get(&id).ok_or("ERR_NO_PROPOSAL")?.into();
+        let policy = self.policy.get().unwrap().to_policy();
+        let user = self.internal_user_info();
+        let account_id = user.account_id.clone();
+        let stake = self.internal_proposal_stake(&account_id, &proposal, user.stake);
+        let (roles, allowed) = policy.can_execute_action(user, &proposal.kind, action);
+        if !allowed {
+            return Err("ERR_PERMISSION_DENIED");
+        }
+        check_reason(action, reason)?;
+        match action {
+            Action::AddProposal | Action::ExtendVotingPeriod => Err("ERR_WRONG_ACTION"),
+            Action::VoteApprove
+            | Action::VoteReject
+            | Action::VoteRemove
+            | Action::VoteSplit(..)
+            | Action::VoteOption(..) => self
+                .internal_check_vote(
+                    &proposal,
+                    &policy,
+                    &account_id,
+                    roles,
+                    stake,
+                    action,
+                    false,
+                    self.cached_total_supply(),
+                )
+                .map(|_| ()),
+            Action::Finalize => {
+                match self.internal_finalize_status(&policy, &proposal, self.cached_total_supply())
+                {
+                    ProposalStatus::Approved if !self.internal_is_executor(&proposal) => {
+                        Err("ERR_NOT_EXECUTOR")
+                    }
+                    ProposalStatus::Approved
+                    | ProposalStatus::Expired
+                    | ProposalStatus::Rejected => Ok(()),
+                    _ => Err("ERR_PROPOSAL_NOT_EXPIRED_OR_FAILED"),
+                }
+            }
+            Action::RemoveProposal | Action::MoveToHub => Ok(()),
+        }
+    }
+
+    /// Checks that given account can cast given vote on given proposal, returning the roles it
+    /// votes through. Votes on commit-reveal proposals are only accepted if `revealed`.
+    #[allow(clippy::too_many_arguments)]
+    fn internal_check_vote(
+        &self,
+        proposal: &Proposal,
+        policy: &Policy,
+        account_id: &AccountId,
+        roles: Vec<String>,
+        stake: Balance,
+        action: &Action,
+        revealed: bool,
+        total_supply: Balance,
+    ) -> Result<Vec<String>, &'static str> {
+        let is_option = if let Action::VoteOption(option) = action {
+            let options = match &proposal.kind {
+                ProposalKind::Poll { options } => options,
+                _ => return Err("ERR_NOT_A_POLL"),
+            };
+            if *option >= options.len() {
+                return Err("ERR_INVALID_POLL_OPTION");
+            }
+            true
+        } else {
+            false
+        };
+        if proposal.status != ProposalStatus::InProgress {
+            return Err("ERR_PROPOSAL_NOT_READY_FOR_VOTE");
+        }
+        if !is_option
+            && !self.config.get().unwrap().proposer_can_vote
+            && *account_id == proposal.proposer
+        {
+            return Err("ERR_PROPOSER_CANNOT_VOTE");
+        }
+        if !proposal.is_whitelisted_voter(account_id) {
+            return Err("ERR_NOT_WHITELISTED");
+        }
+        if !is_option {
+            // Polls are voted on with `VoteOption`, they can only be voted to be removed.
+            if matches!(proposal.kind, ProposalKind::Poll { .. })
+                && !matches!(action, Action::VoteRemove)
+            {
+                return Err("ERR_POLL_VOTE_OPTION");
+            }
+            if proposal.commit_end.is_some() && !revealed {
+                return Err("ERR_COMMIT_REVEAL_VOTE");
+            }
+        }
+        // Votes are accepted up to and including the deadline. The vote is cast at the
+        // current block, so a deciding vote made in time is acted upon right away.
+        if env::block_timestamp() > policy.proposal_deadline(proposal) {
+            return Err("ERR_PROPOSAL_EXPIRED");
+        }
+        let roles = self.internal_voting_roles(account_id, roles);
+        if roles.is_empty() {
+            return Err("ERR_MEMBERSHIP_TOO_RECENT");
+        }
+        if !proposal.has_voting_power(&roles, policy, stake) {
+            return Err("ERR_NO_VOTING_POWER");
+        }
+        if self.internal_in_vote_cooldown(policy, account_id) {
+            return Err("ERR_VOTE_COOLDOWN");
+        }
+        if let Action::VoteSplit(approve_bps, reject_bps, abstain_bps) = action {
+            if *approve_bps as u32 + *reject_bps as u32 + *abstain_bps as u32 != 10_000 {
+                return Err("ERR_INVALID_SPLIT");
+            }
+        }
+        if proposal.votes.contains_key(account_id)
+            || proposal.poll_votes.contains_key(account_id)
+            || proposal.split_votes.contains_key(account_id)
+        {
+            return Err("ERR_ALREADY_VOTED");
+        }
+        let label = proposal.kind.to_policy_label().to_string();
+        if total_supply == 0
+            && roles.iter().any(|role| {
+                let vote_policy = policy.vote_policy(role, &label);
+                vote_policy.max_weight_bps.is_some()
+                    && vote_policy.weight_kind != WeightKind::RoleWeight
+            })
+        {
+            return Err("ERR_NO_TOTAL_SUPPLY");
+        }
+        Ok(roles)
+    }
+
+    /// Status of given proposal being finalized, in which it's executed if approved.
+    fn internal_finalize_status(
+        &self,
+        policy: &Policy,
+        proposal: &Proposal,
+        total_supply: Balance,
+    ) -> ProposalStatus {
+        let retries_exhausted = proposal.status == ProposalStatus::Failed
+            && proposal.retry_count > self.config.get().unwrap().max_execution_retries;
+        if retries_exhausted {
+            // The execution keeps failing, so the proposal can only expire now.
+            ProposalStatus::Expired
+        } else {
+            policy.proposal_status(
+                proposal,
+                policy.roles.iter().map(|r| r.name.clone()).collect(),
+                total_supply,
+                &self.config.get().unwrap().tie_break,
+            )
+        }
+    }
+
+    /// Acts on given proposal on behalf of given user.
+    /// Votes on commit-reveal proposals are only accepted if `revealed` through `reveal_vote`.
+    pub(crate) fn internal_act_proposal(
+        &mut self,
+        id: u64,
+        action: Action,
+        memo: Option<String>,
+        reason: Option<String>,
+        user: UserInfo,
+        revealed: bool,
+    ) -> ProposalStatus {
+        self.assert_not_paused();
+        let versioned_proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL");
+        let needs_migration = !versioned_proposal.is_latest();
+        let mut proposal: Proposal = versioned_proposal.into();
+        let initial_status = proposal.status.clone();
+        if needs_migration {
+            // Lazily rewrite old proposals in the latest version.
+            self.proposals
+                .insert(&id, &VersionedProposal::V2(proposal.clone()));
+            // Old proposals predate the status counters.
+            self.internal_update_status_counts(None, Some(&initial_status));
+        }
         let policy = self.policy.get().unwrap().to_policy();
         // Check permissions for the given action.
-        let (roles, allowed) =
-            policy.can_execute_action(self.internal_user_info(), &proposal.kind, &action);
+        let sender_id = user.account_id.clone();
+        let stake = self.internal_proposal_stake(&sender_id, &proposal, user.stake);
+        let (roles, allowed) = policy.can_execute_action(user, &proposal.kind, &action);
         assert!(allowed, "ERR_PERMISSION_DENIED");
-        let sender_id = env::predecessor_account_id();
+        check_reason(&action, &reason).unwrap_or_else(|error| panic!("{}", error));
         // Update proposal given action. Returns true if should be updated in storage.
         let update = match action {
-            Action::AddProposal => env::panic_str("ERR_WRONG_ACTION"),
+            Action::AddProposal | Action::ExtendVotingPeriod => env::panic_str("ERR_WRONG_ACTION"),
             Action::RemoveProposal => {
                 self.proposals.remove(&id);
+                self.removed_at.remove(&id);
+                self.internal_remove_from_indexes(&proposal, id);
                 false
             }
-            Action::VoteApprove | Action::VoteReject | Action::VoteRemove => {
-                assert!(
-                    matches!(proposal.status, ProposalStatus::InProgress),
-                    "ERR_PROPOSAL_NOT_READY_FOR_VOTE"
-                );
-                proposal.update_votes(
+            Action::VoteOption(option) => {
+                let total_supply = self.internal_total_supply();
+                let roles = self
+                    .internal_check_vote(
+                        &proposal,
+                        &policy,
+                        &sender_id,
+                        roles,
+                        stake,
+                        &action,
+                        revealed,
+                        total_supply,
+                    )
+                    .unwrap_or_else(|error| panic!("{}", error));
+                self.internal_record_vote(&policy, &sender_id);
+                proposal.update_poll_vote(
                     &sender_id,
                     &roles,
-                    Vote::from(action),
+                    option,
                     &policy,
+                    stake,
+                    self.lockups.get(&sender_id),
+                    total_supply,
                 );
-               // Updates proposal status with new votes using the policy.
-                proposal.status =
-                    policy.proposal_status(&proposal, roles);
+                true
+            }
+            Action::VoteApprove
+            | Action::VoteReject
+            | Action::VoteRemove
+            | Action::VoteSplit(..) => {
+                let total_supply = self.internal_total_supply();
+                let roles = self
+                    .internal_check_vote(
+                        &proposal,
+                        &policy,
+                        &sender_id,
+                        roles,
+                        stake,
+                        &action,
+                        revealed,
+                        total_supply,
+                    )
+                    .unwrap_or_else(|error| panic!("{}", error));
+                self.internal_record_vote(&policy, &sender_id);
+                match action {
+                    Action::VoteSplit(approve_bps, reject_bps, abstain_bps) => proposal
+                        .update_split_vote(
+                            &sender_id,
+                            &roles,
+                            [approve_bps, reject_bps, abstain_bps],
+                            &policy,
+                            stake,
+                            self.lockups.get(&sender_id),
+                            total_supply,
+                        ),
+                    _ => proposal.update_votes(
+                        &sender_id,
+                        &roles,
+                        Vote::from(action),
+                        &policy,
+                        reason,
+                        stake,
+                        self.lockups.get(&sender_id),
+                        total_supply,
+                    ),
+                }
+                // Updates proposal status with new votes using the policy.
+                proposal.status = policy.proposal_vote_status(&proposal, roles, total_supply);
+                if proposal.status == ProposalStatus::InProgress
+                    && !policy.can_avoid_rejection(
+                        &proposal,
+                        total_supply,
+                        &self.config.get().unwrap().tie_break,
+                    )
+                {
+                    // The remaining voters can't change the outcome anymore.
+                    proposal.status = ProposalStatus::Rejected;
+                }
                 println!("proposal status after VoteApprove {:?}", proposal.status);
 
                 if proposal.status == ProposalStatus::Approved {
-                    self.internal_execute_proposal(&policy, &mut proposal, id);
+                    if !self.internal_is_executor(&proposal) {
+                        // Stays in progress until the executor finalizes it.
+                        proposal.status = ProposalStatus::InProgress;
+                        log!("Approved, waiting for {:?} to execute", proposal.executor);
+                    } else if self.config.get().unwrap().auto_execute {
+                        self.internal_execute_proposal(&policy, &mut proposal, id);
+                    } else {
+                        self.awaiting_execution.insert(&id);
+                        log!("Approved, waiting for execute");
+                    }
                     true
                 } else if proposal.status == ProposalStatus::Removed {
-                    // self.internal_reject_proposal(&policy, &proposal, false);
-                    self.proposals.remove(&id);
-                    false
+                    // Kept around for the grace period, the bond is forfeited on deletion.
+                    self.removed_at.insert(&id, &env::block_height());
+                    self.internal_slash_approvers(id, &proposal);
+                    true
                 } else if proposal.status == ProposalStatus::Rejected {
-                    // self.internal_reject_proposal(&policy, &proposal, true);
+                    // Voted down but not spam, so unlike removal the bond is returned.
+                    self.internal_return_bond(&mut proposal);
                     true
                 } else {
-                    // Still in progress or expired.
+                    // Still in progress.
                     true
                 }
             }
@@ -512,16 +2466,21 @@ impl Contract {
             //  - if the number of votes in the group has changed (new members has been added) -
             //      the proposal can loose it's approved state. In this case new proposal needs to be made, this one can only expire.
              Action::Finalize => {
-                proposal.status = policy.proposal_status(
-                    &proposal,
-                    policy.roles.iter().map(|r| r.name.clone()).collect(),
-                );
+                proposal.status =
+                    self.internal_finalize_status(&policy, &proposal, self.internal_total_supply());
                 match proposal.status {
                     ProposalStatus::Approved => {
+                        assert!(self.internal_is_executor(&proposal), "ERR_NOT_EXECUTOR");
                         self.internal_execute_proposal(&policy, &mut proposal, id);
                     }
                     ProposalStatus::Expired => {
-                        println!("{:?} proposal expired", proposal.status)
+                        println!("{:?} proposal expired", proposal.status);
+                        self.internal_resolve_poll(id, &mut proposal);
+                        self.internal_return_bond(&mut proposal);
+                    }
+                    ProposalStatus::Rejected => {
+                        // Tied at the deadline, or can't be approved anymore.
+                        self.internal_return_bond(&mut proposal);
                     }
                     _ => {
                         env::panic_str("ERR_PROPOSAL_NOT_EXPIRED_OR_FAILED");
@@ -531,13 +2490,481 @@ impl Contract {
             }
             Action::MoveToHub => false,
         };
+        let status = proposal.status.clone();
         if update {
-            self.proposals
-                .insert(&id, &VersionedProposal::Default(proposal));
+            self.internal_update_status_counts(Some(&initial_status), Some(&proposal.status));
+            self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+        } else if !self.proposals.contains_key(&id) {
+            self.internal_update_status_counts(Some(&initial_status), None);
         }
         if let Some(memo) = memo {
             log!("Memo: {}", memo);
         }
+        if self.proposals.contains_key(&id) {
+            status
+        } else {
+            ProposalStatus::Removed
+        }
+    }
+
+    /// Finalizes up to `limit` proposals in progress or drafts that are past their deadline,
+    /// marking them as expired and returning their bonds. Returns the number of finalized proposals.
+    /// Proposals whose votes reached approval are skipped, they need to be finalized one by one.
+    pub fn finalize_expired(&mut self, limit: u64) -> u64 {
+        self.assert_not_paused();
+        let policy = self.policy.get().unwrap().to_policy();
+        let roles: Vec<String> = policy.roles.iter().map(|r| r.name.clone()).collect();
+        let mut finalized = 0;
+        for id in 0..self.last_proposal_id {
+            if finalized >= limit {
+                break;
+            }
+            if self.internal_expire_if_overdue(&policy, &roles, id) {
+                finalized += 1;
+            }
+        }
+        finalized
+    }
+
+    /// Recomputes and stores the status of given proposal without executing it, returning the
+    /// status. A proposal in progress past its deadline is expired and its bond returned.
+    /// A proposal whose votes reached approval stays in progress, to be executed by `Finalize`.
+    pub fn refresh_status(&mut self, id: u64) -> ProposalStatus {
+        self.assert_not_paused();
+        let policy = self.policy.get().unwrap().to_policy();
+        let roles: Vec<String> = policy.roles.iter().map(|r| r.name.clone()).collect();
+        self.internal_expire_if_overdue(&policy, &roles, id);
+        Proposal::from(self.proposals.get(&id).expect("ERR_NO_PROPOSAL")).status
+    }
+
+    /// Restores removed proposal back to in progress, dropping the votes to remove it.
+    /// Only members of the guardian role can call this, within the removal grace period.
+    /// Stake slashed from the approvers on removal isn't returned.
+    pub fn restore_proposal(&mut self, id: u64) {
+        self.assert_not_paused();
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy.is_role_member(GUARDIAN_ROLE, &env::predecessor_account_id()),
+            "ERR_NOT_GUARDIAN"
+        );
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        let removed_at = self.removed_at.get(&id).expect("ERR_PROPOSAL_NOT_REMOVED");
+        assert!(
+            env::block_height() <= removed_at + self.config.get().unwrap().removal_grace_period.0,
+            "ERR_GRACE_PERIOD_OVER"
+        );
+        proposal.status = ProposalStatus::InProgress;
+        proposal
+            .votes
+            .retain(|_, vote| !matches!(vote.vote, Vote::Remove));
+        for counts in proposal.vote_counts.values_mut() {
+            counts[Vote::Remove as usize] = 0;
+        }
+        self.removed_at.remove(&id);
+        self.internal_update_status_counts(
+            Some(&ProposalStatus::Removed),
+            Some(&ProposalStatus::InProgress),
+        );
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+    }
+
+    /// Confirms the caller backs given draft proposal it's listed as co-sponsor of. Once all
+    /// co-sponsors confirmed, the proposal opens for voting and its voting period starts.
+    /// Drafts can only be confirmed within the voting period from their submission.
+    pub fn confirm_cosponsorship(&mut self, id: u64) {
+        self.assert_not_paused();
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        assert_eq!(
+            proposal.status,
+            ProposalStatus::Draft,
+            "ERR_PROPOSAL_NOT_DRAFT"
+        );
+        assert!(
+            self.policy
+                .get()
+                .unwrap()
+                .to_policy()
+                .proposal_deadline(&proposal)
+                >= env::block_timestamp(),
+            "ERR_PROPOSAL_EXPIRED"
+        );
+        let account_id = env::predecessor_account_id();
+        assert!(
+            proposal.cosponsors.contains(&account_id),
+            "ERR_NOT_COSPONSOR"
+        );
+        assert!(
+            !proposal.confirmed_cosponsors.contains(&account_id),
+            "ERR_ALREADY_CONFIRMED"
+        );
+        proposal.confirmed_cosponsors.push(account_id.clone());
+        emit_event(
+            "cosponsorship_confirmed",
+            json!({ "proposal_id": U64(id), "account_id": account_id }),
+        );
+        if proposal.confirmed_cosponsors.len() == proposal.cosponsors.len() {
+            proposal.status = ProposalStatus::InProgress;
+            proposal.submission_time = U64(env::block_timestamp());
+            self.internal_update_status_counts(
+                Some(&ProposalStatus::Draft),
+                Some(&ProposalStatus::InProgress),
+            );
+            self.internal_notify_observer(id, "proposal_opened", &proposal.status);
+        }
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+    }
+
+    /// Executes given proposal in progress regardless of its votes. Only members of the admin
+    /// role can call this, and only if `Config::allow_force_execute` is set.
+    pub fn force_execute(&mut self, id: u64) {
+        self.assert_not_paused();
+        let admin_id = env::predecessor_account_id();
+        assert!(
+            self.config.get().unwrap().allow_force_execute,
+            "ERR_FORCE_EXECUTE_DISABLED"
+        );
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy.is_role_member(ADMIN_ROLE, &admin_id),
+            "ERR_NOT_ADMIN"
+        );
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        assert_eq!(
+            proposal.status,
+            ProposalStatus::InProgress,
+            "ERR_PROPOSAL_NOT_IN_PROGRESS"
+        );
+        emit_event(
+            "proposal_force_executed",
+            json!({
+                "proposal_id": U64(id),
+                "admin_id": admin_id,
+            }),
+        );
+        proposal.status = ProposalStatus::Approved;
+        self.internal_execute_proposal(&policy, &mut proposal, id);
+        self.internal_update_status_counts(
+            Some(&ProposalStatus::InProgress),
+            Some(&proposal.status),
+        );
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+    }
+
+    /// Executes given approved proposal, if `Config::auto_execute` is not set. Anyone can call
+    /// this, unless the proposal restricts who executes it.
+    pub fn execute(&mut self, id: u64) {
+        self.assert_not_paused();
+        assert!(
+            self.awaiting_execution.contains(&id),
+            "ERR_PROPOSAL_NOT_AWAITING_EXECUTION"
+        );
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        assert!(self.internal_is_executor(&proposal), "ERR_NOT_EXECUTOR");
+        let policy = self.policy.get().unwrap().to_policy();
+        self.internal_execute_proposal(&policy, &mut proposal, id);
+        self.internal_update_status_counts(Some(&ProposalStatus::Approved), Some(&proposal.status));
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+    }
+
+    /// Rejects given approved proposal that wasn't executed yet, either waiting for `execute` or
+    /// a scheduled config, so it never runs. E.g. when the target contract got compromised.
+    /// The bond is returned. Only members of the guardian role can call this.
+    pub fn abort_execution(&mut self, id: u64) {
+        let guardian_id = env::predecessor_account_id();
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy.is_role_member(GUARDIAN_ROLE, &guardian_id),
+            "ERR_NOT_GUARDIAN"
+        );
+        assert!(
+            self.awaiting_execution.remove(&id) || self.scheduled_configs.remove(&id),
+            "ERR_PROPOSAL_NOT_AWAITING_EXECUTION"
+        );
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        proposal.status = ProposalStatus::Rejected;
+        self.internal_return_bond(&mut proposal);
+        self.internal_update_status_counts(
+            Some(&ProposalStatus::Approved),
+            Some(&ProposalStatus::Rejected),
+        );
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+        emit_event(
+            "execution_aborted",
+            json!({
+                "proposal_id": U64(id),
+                "guardian_id": guardian_id,
+            }),
+        );
+    }
+
+    /// Replaces the receiver of given failed function call proposal, e.g. when it failed because
+    /// of a typo in the account, so it can be finalized again without a new vote. The retries
+    /// start over. Only members of the admin role can call this, and only for calls without
+    /// deposit, so a single admin can't redirect funds of the DAO.
+    pub fn amend_failed_call(&mut self, id: u64, new_receiver: AccountId) {
+        self.assert_not_paused();
+        let admin_id = env::predecessor_account_id();
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy.is_role_member(ADMIN_ROLE, &admin_id),
+            "ERR_NOT_ADMIN"
+        );
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        assert_eq!(
+            proposal.status,
+            ProposalStatus::Failed,
+            "ERR_PROPOSAL_NOT_FAILED"
+        );
+        let old_receiver = match &mut proposal.kind {
+            ProposalKind::FunctionCall {
+                receiver_id,
+                actions,
+            } => {
+                assert!(
+                    actions.iter().all(|action| action.deposit.0 == 0),
+                    "ERR_AMENDED_CALL_HAS_DEPOSIT"
+                );
+                std::mem::replace(receiver_id, new_receiver.clone())
+            }
+            _ => env::panic_str("ERR_NOT_A_FUNCTION_CALL"),
+        };
+        proposal.retry_count = 0;
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
+        emit_event(
+            "call_amended",
+            json!({
+                "proposal_id": U64(id),
+                "admin_id": admin_id,
+                "old_receiver_id": old_receiver,
+                "receiver_id": new_receiver,
+            }),
+        );
+    }
+
+    /// Removes the members whose term ended from their roles, returning the number removed.
+    /// They already can't act through these roles, this only updates the policy, which isn't
+    /// recorded in the policy history as no proposal changed it. Anyone can call this.
+    pub fn prune_expired_members(&mut self) -> u32 {
+        self.assert_not_paused();
+        let mut policy = self.policy.get().unwrap().to_policy();
+        let expired: Vec<(String, AccountId)> = policy
+            .roles
+            .iter()
+            .flat_map(|role| {
+                role.kind
+                    .accounts()
+                    .iter()
+                    .map(move |member_id| (role.name.clone(), member_id.clone()))
+            })
+            .filter(|(role, member_id)| self.internal_term_ended(role, member_id))
+            .collect();
+        if expired.is_empty() {
+            return 0;
+        }
+        for (role, member_id) in expired.iter() {
+            policy.remove_member_from_role(role, member_id);
+            self.member_terms.remove(&(role.clone(), member_id.clone()));
+            self.member_since.remove(&(role.clone(), member_id.clone()));
+            emit_event(
+                "member_term_ended",
+                json!({ "role": role, "member_id": member_id }),
+            );
+        }
+        self.policy.set(&VersionedPolicy::Current(policy));
+        expired.len() as u32
+    }
+
+    /// Applies the config of given approved `ChangeConfig` proposal once its effective block is
+    /// reached. Anyone can call this.
+    pub fn poke_scheduled_config(&mut self, id: u64) {
+        self.assert_not_paused();
+        assert!(
+            self.scheduled_configs.contains(&id),
+            "ERR_NO_SCHEDULED_CONFIG"
+        );
+        let proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        if let ProposalKind::ChangeConfig {
+            config,
+            effective_block: Some(effective_block),
+        } = proposal.kind
+        {
+            assert!(
+                env::block_height() >= effective_block,
+                "ERR_CONFIG_NOT_EFFECTIVE_YET"
+            );
+            self.scheduled_configs.remove(&id);
+            self.internal_set_config(&config, id, &proposal.proposer);
+            emit_event(
+                "scheduled_config_applied",
+                json!({
+                    "proposal_id": U64(id),
+                    "effective_block": U64(effective_block),
+                }),
+            );
+        }
+    }
+
+    /// Deletes removed proposal once its grace period is over, forfeiting the bond.
+    pub fn delete_removed_proposal(&mut self, id: u64) {
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        let removed_at = self.removed_at.get(&id).expect("ERR_PROPOSAL_NOT_REMOVED");
+        assert!(
+            env::block_height() > removed_at + self.config.get().unwrap().removal_grace_period.0,
+            "ERR_GRACE_PERIOD_NOT_OVER"
+        );
+        self.internal_forfeit_bond(&mut proposal);
+        self.removed_at.remove(&id);
+        self.proposals.remove(&id);
+        self.internal_remove_from_indexes(&proposal, id);
+        self.internal_update_status_counts(Some(&ProposalStatus::Removed), None);
+    }
+
+    /// Deletes the finished proposals submitted before `before_block` among the `limit` ids
+    /// starting at `from_index` to free their storage, returning the number deleted. Only members
+    /// of the guardian role can call this.
+    /// Proposals in progress, failed or waiting for their execution callback are kept, and so are
+    /// removed proposals within their grace period. Bonds of removed proposals are forfeited.
+    /// Ids of deleted approved proposals are kept, as proposals can depend on them.
+    pub fn prune_proposals(
+        &mut self,
+        from_index: u64,
+        before_block: BlockHeight,
+        limit: u64,
+    ) -> u64 {
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy.is_role_member(GUARDIAN_ROLE, &env::predecessor_account_id()),
+            "ERR_NOT_GUARDIAN"
+        );
+        let grace_period = self.config.get().unwrap().removal_grace_period.0;
+        let mut pruned = 0;
+        for id in from_index..std::cmp::min(self.last_proposal_id, from_index.saturating_add(limit))
+        {
+            let mut proposal: Proposal = match self.proposals.get(&id) {
+                Some(proposal) => proposal.into(),
+                None => continue,
+            };
+            if proposal.submission_block >= before_block {
+                // Ids are in submission order.
+                break;
+            }
+            let finished = match proposal.status {
+                ProposalStatus::Approved => {
+                    !self.pending_executions.contains(&id)
+                        && !self.scheduled_configs.contains(&id)
+                        && !self.awaiting_execution.contains(&id)
+                }
+                ProposalStatus::Rejected | ProposalStatus::Expired | ProposalStatus::Moved => true,
+                ProposalStatus::Removed => self
+                    .removed_at
+                    .get(&id)
+                    .map(|removed_at| env::block_height() > removed_at + grace_period)
+                    .unwrap_or(true),
+                ProposalStatus::InProgress | ProposalStatus::Failed | ProposalStatus::Draft => {
+                    false
+                }
+            };
+            if !finished {
+                continue;
+            }
+            if proposal.status == ProposalStatus::Approved {
+                self.pruned_approved.insert(&id);
+            }
+            self.internal_forfeit_bond(&mut proposal);
+            self.removed_at.remove(&id);
+            if let Some(mut comments) = self.comments.remove(&id) {
+                comments.clear();
+            }
+            self.proposals.remove(&id);
+            self.internal_remove_from_indexes(&proposal, id);
+            self.internal_update_status_counts(Some(&proposal.status), None);
+            pruned += 1;
+        }
+        pruned
+    }
+
+    /// Deletes up to `limit` suggestions older than the configured age with fewer approve votes
+    /// than the configured support, returning the number deleted. Anyone can call this.
+    /// Approved suggestions were taken up by the DAO and are kept, and so are removed ones.
+    /// Bonds of the deleted suggestions are returned to their proposers.
+    pub fn prune_stale_suggestions(&mut self, limit: u64) -> u64 {
+        let pruning = self
+            .config
+            .get()
+            .unwrap()
+            .suggestion_pruning
+            .expect("ERR_SUGGESTION_PRUNING_DISABLED");
+        let mut pruned = 0;
+        for id in 0..self.last_proposal_id {
+            if pruned >= limit {
+                break;
+            }
+            let mut proposal: Proposal = match self.proposals.get(&id) {
+                Some(proposal) => proposal.into(),
+                None => continue,
+            };
+            if proposal.submission_block + pruning.max_age.0 > env::block_height() {
+                // Ids are in submission order.
+                break;
+            }
+            let support = proposal
+                .votes
+                .values()
+                .filter(|vote| matches!(vote.vote, Vote::Approve))
+                .count();
+            if !matches!(proposal.kind, ProposalKind::Suggestion { .. })
+                || matches!(
+                    proposal.status,
+                    ProposalStatus::Approved | ProposalStatus::Removed
+                )
+                || support >= pruning.min_support as usize
+            {
+                continue;
+            }
+            self.internal_return_bond(&mut proposal);
+            if let Some(mut comments) = self.comments.remove(&id) {
+                comments.clear();
+            }
+            self.proposals.remove(&id);
+            self.internal_remove_from_indexes(&proposal, id);
+            self.internal_update_status_counts(Some(&proposal.status), None);
+            pruned += 1;
+        }
+        pruned
+    }
+
+    /// Extends the voting period of given proposal by `extra` nanoseconds, at most its voting
+    /// period. Can only be done once per proposal and only while it's still in progress.
+    pub fn extend_voting_period(&mut self, id: u64, extra: U64) {
+        self.assert_not_paused();
+        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy
+                .can_execute_action(
+                    self.internal_user_info(),
+                    &proposal.kind,
+                    &Action::ExtendVotingPeriod
+                )
+                .1,
+            "ERR_PERMISSION_DENIED"
+        );
+        assert!(
+            matches!(proposal.status, ProposalStatus::InProgress),
+            "ERR_PROPOSAL_NOT_IN_PROGRESS"
+        );
+        assert!(
+            policy.proposal_deadline(&proposal) >= env::block_timestamp(),
+            "ERR_PROPOSAL_EXPIRED"
+        );
+        assert!(!proposal.extended, "ERR_ALREADY_EXTENDED");
+        assert!(
+            extra.0 <= policy.proposal_period_for(proposal.kind.to_policy_label()),
+            "ERR_EXTENSION_TOO_LONG"
+        );
+        proposal.extended = true;
+        proposal.period_extension = extra;
+        self.proposals.insert(&id, &VersionedProposal::V2(proposal));
     }
 
     /// Receiving callback after the proposal has been finalized.
@@ -551,18 +2978,80 @@ impl Contract {
             .get(&proposal_id)
             .expect("ERR_NO_PROPOSAL")
             .into();
+        let initial_status = proposal.status.clone();
+        let legs = proposal.unpaid_legs();
+        let (receiver_amount, fee) = proposal.unpaid_transfer();
+        let expected_results = match &proposal.kind {
+            ProposalKind::MultiTokenTransfer { .. } => legs.len() as u64,
+            ProposalKind::Transfer { .. } => (fee > 0) as u64 + (receiver_amount > 0) as u64,
+            _ => 1,
+        };
         assert_eq!(
             env::promise_results_count(),
-            1,
+            expected_results,
             "ERR_UNEXPECTED_CALLBACK_PROMISES"
         );
-        let result: PromiseOrValue<()> = match env::promise_result(0) {
+        self.internal_release_pending_transfer(&proposal);
+        self.pending_executions.remove(&proposal_id);
+        proposal.failed_leg = None;
+        let failed = (0..expected_results).find(|i| match env::promise_result(*i) {
             PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Successful(_) => self.internal_callback_proposal_success(&mut proposal),
-            PromiseResult::Failed => self.internal_callback_proposal_fail(&mut proposal),
+            PromiseResult::Successful(_) => false,
+            PromiseResult::Failed => true,
+        });
+        for (i, (leg, (token_id, amount))) in legs.iter().enumerate() {
+            if matches!(env::promise_result(i as u64), PromiseResult::Successful(_)) {
+                proposal.paid_legs.push(*leg);
+                if let Some(token_id) = convert_old_to_new_token(token_id) {
+                    self.internal_spend_ft_balance(&token_id, amount.0);
+                }
+            }
+        }
+        if let ProposalKind::Transfer { token_id, .. } = &proposal.kind {
+            // The fee comes first if it was sent, then the transfer to the receiver.
+            let token_id = convert_old_to_new_token(token_id);
+            let paid = |i: u64| matches!(env::promise_result(i), PromiseResult::Successful(_));
+            if fee > 0 && paid(0) {
+                proposal.fee_paid = true;
+                if let Some(token_id) = &token_id {
+                    self.internal_spend_ft_balance(token_id, fee);
+                }
+            }
+            if receiver_amount > 0 && paid((fee > 0) as u64) {
+                proposal.paid_legs.push(0);
+                if let Some(token_id) = &token_id {
+                    self.internal_spend_ft_balance(token_id, receiver_amount);
+                }
+            }
+        }
+        if let (None, ProposalKind::TransferPercent { token_id, .. }) = (failed, &proposal.kind) {
+            if let (Some(token_id), Some(amount)) =
+                (convert_old_to_new_token(token_id), proposal.percent_amount)
+            {
+                self.internal_spend_ft_balance(&token_id, amount.0);
+            }
+        }
+        proposal.percent_amount = None;
+        let result: PromiseOrValue<()> = match failed {
+            None => {
+                self.internal_record_staking(&proposal);
+                self.internal_notify_observer(
+                    proposal_id,
+                    "proposal_executed",
+                    &ProposalStatus::Approved,
+                );
+                self.internal_callback_proposal_success(&mut proposal)
+            }
+            Some(i) => {
+                if let Some((leg, _)) = legs.get(i as usize) {
+                    proposal.failed_leg = Some(*leg);
+                }
+                self.internal_callback_proposal_fail(&mut proposal)
+            }
         };
+        self.internal_update_status_counts(Some(&initial_status), Some(&proposal.status));
         self.proposals
-            .insert(&proposal_id, &VersionedProposal::Default(proposal.into()));
+            .insert(&proposal_id, &VersionedProposal::V2(proposal.into()));
         result
     }
 }
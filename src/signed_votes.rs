@@ -0,0 +1,190 @@
+//! Votes signed off-chain and submitted in batches by a relayer, so voters don't pay gas.
+//! Contracts can't read access keys of other accounts, so voters register a vote key first.
+//! The same keys sign approvals of proposals gathered before they are added.
+
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{CurveType, Gas, PublicKey};
+
+use crate::ed25519;
+use crate::*;
+
+/// Gas to verify and apply one signed vote or approval. Verifying the signature takes about
+/// 8.6 million wasm instructions, about 7 Tgas at 822,756 gas per instruction. Applying the vote
+/// takes about 1.4 Tgas of host calls on top of its own instructions.
+pub const GAS_PER_SIGNED_VOTE: Gas = Gas(15_000_000_000_000);
+
+/// Most signed votes or approvals submitted at once, so a batch fits in the gas of a call.
+pub const MAX_SIGNED_VOTES: usize = 16;
+
+/// Vote on a proposal signed by the voter with their registered vote key.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignedVote {
+    /// Account casting the vote.
+    pub account_id: AccountId,
+    /// One of `VoteApprove`, `VoteReject` or `VoteRemove`.
+    pub action: Action,
+    /// Chosen by the voter, each nonce of a voter can only be used once.
+    pub nonce: U64,
+    /// ed25519 signature of the message returned by `signed_vote_message`.
+    pub signature: Base64VecU8,
+}
+
+/// Message signed by the voter: `<dao account>:<proposal id>:<voter>:<action>:<nonce>`.
+pub fn signed_vote_message(proposal_id: u64, vote: &SignedVote) -> String {
+    format!(
+        "{}:{}:{}:{}:{}",
+        env::current_account_id(),
+        proposal_id,
+        vote.account_id,
+        vote.action.to_policy_label(),
+        vote.nonce.0
+    )
+}
+
+/// Approval of a proposal not added yet, signed by the approver with their registered vote key.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignedApproval {
+    /// Account approving the proposal.
+    pub account_id: AccountId,
+    /// ed25519 signature of the message returned by `proposal_approval_message`.
+    pub signature: Base64VecU8,
+}
+
+/// Message signed by approvers: `<dao account>:<proposal id>:<hash>`, with the hex encoded
+/// sha256 of the borsh serialized input as hash. The id the proposal will be added under is
+/// `get_last_proposal_id`, so approvals can't be replayed to add the same proposal again.
+pub fn proposal_approval_message(proposal_id: u64, input: &ProposalInput) -> String {
+    format!(
+        "{}:{}:{}",
+        env::current_account_id(),
+        proposal_id,
+        hex::encode(env::sha256(&input.try_to_vec().unwrap()))
+    )
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets the ed25519 key the caller signs off-chain votes with.
+    pub fn register_vote_key(&mut self, public_key: PublicKey) {
+        assert_eq!(
+            public_key.curve_type(),
+            CurveType::ED25519,
+            "ERR_INVALID_VOTE_KEY"
+        );
+        self.vote_keys
+            .insert(&env::predecessor_account_id(), &public_key);
+    }
+
+    /// Applies votes signed off-chain to given proposal, as if each voter called `act_proposal`.
+    /// Entries with a bad signature, a used nonce or a non vote action and entries arriving after
+    /// the proposal is decided are skipped. Returns for each entry whether it was applied.
+    /// Other failures, like a voter without permission, fail the whole batch.
+    /// At most `MAX_SIGNED_VOTES` entries, each needing `GAS_PER_SIGNED_VOTE`.
+    pub fn act_proposal_signed(&mut self, id: u64, votes: Vec<SignedVote>) -> Vec<bool> {
+        assert!(votes.len() <= MAX_SIGNED_VOTES, "ERR_TOO_MANY_SIGNED_VOTES");
+        votes
+            .into_iter()
+            .map(|vote| {
+                let applied = self.internal_verify_signed_vote(id, &vote)
+                    && Proposal::from(self.proposals.get(&id).expect("ERR_NO_PROPOSAL")).status
+                        == ProposalStatus::InProgress;
+                if applied {
+                    self.used_vote_nonces
+                        .insert(&(vote.account_id.clone(), vote.nonce.0));
+                    let user = self.internal_account_info(vote.account_id);
+                    self.internal_act_proposal(id, vote.action, None, None, user, false);
+                } else {
+                    log!("Skipped signed vote of {}", vote.account_id);
+                }
+                applied
+            })
+            .collect()
+    }
+
+    /// Adds a proposal like `add_proposal` and approves it on behalf of the signers of the given
+    /// approvals, as if each of them voted to approve it right after. Fails if any signature
+    /// doesn't match the message of `proposal_approval_message` for this input.
+    /// Approvals left once the proposal is decided are ignored. At most `MAX_SIGNED_VOTES`.
+    #[payable]
+    pub fn add_proposal_with_approvals(
+        &mut self,
+        input: ProposalInput,
+        approvals: Vec<SignedApproval>,
+    ) -> u64 {
+        assert!(
+            approvals.len() <= MAX_SIGNED_VOTES,
+            "ERR_TOO_MANY_SIGNED_VOTES"
+        );
+        let expected_id = self.last_proposal_id;
+        let message = proposal_approval_message(expected_id, &input);
+        for approval in approvals.iter() {
+            assert!(
+                self.internal_verify_signature(
+                    &approval.account_id,
+                    message.as_bytes(),
+                    &approval.signature
+                ),
+                "ERR_INVALID_APPROVAL"
+            );
+        }
+        let id = self.add_proposal(input);
+        // Already added with the same idempotency key, approvals were for a new proposal.
+        assert_eq!(id, expected_id, "ERR_PROPOSAL_ALREADY_ADDED");
+        for approval in approvals {
+            if Proposal::from(self.proposals.get(&id).expect("ERR_NO_PROPOSAL")).status
+                != ProposalStatus::InProgress
+            {
+                break;
+            }
+            let user = self.internal_account_info(approval.account_id);
+            self.internal_act_proposal(id, Action::VoteApprove, None, None, user, false);
+        }
+        id
+    }
+}
+
+impl Contract {
+    /// Checks the action, the nonce and the signature of given vote.
+    fn internal_verify_signed_vote(&self, id: u64, vote: &SignedVote) -> bool {
+        if !matches!(
+            vote.action,
+            Action::VoteApprove | Action::VoteReject | Action::VoteRemove
+        ) || self
+            .used_vote_nonces
+            .contains(&(vote.account_id.clone(), vote.nonce.0))
+        {
+            return false;
+        }
+        self.internal_verify_signature(
+            &vote.account_id,
+            signed_vote_message(id, vote).as_bytes(),
+            &vote.signature,
+        )
+    }
+
+    /// Checks that given signature of the message is made with the vote key of given account.
+    fn internal_verify_signature(
+        &self,
+        account_id: &AccountId,
+        message: &[u8],
+        signature: &Base64VecU8,
+    ) -> bool {
+        let public_key = match self.vote_keys.get(account_id) {
+            Some(public_key) => public_key,
+            None => return false,
+        };
+        let public_key: [u8; 32] = match public_key.as_bytes()[1..].try_into() {
+            Ok(public_key) => public_key,
+            Err(_) => return false,
+        };
+        let signature: [u8; 64] = match signature.0.as_slice().try_into() {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        ed25519::verify(&public_key, message, &signature)
+    }
+}
@@ -0,0 +1,50 @@
+//! Treasury $NEAR delegated to validator staking pools by proposals.
+
+use near_sdk::Gas;
+
+use crate::*;
+
+/// Gas for a `deposit_and_stake` or `unstake` call on a staking pool.
+pub const GAS_FOR_STAKING: Gas = Gas(50_000_000_000_000);
+
+/// Gas for a `withdraw_all` call on a staking pool.
+pub const GAS_FOR_WITHDRAW: Gas = Gas(50_000_000_000_000);
+
+/// Methods of the standard staking pool used by this DAO.
+#[ext_contract(ext_staking_pool)]
+pub trait StakingPool {
+    fn deposit_and_stake(&mut self);
+    fn unstake(&mut self, amount: U128);
+    fn withdraw_all(&mut self);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Withdraws the unstaked balance of this DAO in given pool back to the treasury, including
+    /// the rewards that were unstaked. Pools release unstaked $NEAR a few epochs after `unstake`.
+    /// Anyone can call this.
+    pub fn claim_staking_rewards(&mut self, pool_id: AccountId) -> Promise {
+        assert!(
+            self.delegations.contains_key(&pool_id),
+            "ERR_UNKNOWN_STAKING_POOL"
+        );
+        ext_staking_pool::withdraw_all(pool_id, 0, GAS_FOR_WITHDRAW)
+    }
+}
+
+impl Contract {
+    /// Updates the amount delegated to the pool of given executed staking proposal.
+    /// Pools are kept once fully unstaked, so what's left in them can still be withdrawn.
+    pub(crate) fn internal_record_staking(&mut self, proposal: &Proposal) {
+        match &proposal.kind {
+            ProposalKind::StakeDelegate { pool_id, amount } => {
+                *self.delegations.entry(pool_id.clone()).or_default() += amount.0;
+            }
+            ProposalKind::StakeUnstake { pool_id, amount } => {
+                let delegated = self.delegations.entry(pool_id.clone()).or_default();
+                *delegated = delegated.saturating_sub(amount.0);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Payouts vested linearly over a range of blocks, withdrawn by the receiver as they accrue.
+
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_sdk::serde_json::json;
+use near_sdk::Gas;
+
+use crate::events::emit_event;
+use crate::types::{convert_old_to_new_token, ONE_YOCTO_NEAR};
+use crate::*;
+
+/// Gas for the callback after a stream withdrawal, re-crediting failed transfers.
+const GAS_FOR_ON_STREAM_WITHDRAW: Gas = Gas(10_000_000_000_000);
+
+/// Payout of `total` of `token_id` to `receiver_id`, created by an approved `StreamCreate`
+/// proposal. Nothing is vested before `start_block` and everything from `end_block` on.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct Stream {
+    /// Can be "" for $NEAR or a valid account id.
+    pub token_id: OldAccountId,
+    pub receiver_id: AccountId,
+    pub total: U128,
+    pub start_block: BlockHeight,
+    pub end_block: BlockHeight,
+    /// Amount already paid to the receiver.
+    pub withdrawn: U128,
+}
+
+impl Stream {
+    /// Amount vested at the current block.
+    pub fn vested(&self) -> Balance {
+        let block = env::block_height();
+        if block <= self.start_block {
+            0
+        } else if block >= self.end_block {
+            self.total.0
+        } else {
+            self.total.0 * (block - self.start_block) as u128
+                / (self.end_block - self.start_block) as u128
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Pays the receiver of given stream what vested since the last withdrawal.
+    /// The id is the one of the `StreamCreate` proposal. Anyone can call this.
+    pub fn withdraw_stream(&mut self, id: u64) -> Promise {
+        self.assert_not_paused();
+        let mut stream = self.streams.get(&id).expect("ERR_NO_STREAM");
+        let amount = stream.vested() - stream.withdrawn.0;
+        assert!(amount > 0, "ERR_NOTHING_TO_WITHDRAW");
+        stream.withdrawn = U128(stream.withdrawn.0 + amount);
+        self.streams.insert(&id, &stream);
+        emit_event(
+            "stream_withdrawn",
+            json!({
+                "stream_id": U64(id),
+                "receiver_id": stream.receiver_id,
+                "amount": U128(amount),
+            }),
+        );
+        let transfer = match convert_old_to_new_token(&stream.token_id) {
+            None => {
+                self.locked_amount -= amount;
+                Promise::new(stream.receiver_id).transfer(amount)
+            }
+            Some(token_id) => {
+                let gas = self.config.get().unwrap().ft_transfer_gas(&token_id);
+                ext_fungible_token::ft_transfer(
+                    stream.receiver_id,
+                    U128(amount),
+                    None,
+                    token_id,
+                    ONE_YOCTO_NEAR,
+                    gas,
+                )
+            }
+        };
+        transfer.then(ext_self::on_stream_withdraw_callback(
+            id,
+            U128(amount),
+            env::current_account_id(),
+            0,
+            GAS_FOR_ON_STREAM_WITHDRAW,
+        ))
+    }
+
+    /// Credits the withdrawal back to the stream if the transfer has failed.
+    #[private]
+    pub fn on_stream_withdraw_callback(&mut self, id: u64, amount: U128) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "ERR_UNEXPECTED_CALLBACK_PROMISES"
+        );
+        if let PromiseResult::Failed = env::promise_result(0) {
+            let mut stream = self.streams.get(&id).expect("ERR_NO_STREAM");
+            stream.withdrawn = U128(stream.withdrawn.0 - amount.0);
+            if stream.token_id == OLD_BASE_TOKEN {
+                self.locked_amount += amount.0;
+            }
+            self.streams.insert(&id, &stream);
+        }
+    }
+
+    /// Returns the stream created by given proposal, if any.
+    pub fn get_stream(&self, id: u64) -> Option<Stream> {
+        self.streams.get(&id)
+    }
+}
+
+impl Contract {
+    /// Records the stream of given approved `StreamCreate` proposal.
+    /// Streamed $NEAR stays locked until withdrawn.
+    pub(crate) fn internal_create_stream(&mut self, proposal_id: u64, stream: Stream) {
+        if stream.token_id == OLD_BASE_TOKEN {
+            self.locked_amount += stream.total.0;
+        }
+        self.streams.insert(&proposal_id, &stream);
+    }
+}
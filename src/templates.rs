@@ -0,0 +1,74 @@
+//! Named drafts of proposals that are added often, e.g. recurring grants.
+
+use crate::*;
+
+/// Kind and description that proposals added from a template start with.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProposalTemplate {
+    pub kind: ProposalKind,
+    pub description: String,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Stores a template under given name, replacing any previous one.
+    /// Only members of the admin role can call this.
+    pub fn add_template(&mut self, name: String, kind: ProposalKind, description: String) {
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy.is_role_member(ADMIN_ROLE, &env::predecessor_account_id()),
+            "ERR_NOT_ADMIN"
+        );
+        assert!(!name.is_empty(), "ERR_EMPTY_TEMPLATE_NAME");
+        self.templates
+            .insert(&name, &ProposalTemplate { kind, description });
+    }
+
+    /// Adds a proposal from given template, like `add_proposal`.
+    /// Fields of `overrides` replace the template ones, except an empty description.
+    /// The kind can only be overridden by a kind of the same type, e.g. to change an amount.
+    #[payable]
+    pub fn add_proposal_from_template(
+        &mut self,
+        name: String,
+        overrides: Option<ProposalInput>,
+    ) -> u64 {
+        let template = self.templates.get(&name).expect("ERR_NO_TEMPLATE");
+        let proposal = match overrides {
+            Some(mut overrides) => {
+                assert_eq!(
+                    overrides.kind.to_policy_label(),
+                    template.kind.to_policy_label(),
+                    "ERR_TEMPLATE_KIND_MISMATCH"
+                );
+                if overrides.description.is_empty() {
+                    overrides.description = template.description;
+                }
+                overrides
+            }
+            None => ProposalInput {
+                description: template.description,
+                kind: template.kind,
+                tags: vec![],
+                category: None,
+                depends_on: None,
+                reference_hash: None,
+                reference_url: None,
+                idempotency_key: None,
+                executor: None,
+                commit_end: None,
+                required_roles: vec![],
+                cosponsors: vec![],
+                voter_whitelist: vec![],
+            },
+        };
+        self.add_proposal(proposal)
+    }
+
+    /// Returns the template stored under given name.
+    pub fn get_template(&self, name: String) -> Option<ProposalTemplate> {
+        self.templates.get(&name)
+    }
+}
@@ -0,0 +1,269 @@
+//! Cached balances of the fungible tokens held by the DAO and the transfers being sent from them.
+
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::serde_json::json;
+use near_sdk::{Gas, PromiseOrValue};
+
+use crate::events::emit_event;
+use crate::types::convert_old_to_new_token;
+use crate::*;
+
+/// Gas for single ft_balance_of call.
+const GAS_FOR_FT_BALANCE_OF: Gas = Gas(5_000_000_000_000);
+
+/// Gas for the callback storing the fetched balance.
+const GAS_FOR_ON_FT_BALANCE: Gas = Gas(5_000_000_000_000);
+
+/// Gas for single ft_total_supply call.
+const GAS_FOR_FT_TOTAL_SUPPLY: Gas = Gas(5_000_000_000_000);
+
+/// Gas for the callback storing the fetched total supply.
+const GAS_FOR_ON_TOTAL_SUPPLY: Gas = Gas(5_000_000_000_000);
+
+/// Total supply of the governance token, with the block it was fetched at.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct CachedTotalSupply {
+    pub amount: U128,
+    pub block_height: BlockHeight,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Fetches the balance of this DAO in given token and caches it.
+    /// The cached balance is used to reject `Transfer` proposals the DAO can't fund.
+    /// Only tokens paid by a proposal are cached.
+    pub fn refresh_ft_balance(&mut self, token_id: AccountId) -> Promise {
+        assert!(self.ft_tokens.contains(&token_id), "ERR_TOKEN_NOT_TRACKED");
+        ext_fungible_token::ft_balance_of(
+            env::current_account_id(),
+            token_id.clone(),
+            0,
+            GAS_FOR_FT_BALANCE_OF,
+        )
+        .then(ext_self::on_ft_balance(
+            token_id,
+            env::current_account_id(),
+            0,
+            GAS_FOR_ON_FT_BALANCE,
+        ))
+    }
+
+    /// Stores the balance fetched by `refresh_ft_balance`.
+    #[private]
+    pub fn on_ft_balance(&mut self, token_id: AccountId, #[callback_unwrap] balance: U128) -> U128 {
+        self.ft_balances.insert(&token_id, &balance.0);
+        self.ft_tokens.insert(&token_id);
+        balance
+    }
+
+    /// Fetches the total supply of the governance token and caches it.
+    /// The cached supply is the total weight of token weighted votes.
+    pub fn refresh_total_supply(&mut self) -> Promise {
+        let token_id = self
+            .config
+            .get()
+            .unwrap()
+            .governance_token
+            .expect("ERR_NO_GOVERNANCE_TOKEN");
+        ext_fungible_token::ft_total_supply(token_id, 0, GAS_FOR_FT_TOTAL_SUPPLY).then(
+            ext_self::on_total_supply(env::current_account_id(), 0, GAS_FOR_ON_TOTAL_SUPPLY),
+        )
+    }
+
+    /// Stores the total supply fetched by `refresh_total_supply`.
+    #[private]
+    pub fn on_total_supply(&mut self, #[callback_unwrap] total_supply: U128) -> U128 {
+        self.total_supply = Some(CachedTotalSupply {
+            amount: total_supply,
+            block_height: env::block_height(),
+        });
+        total_supply
+    }
+}
+
+/// Message of a token deposit earmarking it for a proposal, e.g. `{"proposal_id": "3"}`.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct DepositMessage {
+    proposal_id: U64,
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    /// Accepts all tokens sent with `ft_transfer_call`, adding them to the cached balance of the
+    /// calling token if a proposal pays it. A tracked token without a cached balance starts with
+    /// the deposit, `refresh_ft_balance` picks up what the DAO held before. Other tokens aren't
+    /// cached. `msg` can earmark the deposit for an existing proposal with a `DepositMessage`,
+    /// which is only reported in the event.
+    /// Tokens sent with `LOCK_VOTING_POWER_MSG` are locked as voting power of the sender instead.
+    /// Other messages are ignored.
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let token_id = env::predecessor_account_id();
+        if msg == LOCK_VOTING_POWER_MSG {
+            self.internal_lock_voting_balance(&token_id, &sender_id, amount.0);
+            return PromiseOrValue::Value(U128(0));
+        }
+        if self.ft_tokens.contains(&token_id) {
+            let balance = self.ft_balances.get(&token_id).unwrap_or(0);
+            self.ft_balances.insert(&token_id, &(balance + amount.0));
+        }
+        let proposal_id = if msg.is_empty() {
+            None
+        } else {
+            match near_sdk::serde_json::from_str::<DepositMessage>(&msg) {
+                Ok(message) if self.proposals.contains_key(&message.proposal_id.0) => {
+                    Some(message.proposal_id)
+                }
+                _ => {
+                    log!("Ignoring unknown deposit message");
+                    None
+                }
+            }
+        };
+        emit_event(
+            "ft_deposit",
+            json!({
+                "token_id": token_id,
+                "sender_id": sender_id,
+                "amount": amount,
+                "proposal_id": proposal_id,
+            }),
+        );
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+impl Contract {
+    /// Cached total supply of the governance token, zero if it was never fetched.
+    pub(crate) fn cached_total_supply(&self) -> Balance {
+        self.total_supply
+            .as_ref()
+            .map(|total_supply| total_supply.amount.0)
+            .unwrap_or(0)
+    }
+
+    /// Cached total supply of the governance token, for computing the status of proposals.
+    /// Emits `total_supply_stale` if it's older than `Config::total_supply_max_age`.
+    pub(crate) fn internal_total_supply(&self) -> Balance {
+        let config = self.config.get().unwrap();
+        let token_id = match config.governance_token {
+            Some(token_id) => token_id,
+            None => return self.cached_total_supply(),
+        };
+        let fetched_at = self
+            .total_supply
+            .as_ref()
+            .map(|total_supply| total_supply.block_height);
+        let stale = fetched_at
+            .map(|block| env::block_height() > block + config.total_supply_max_age.0)
+            .unwrap_or(true);
+        if stale {
+            emit_event(
+                "total_supply_stale",
+                json!({
+                    "token_id": token_id,
+                    "fetched_at": fetched_at.map(U64),
+                }),
+            );
+        }
+        self.cached_total_supply()
+    }
+
+    /// Panics if the DAO doesn't hold `amount` of given token.
+    /// $NEAR is checked against the available balance, tokens against the cached balance if any.
+    pub(crate) fn assert_treasury_covers(&self, token_id: &Option<AccountId>, amount: Balance) {
+        let balance = match token_id {
+            None => {
+                // Attached deposit is locked as the bond of the proposal being added.
+                self.get_available_amount()
+                    .0
+                    .saturating_sub(env::attached_deposit())
+            }
+            Some(token_id) => match self.ft_balances.get(token_id) {
+                Some(balance) => balance,
+                None => return,
+            },
+        };
+        assert!(amount <= balance, "ERR_INSUFFICIENT_TREASURY");
+    }
+
+    /// Balance of given token that isn't committed yet. Use "" for $NEAR.
+    /// Tokens use the cached balance, less the claimable payouts and transfers being sent.
+    pub(crate) fn internal_spendable_balance(&self, token_id: &OldAccountId) -> Balance {
+        match convert_old_to_new_token(token_id) {
+            None => self.get_available_amount().0,
+            Some(ft_token_id) => {
+                let reserved = self.claimable_totals.get(token_id).copied().unwrap_or(0)
+                    + self
+                        .pending_transfers
+                        .get(&ft_token_id)
+                        .copied()
+                        .unwrap_or(0);
+                self.ft_balances
+                    .get(&ft_token_id)
+                    .unwrap_or(0)
+                    .saturating_sub(reserved)
+            }
+        }
+    }
+
+    /// Starts caching the balance of given token, once a proposal pays it.
+    /// Use "" for $NEAR, which isn't cached.
+    pub(crate) fn internal_track_ft_token(&mut self, token_id: &OldAccountId) {
+        if let Some(token_id) = convert_old_to_new_token(token_id) {
+            self.ft_tokens.insert(&token_id);
+        }
+    }
+
+    /// Takes `amount` of given token sent by the DAO off its cached balance, if any.
+    pub(crate) fn internal_spend_ft_balance(&mut self, token_id: &AccountId, amount: Balance) {
+        if let Some(balance) = self.ft_balances.get(token_id) {
+            self.ft_balances
+                .insert(token_id, &balance.saturating_sub(amount));
+        }
+    }
+
+    /// Tracks `amount` of given token being sent by an approved transfer until its callback.
+    /// Sent $NEAR leaves the balance right away, so it's not tracked.
+    pub(crate) fn internal_add_pending_transfer(&mut self, token_id: &AccountId, amount: Balance) {
+        *self.pending_transfers.entry(token_id.clone()).or_default() += amount;
+    }
+
+    /// Stops tracking the transfers of given proposal, once its execution is over.
+    pub(crate) fn internal_release_pending_transfer(&mut self, proposal: &Proposal) {
+        let transfers = match &proposal.kind {
+            ProposalKind::Transfer { token_id, .. } => {
+                let (receiver_amount, fee) = proposal.unpaid_transfer();
+                vec![(token_id.clone(), U128(receiver_amount + fee))]
+            }
+            ProposalKind::TransferPercent { token_id, .. } => proposal
+                .percent_amount
+                .map(|amount| vec![(token_id.clone(), amount)])
+                .unwrap_or_default(),
+            ProposalKind::MultiTokenTransfer { .. } => proposal
+                .unpaid_legs()
+                .into_iter()
+                .map(|(_, transfer)| transfer)
+                .collect(),
+            _ => return,
+        };
+        for (token_id, amount) in transfers {
+            if let Some(token_id) = convert_old_to_new_token(&token_id) {
+                if let Some(pending) = self.pending_transfers.get_mut(&token_id) {
+                    *pending = pending.saturating_sub(amount.0);
+                    if *pending == 0 {
+                        self.pending_transfers.remove(&token_id);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,9 @@
+use std::collections::HashMap;
+
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::json_types::Base64VecU8;
+use near_sdk::json_types::{Base64VecU8, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{AccountId, Balance, Gas};
+use near_sdk::{AccountId, Balance, BlockHeight, Gas};
 
 /// Account ID used for $NEAR in near-sdk v3.
 /// Need to keep it around for backward compatibility.
@@ -17,6 +19,43 @@ pub const ONE_YOCTO_NEAR: Balance = 1;
 /// Gas for single ft_transfer call.
 pub const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
 
+/// Minimum gas that can be configured for `ft_transfer` calls on a token.
+pub const MIN_FT_TRANSFER_GAS: Gas = Gas(5_000_000_000_000);
+
+/// Maximum gas that can be configured for `ft_transfer` calls on a token.
+pub const MAX_FT_TRANSFER_GAS: Gas = Gas(100_000_000_000_000);
+
+/// Gas for single mint or burn call on the governance token.
+pub const GAS_FOR_MINT_OR_BURN: Gas = Gas(10_000_000_000_000);
+
+/// Default gas attached to the callback after proposal execution.
+pub const DEFAULT_CALLBACK_GAS: Gas = Gas(20_000_000_000_000);
+
+/// Minimum gas that can be configured for the callback after proposal execution.
+pub const MIN_CALLBACK_GAS: Gas = Gas(5_000_000_000_000);
+
+/// Maximum gas that can be configured for the callback after proposal execution.
+pub const MAX_CALLBACK_GAS: Gas = Gas(100_000_000_000_000);
+
+/// Default number of blocks a removed proposal can be restored for, about a day.
+pub const DEFAULT_REMOVAL_GRACE_PERIOD: u64 = 86_400;
+
+/// Default number of times a failed proposal execution can be retried.
+pub const DEFAULT_MAX_EXECUTION_RETRIES: u32 = 3;
+
+/// Default number of blocks after which the cached total supply of the governance token is stale.
+pub const DEFAULT_TOTAL_SUPPLY_MAX_AGE: u64 = 86_400;
+
+/// Gas for single storage_deposit call.
+pub const GAS_FOR_STORAGE_DEPOSIT: Gas = Gas(10_000_000_000_000);
+
+/// Deposit attached to storage_deposit when registering a receiver of the token.
+/// Matches the minimum storage balance of the standard fungible token, the excess is refunded.
+pub const STORAGE_DEPOSIT_FOR_REGISTRATION: Balance = 1_250_000_000_000_000_000_000;
+
+/// Maximum number of config changes kept in the config history.
+pub const MAX_CONFIG_HISTORY: usize = 10;
+
 /// Configuration of the DAO.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
@@ -28,6 +67,285 @@ pub struct Config {
     /// Generic metadata. Can be used by specific UI to store additional data.
     /// This is not used by anything in the contract.
     pub metadata: Base64VecU8,
+    /// If set, `UpgradeSelf` deploys code from blobs stored in this contract instead of
+    /// going through the factory.
+    #[serde(default)]
+    pub upgrade_from_blob: bool,
+    /// Gas attached to the callback after proposal execution.
+    #[serde(default = "default_callback_gas")]
+    pub callback_gas: U64,
+    /// Minimum stake required to add a proposal. Zero allows anyone to add proposals.
+    /// Needs a `voting_power_source` based on balances, every member has the same stake otherwise.
+    #[serde(default = "default_min_proposal_stake")]
+    pub min_proposal_stake: U128,
+    /// Number of blocks after removal during which a guardian can restore a removed proposal.
+    #[serde(default = "default_removal_grace_period")]
+    pub removal_grace_period: U64,
+    /// If set, approved transfers without `msg` are credited to the receivers,
+    /// who withdraw them with `claim`, instead of being sent right away.
+    #[serde(default)]
+    pub claimable_payouts: bool,
+    /// If set, members who voted to approve a proposal that gets removed lose part of their
+    /// locked stake to the treasury.
+    #[serde(default)]
+    pub slashing: Option<SlashingConfig>,
+    /// Maximum total deposit attached to the actions of a single function call proposal.
+    /// Calls without deposit are always allowed. Not capped unless set.
+    #[serde(default = "default_max_function_call_deposit")]
+    pub max_function_call_deposit: U128,
+    /// If set, limits how many proposals a single account can add within a window of blocks.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// Policy labels of the proposal kinds that can't be added anymore.
+    /// Proposals added before a kind was disabled can still be voted on and executed.
+    #[serde(default)]
+    pub disabled_kinds: Vec<String>,
+    /// How many times the execution of an approved proposal can be retried after failing.
+    /// Once exhausted, finalizing the proposal expires it.
+    #[serde(default = "default_max_execution_retries")]
+    pub max_execution_retries: u32,
+    /// Whether members of the admin role can execute proposals in progress without votes.
+    #[serde(default)]
+    pub allow_force_execute: bool,
+    /// Number of blocks after being added to a role by `AddMemberToRole` before a member
+    /// can vote through that role.
+    #[serde(default = "default_member_voting_delay_blocks")]
+    pub member_voting_delay_blocks: U64,
+    /// Token whose total supply is the total weight of token weighted votes.
+    #[serde(default)]
+    pub governance_token: Option<AccountId>,
+    /// Number of blocks after which the cached total supply is reported as stale when used.
+    #[serde(default = "default_total_supply_max_age")]
+    pub total_supply_max_age: U64,
+    /// Shortest proposal period, in nanoseconds, that policy changes can set.
+    /// Keeps a brief quorum from passing proposals before other members can react.
+    #[serde(default = "default_min_voting_period")]
+    pub min_voting_period: U64,
+    /// Contract notified when proposals are added, approved and executed.
+    #[serde(default)]
+    pub observer: Option<AccountId>,
+    /// How proposals with as much approve as reject weight are decided at their deadline.
+    #[serde(default)]
+    pub tie_break: TieBreak,
+    /// Whether the deciding vote executes an approved proposal. If not set, approved proposals
+    /// are executed with a separate `execute` call, keeping the cost of voting predictable.
+    #[serde(default = "default_auto_execute")]
+    pub auto_execute: bool,
+    /// Whether proposers can vote on their own proposals.
+    #[serde(default = "default_proposer_can_vote")]
+    pub proposer_can_vote: bool,
+    /// If set, stale suggestions with little support can be pruned by anyone.
+    #[serde(default)]
+    pub suggestion_pruning: Option<SuggestionPruning>,
+    /// Gas for `ft_transfer` calls per token, for tokens needing other than `GAS_FOR_FT_TRANSFER`.
+    #[serde(default)]
+    pub ft_transfer_gas: HashMap<AccountId, U64>,
+    /// Minimum length of the description of proposals of the high risk kinds.
+    #[serde(default)]
+    pub min_description_length: u32,
+    /// Policy labels of the proposal kinds `min_description_length` applies to.
+    /// Votes and suggestions are only signaling, so they are exempt even if listed.
+    #[serde(default = "default_high_risk_kinds")]
+    pub high_risk_kinds: Vec<String>,
+    /// Share of every approved `Transfer` sent to `fee_receiver` instead, in basis points.
+    #[serde(default)]
+    pub transfer_fee_bps: u16,
+    /// Account receiving the transfer fee, e.g. a maintenance fund.
+    /// It must be registered with the tokens transferred, the proposal fails until the fee is paid
+    /// otherwise. The fee and its receiver are fixed when a transfer is first executed.
+    #[serde(default)]
+    pub fee_receiver: Option<AccountId>,
+    /// If set, accounts must wait between their votes, to slow down scripted voting.
+    #[serde(default)]
+    pub vote_cooldown: Option<VoteCooldown>,
+    /// What the stake of a voter is, i.e. the weight of its token weighted votes.
+    /// Only changed by a `ChangeConfig` proposal, like the rest of the config.
+    #[serde(default)]
+    pub voting_power_source: VotingPowerSource,
+}
+
+/// Outcome of a proposal with exactly as much approve as reject weight in a role at its deadline.
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TieBreak {
+    #[default]
+    RejectOnTie,
+    ApproveOnTie,
+    /// The vote of the proposer decides, rejected if the proposer didn't vote to approve.
+    ProposerWins,
+}
+
+/// Where the stake of a voter comes from.
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum VotingPowerSource {
+    /// Every member has a stake of one.
+    #[default]
+    OnePerMember,
+    /// Balance of given token the voter locked in this DAO before the proposal was submitted.
+    /// Tokens are locked with `ft_transfer_call`, see `LOCK_VOTING_POWER_MSG`.
+    FtBalance(AccountId),
+    /// $NEAR locked by the voter with `lock_stake`.
+    StakedBalance,
+}
+
+
+/// How much locked stake is slashed from approvers of a removed proposal.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SlashingConfig {
+    /// Part of the locked stake of each approver to slash, in basis points.
+    pub basis_points: u32,
+    /// Maximum amount slashed in total for a single removed proposal.
+    pub max_total: U128,
+}
+
+/// Limit on the number of proposals added by a single account.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RateLimit {
+    /// Maximum number of proposals an account can add within the window.
+    pub max_proposals: u32,
+    /// Length of the window in blocks.
+    pub window: U64,
+    /// Roles whose members are not limited, like the council.
+    pub exempt_roles: Vec<String>,
+}
+
+/// Minimum gap between two votes of the same account, across all proposals.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VoteCooldown {
+    /// Number of blocks after a vote before the account can vote again.
+    pub blocks: U64,
+    /// Roles whose members can vote without waiting, like the council.
+    pub exempt_roles: Vec<String>,
+}
+
+/// Config change made by a `ChangeConfig` proposal.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConfigHistoryEntry {
+    /// Block at which the config was changed.
+    pub block_height: BlockHeight,
+    /// Proposal that changed the config.
+    pub proposal_id: u64,
+    /// Proposer of that proposal.
+    pub proposer: AccountId,
+    /// Config before the change.
+    pub old_config: Config,
+    /// Config after the change.
+    pub new_config: Config,
+}
+
+/// When suggestions nobody supported can be pruned with `prune_stale_suggestions`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SuggestionPruning {
+    /// Age in blocks after which a suggestion is stale.
+    pub max_age: U64,
+    /// Number of approve votes a suggestion needs to be kept.
+    pub min_support: u32,
+}
+
+pub(crate) fn default_callback_gas() -> U64 {
+    U64(DEFAULT_CALLBACK_GAS.0)
+}
+
+pub(crate) fn default_min_proposal_stake() -> U128 {
+    U128(0)
+}
+
+pub(crate) fn default_removal_grace_period() -> U64 {
+    U64(DEFAULT_REMOVAL_GRACE_PERIOD)
+}
+
+pub(crate) fn default_member_voting_delay_blocks() -> U64 {
+    U64(0)
+}
+
+pub(crate) fn default_total_supply_max_age() -> U64 {
+    U64(DEFAULT_TOTAL_SUPPLY_MAX_AGE)
+}
+
+pub(crate) fn default_min_voting_period() -> U64 {
+    U64(0)
+}
+
+pub(crate) fn default_auto_execute() -> bool {
+    true
+}
+
+pub(crate) fn default_proposer_can_vote() -> bool {
+    true
+}
+
+pub(crate) fn default_high_risk_kinds() -> Vec<String> {
+    vec![
+        "call".to_string(),
+        "upgrade_self".to_string(),
+        "upgrade_remote".to_string(),
+    ]
+}
+
+pub(crate) fn default_max_execution_retries() -> u32 {
+    DEFAULT_MAX_EXECUTION_RETRIES
+}
+
+pub(crate) fn default_max_function_call_deposit() -> U128 {
+    U128(Balance::MAX)
+}
+
+impl Config {
+    /// Panics if the callback gas, the slashing rate or the transfer fee are out of bounds,
+    /// or if a minimum proposal stake is set without a balance based voting power source.
+    pub fn assert_valid(&self) {
+        assert!(
+            self.callback_gas.0 >= MIN_CALLBACK_GAS.0 && self.callback_gas.0 <= MAX_CALLBACK_GAS.0,
+            "ERR_INVALID_CALLBACK_GAS"
+        );
+        if let Some(slashing) = &self.slashing {
+            assert!(slashing.basis_points <= 10_000, "ERR_INVALID_SLASHING");
+        }
+        assert!(
+            self.ft_transfer_gas
+                .values()
+                .all(|gas| { gas.0 >= MIN_FT_TRANSFER_GAS.0 && gas.0 <= MAX_FT_TRANSFER_GAS.0 }),
+            "ERR_INVALID_FT_TRANSFER_GAS"
+        );
+        assert!(
+            self.transfer_fee_bps <= 10_000
+                && (self.transfer_fee_bps == 0 || self.fee_receiver.is_some()),
+            "ERR_INVALID_TRANSFER_FEE"
+        );
+        assert!(
+            self.min_proposal_stake.0 == 0
+                || self.voting_power_source != VotingPowerSource::OnePerMember,
+            "ERR_MIN_STAKE_WITHOUT_BALANCE"
+        );
+    }
+
+    /// Part of a transfer of `amount` that goes to the fee receiver, none without a receiver.
+    pub fn transfer_fee(&self, amount: Balance) -> Balance {
+        if self.fee_receiver.is_none() {
+            return 0;
+        }
+        let bps = self.transfer_fee_bps as Balance;
+        // Split to not overflow on large token balances.
+        amount / 10_000 * bps + amount % 10_000 * bps / 10_000
+    }
+
+    /// Gas for an `ft_transfer` call on given token.
+    pub fn ft_transfer_gas(&self, token_id: &AccountId) -> Gas {
+        self.ft_transfer_gas
+            .get(token_id)
+            .map(|gas| Gas(gas.0))
+            .unwrap_or(GAS_FOR_FT_TRANSFER)
+    }
 }
 
 #[cfg(test)]
@@ -37,6 +355,33 @@ impl Config {
             name: "Test".to_string(),
             purpose: "to test".to_string(),
             metadata: Base64VecU8(vec![]),
+            upgrade_from_blob: false,
+            callback_gas: default_callback_gas(),
+            min_proposal_stake: default_min_proposal_stake(),
+            removal_grace_period: default_removal_grace_period(),
+            claimable_payouts: false,
+            slashing: None,
+            max_function_call_deposit: default_max_function_call_deposit(),
+            rate_limit: None,
+            disabled_kinds: vec![],
+            max_execution_retries: default_max_execution_retries(),
+            allow_force_execute: false,
+            member_voting_delay_blocks: default_member_voting_delay_blocks(),
+            governance_token: None,
+            total_supply_max_age: default_total_supply_max_age(),
+            min_voting_period: default_min_voting_period(),
+            observer: None,
+            tie_break: TieBreak::default(),
+            auto_execute: default_auto_execute(),
+            proposer_can_vote: default_proposer_can_vote(),
+            suggestion_pruning: None,
+            ft_transfer_gas: HashMap::default(),
+            min_description_length: 0,
+            high_risk_kinds: default_high_risk_kinds(),
+            transfer_fee_bps: 0,
+            fee_receiver: None,
+            vote_cooldown: None,
+            voting_power_source: VotingPowerSource::default(),
         }
     }
 }
@@ -60,11 +405,22 @@ pub enum Action {
     Finalize,
     /// Move a proposal to the hub to shift into another DAO.
     MoveToHub,
+    /// Extend the voting period of given proposal once. Used via `extend_voting_period`.
+    ExtendVotingPeriod,
+    /// Vote for the option at given index of a poll.
+    VoteOption(usize),
+    /// Vote splitting the weight between approve, reject and abstain, in basis points.
+    /// Used via `act_proposal_split`.
+    VoteSplit(u16, u16, u16),
 }
 
 impl Action {
     pub fn to_policy_label(&self) -> String {
-        format!("{:?}", self)
+        match self {
+            Action::VoteOption(_) => "VoteOption".to_string(),
+            Action::VoteSplit(..) => "VoteSplit".to_string(),
+            _ => format!("{:?}", self),
+        }
     }
 }
 
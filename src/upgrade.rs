@@ -112,7 +112,6 @@ pub(crate) fn upgrade_using_factory(code_hash: Base58CryptoHash) {
     env::promise_return(promise_id);
 }
 
-#[allow(dead_code)]
 pub(crate) fn upgrade_self(hash: &[u8]) {
     let current_id = env::current_account_id();
     let input = env::storage_read(hash).expect("ERR_NO_HASH");
@@ -1,7 +1,10 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 
 use std::cmp::min;
+use std::collections::HashMap;
 
+use crate::proposals::VoteWithTimestamp;
+use crate::types::convert_old_to_new_token;
 use crate::*;
 
 /// This is format of output via JSON for the proposal.
@@ -14,6 +17,85 @@ pub struct ProposalOutput {
     pub proposal: Proposal,
 }
 
+/// Maximum number of proposals materialized to sort them by activity or approval progress.
+pub const MAX_SORTED_PROPOSALS: u64 = 100;
+
+/// Expected time between blocks in nanoseconds, about a second, to convert windows given in
+/// blocks to the timestamps deadlines are computed with.
+pub const EXPECTED_BLOCK_TIME: u64 = 1_000_000_000;
+
+/// Order of proposals in the list view.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub enum ProposalSort {
+    /// Oldest first.
+    ByIdAsc,
+    /// Newest first.
+    ByIdDesc,
+    /// Most recently voted on first.
+    ByActivity,
+    /// Closest to passing first.
+    ByApprovalProgress,
+}
+
+/// Proposals needing attention of a council member.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilDigest {
+    /// Proposals in progress the account hasn't voted on yet.
+    pub to_vote: Vec<ProposalOutput>,
+    /// Proposals that are expired or failed and can be finalized.
+    pub to_finalize: Vec<ProposalOutput>,
+    /// Suggestions in progress.
+    pub suggestions: Vec<ProposalOutput>,
+}
+
+/// Spendable and committed funds of the DAO.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasurySummary {
+    /// Balance of the DAO in $NEAR.
+    pub total: U128,
+    /// $NEAR locked for the storage of the contract.
+    pub storage: U128,
+    /// $NEAR locked in bonds of proposals.
+    pub bonds: U128,
+    /// $NEAR locked as stake for voting.
+    pub locked_stake: U128,
+    /// Owed for approved payouts that are waiting to be claimed or being sent, per token.
+    /// "" is $NEAR.
+    pub reserved: HashMap<OldAccountId, U128>,
+    /// What is left to spend, per token. "" is $NEAR, tokens use their last fetched balance.
+    pub available: HashMap<OldAccountId, U128>,
+}
+
+/// Tracked obligations in one token compared to the balance actually held.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenReconciliation {
+    /// Owed for bonds, locked stake, claimable payouts and transfers being sent.
+    pub obligations: U128,
+    /// Balance held. For $NEAR the account balance less storage, for tokens the last fetched one.
+    pub balance: U128,
+    /// Balance above the obligations.
+    pub surplus: U128,
+    /// Obligations the balance doesn't cover, which means a ledger drifted.
+    pub deficit: U128,
+}
+
+/// Tracked obligations compared to the balances held, per token. "" is $NEAR.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReconcileReport {
+    pub tokens: HashMap<OldAccountId, TokenReconciliation>,
+    /// `locked_amount` as tracked, which should match the $NEAR obligations.
+    pub locked_amount: U128,
+}
+
 #[near_bindgen]
 impl Contract {
     /// Returns semver of this contract.
@@ -31,6 +113,124 @@ impl Contract {
         self.policy.get().unwrap().to_policy().clone()
     }
 
+    /// Returns the vote policy of the current policy used for roles without a specific one.
+    pub fn get_default_vote_policy(&self) -> VotePolicy {
+        self.policy.get().unwrap().to_policy().default_vote_policy
+    }
+
+    /// Returns the parameters of the current policy, as updated by `ChangePolicyUpdateParameters`.
+    pub fn get_policy_parameters(&self) -> PolicyParameters {
+        let policy = self.policy.get().unwrap().to_policy();
+        PolicyParameters {
+            proposal_period: Some(policy.proposal_period),
+            proposal_periods: Some(policy.proposal_periods),
+        }
+    }
+
+    /// Returns the role of the current policy with given name, if any.
+    pub fn get_role(&self, name: String) -> Option<RolePermission> {
+        self.get_policy()
+            .roles
+            .into_iter()
+            .find(|role| role.name == name)
+    }
+
+    /// Returns all roles of the current policy, with their members, permissions and vote policies.
+    pub fn get_roles(&self) -> Vec<RolePermission> {
+        self.get_policy().roles
+    }
+
+    /// Returns the members of each role of the current policy by role name, sorted.
+    /// Accounts in several roles are listed in each of them.
+    pub fn get_all_members(&self) -> HashMap<String, Vec<AccountId>> {
+        self.get_policy()
+            .roles
+            .into_iter()
+            .map(|role| {
+                let mut members: Vec<AccountId> = match role.kind {
+                    RoleKind::Member(accounts) | RoleKind::Group(accounts) => {
+                        accounts.into_iter().collect()
+                    }
+                };
+                members.sort();
+                (role.name, members)
+            })
+            .collect()
+    }
+
+    /// Returns up to `limit` previous policies, newest first.
+    pub fn get_policy_history(&self, limit: u64) -> Vec<PolicyHistoryEntry> {
+        self.policy_history
+            .get()
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .take(limit as usize)
+            .collect()
+    }
+
+    /// Returns up to `limit` previous config changes, newest first.
+    pub fn get_config_history(&self, limit: u64) -> Vec<ConfigHistoryEntry> {
+        self.config_history
+            .get()
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .take(limit as usize)
+            .collect()
+    }
+
+    /// Returns the $NEAR delegated to each staking pool.
+    pub fn get_delegations(&self) -> HashMap<AccountId, U128> {
+        self.delegations
+            .iter()
+            .map(|(pool_id, amount)| (pool_id.clone(), U128(*amount)))
+            .collect()
+    }
+
+    /// Returns the stake locked by given account, if any.
+    pub fn get_lockup(&self, account_id: AccountId) -> Option<StakeLockup> {
+        self.lockups.get(&account_id)
+    }
+
+    /// Returns the last fetched balance of this DAO in given token, if any.
+    pub fn get_ft_balance(&self, token_id: AccountId) -> Option<U128> {
+        self.ft_balances.get(&token_id).map(U128)
+    }
+
+    /// Returns the policy label of each proposal kind, used in role permissions and vote policies.
+    pub fn get_proposal_kind_labels(&self) -> HashMap<String, String> {
+        PROPOSAL_KIND_LABELS
+            .iter()
+            .map(|(kind, label)| (kind.to_string(), label.to_string()))
+            .collect()
+    }
+
+    /// Returns the JSON schema of proposal kinds, see `PROPOSAL_KIND_SCHEMA`.
+    pub fn get_proposal_schema(&self) -> String {
+        PROPOSAL_KIND_SCHEMA.to_string()
+    }
+
+    /// Returns payouts waiting to be claimed by given account, per token.
+    pub fn get_claimable(&self, account_id: AccountId) -> HashMap<OldAccountId, U128> {
+        self.claimable
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(token_id, amount)| (token_id, U128(amount)))
+            .collect()
+    }
+
+    /// Returns the key given account signs off-chain votes with, if registered.
+    pub fn get_vote_key(&self, account_id: AccountId) -> Option<PublicKey> {
+        self.vote_keys.get(&account_id)
+    }
+
+    /// Returns if the contract is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
     /// Returns if blob with given hash is stored.
     pub fn has_blob(&self, hash: Base58CryptoHash) -> bool {
         env::storage_has_key(&CryptoHash::from(hash))
@@ -43,27 +243,274 @@ impl Contract {
     }
 
     /// Returns available amount of NEAR that can be spent (outside of amount for storage and bonds).
-    // pub fn get_available_amount(&self) -> U128 {
-    //     U128(env::account_balance() - self.get_locked_storage_amount().0 - self.locked_amount)
-    // }
+    pub fn get_available_amount(&self) -> U128 {
+        U128(env::account_balance() - self.get_locked_storage_amount().0 - self.locked_amount)
+    }
+
+    /// Returns the balances of the DAO and how much of them is committed, see `TreasurySummary`.
+    pub fn get_treasury_summary(&self) -> TreasurySummary {
+        let claimable_near = self
+            .claimable_totals
+            .get(OLD_BASE_TOKEN)
+            .copied()
+            .unwrap_or(0);
+        let mut reserved: HashMap<OldAccountId, Balance> = self.claimable_totals.clone();
+        for (token_id, amount) in self.pending_transfers.iter() {
+            *reserved.entry(token_id.to_string()).or_default() += amount;
+        }
+        let mut available = HashMap::new();
+        available.insert(OLD_BASE_TOKEN.to_string(), self.get_available_amount());
+        for token_id in self.ft_tokens.iter() {
+            available.insert(
+                token_id.to_string(),
+                U128(self.internal_spendable_balance(&token_id.to_string())),
+            );
+        }
+        TreasurySummary {
+            total: U128(env::account_balance()),
+            storage: self.get_locked_storage_amount(),
+            bonds: U128(self.locked_amount - self.locked_stake - claimable_near),
+            locked_stake: U128(self.locked_stake),
+            reserved: reserved
+                .into_iter()
+                .map(|(token_id, amount)| (token_id, U128(amount)))
+                .collect(),
+            available,
+        }
+    }
+
+    /// Compares the obligations of the DAO to the balances it holds, see `ReconcileReport`.
+    /// $NEAR bonds and streams are summed over the stored proposals instead of read from
+    /// `locked_amount`, so drift of the latter shows up too.
+    pub fn reconcile(&self) -> ReconcileReport {
+        let bonds: Balance = (0..self.last_proposal_id)
+            .filter_map(|id| self.proposals.get(&id))
+            .map(|proposal| Proposal::from(proposal).bond.0)
+            .sum();
+        let mut obligations: HashMap<OldAccountId, Balance> = self.claimable_totals.clone();
+        *obligations.entry(OLD_BASE_TOKEN.to_string()).or_default() += bonds + self.locked_stake;
+        for (token_id, amount) in self.pending_transfers.iter() {
+            *obligations.entry(token_id.to_string()).or_default() += amount;
+        }
+        for stream in (0..self.last_proposal_id).filter_map(|id| self.streams.get(&id)) {
+            *obligations.entry(stream.token_id).or_default() += stream.total.0 - stream.withdrawn.0;
+        }
+        for token_id in self.ft_tokens.iter() {
+            obligations.entry(token_id.to_string()).or_default();
+        }
+        let tokens = obligations
+            .into_iter()
+            .map(|(token_id, obligations)| {
+                let balance = match convert_old_to_new_token(&token_id) {
+                    None => {
+                        env::account_balance().saturating_sub(self.get_locked_storage_amount().0)
+                    }
+                    Some(ft_token_id) => self.ft_balances.get(&ft_token_id).unwrap_or(0),
+                };
+                let reconciliation = TokenReconciliation {
+                    obligations: U128(obligations),
+                    balance: U128(balance),
+                    surplus: U128(balance.saturating_sub(obligations)),
+                    deficit: U128(obligations.saturating_sub(balance)),
+                };
+                (token_id, reconciliation)
+            })
+            .collect();
+        ReconcileReport {
+            tokens,
+            locked_amount: U128(self.locked_amount),
+        }
+    }
+
+    /// Returns number of proposals in each status.
+    pub fn get_proposal_status_counts(&self) -> HashMap<ProposalStatus, u64> {
+        self.proposal_status_counts.clone()
+    }
 
     /// Last proposal's id.
     pub fn get_last_proposal_id(&self) -> u64 {
         self.last_proposal_id
     }
 
-    /// Get proposals in paginated view.
-    pub fn get_proposals(&self, from_index: u64, limit: u64) -> Vec<ProposalOutput> {
-        (from_index..min(self.last_proposal_id, from_index + limit))
+    /// Get proposals in paginated view, by default in id order.
+    /// `ByActivity` and `ByApprovalProgress` have to read and sort the proposals before paginating,
+    /// so they only consider the latest `MAX_SORTED_PROPOSALS` proposals to bound the gas.
+    pub fn get_proposals(
+        &self,
+        from_index: u64,
+        limit: u64,
+        sort: Option<ProposalSort>,
+    ) -> Vec<ProposalOutput> {
+        let output = |id| {
+            self.proposals.get(&id).map(|proposal| ProposalOutput {
+                id,
+                proposal: proposal.into(),
+            })
+        };
+        match sort.unwrap_or(ProposalSort::ByIdAsc) {
+            ProposalSort::ByIdAsc => (from_index..min(self.last_proposal_id, from_index + limit))
+                .filter_map(output)
+                .collect(),
+            ProposalSort::ByIdDesc => {
+                let to_index = self.last_proposal_id.saturating_sub(from_index);
+                (to_index.saturating_sub(limit)..to_index)
+                    .rev()
+                    .filter_map(output)
+                    .collect()
+            }
+            sort => {
+                let mut proposals: Vec<ProposalOutput> =
+                    (self.last_proposal_id.saturating_sub(MAX_SORTED_PROPOSALS)
+                        ..self.last_proposal_id)
+                        .filter_map(output)
+                        .collect();
+                if sort == ProposalSort::ByActivity {
+                    let last_vote = |output: &ProposalOutput| {
+                        output
+                            .proposal
+                            .votes
+                            .values()
+                            .map(|vote| vote.blocknumber)
+                            .max()
+                            .unwrap_or(0)
+                    };
+                    proposals
+                        .sort_by_key(|output| std::cmp::Reverse((last_vote(output), output.id)));
+                } else {
+                    let policy = self.policy.get().unwrap().to_policy();
+                    let total_supply = self.cached_total_supply();
+                    proposals.sort_by_key(|output| {
+                        std::cmp::Reverse((
+                            policy.approval_progress(&output.proposal, total_supply),
+                            output.id,
+                        ))
+                    });
+                }
+                proposals
+                    .into_iter()
+                    .skip(from_index as usize)
+                    .take(limit as usize)
+                    .collect()
+            }
+        }
+    }
+
+    /// Get proposals with given tag, oldest first, in paginated view.
+    pub fn get_proposals_by_tag(
+        &self,
+        tag: String,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ProposalOutput> {
+        self.proposals_by_tag
+            .get(&tag)
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|id| self.get_proposal(id))
+            .collect()
+    }
+
+    /// Get proposals of the kind with given policy label, e.g. `transfer`, oldest first,
+    /// in paginated view.
+    pub fn get_proposals_by_kind(
+        &self,
+        label: String,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ProposalOutput> {
+        assert!(
+            PROPOSAL_KIND_LABELS
+                .iter()
+                .any(|(_, known)| *known == label),
+            "ERR_UNKNOWN_KIND_LABEL"
+        );
+        self.proposals_by_kind
+            .get(&label)
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|id| self.get_proposal(id))
+            .collect()
+    }
+
+    /// Get proposals submitted by given account, oldest first, in paginated view.
+    pub fn get_proposals_by_proposer(
+        &self,
+        account_id: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ProposalOutput> {
+        self.proposals_by_proposer
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|id| self.get_proposal(id))
+            .collect()
+    }
+
+    /// Get proposals in progress that expire within `within_blocks` blocks from now, among the
+    /// `limit` proposals starting from `from_index`. Deadlines are timestamps, so the window is
+    /// converted with `EXPECTED_BLOCK_TIME` and is only as precise as the block time.
+    pub fn get_expiring_soon(
+        &self,
+        from_index: u64,
+        within_blocks: u64,
+        limit: u64,
+    ) -> Vec<ProposalOutput> {
+        let policy = self.policy.get().unwrap().to_policy();
+        let now = env::block_timestamp();
+        let within = within_blocks.saturating_mul(EXPECTED_BLOCK_TIME);
+        (from_index..min(self.last_proposal_id, from_index.saturating_add(limit)))
             .filter_map(|id| {
                 self.proposals.get(&id).map(|proposal| ProposalOutput {
                     id,
                     proposal: proposal.into(),
                 })
             })
+            .filter(|output| {
+                let deadline = policy.proposal_deadline(&output.proposal);
+                output.proposal.status == ProposalStatus::InProgress
+                    && deadline >= now
+                    && deadline - now <= within
+            })
             .collect()
     }
 
+    /// Get proposals needing attention of given council member.
+    pub fn get_council_digest(&self, account_id: AccountId) -> CouncilDigest {
+        let policy = self.policy.get().unwrap().to_policy();
+        let now = env::block_timestamp();
+        let mut digest = CouncilDigest {
+            to_vote: vec![],
+            to_finalize: vec![],
+            suggestions: vec![],
+        };
+        for id in 0..self.last_proposal_id {
+            let proposal: Proposal = match self.proposals.get(&id) {
+                Some(proposal) => proposal.into(),
+                None => continue,
+            };
+            let in_progress = proposal.status == ProposalStatus::InProgress;
+            let expired = in_progress && policy.proposal_deadline(&proposal) < now;
+            let output = ProposalOutput { id, proposal };
+            if expired || output.proposal.status == ProposalStatus::Failed {
+                digest.to_finalize.push(output);
+            } else if !in_progress {
+                continue;
+            } else if matches!(output.proposal.kind, ProposalKind::Suggestion { .. }) {
+                digest.suggestions.push(output);
+            } else if !output.proposal.votes.contains_key(&account_id) {
+                digest.to_vote.push(output);
+            }
+        }
+        digest
+    }
+
     /// Get specific proposal.
     pub fn get_proposal(&self, id: u64) -> ProposalOutput {
         let proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL");
@@ -72,4 +519,138 @@ impl Contract {
             proposal: proposal.into(),
         }
     }
+
+    /// Get comments on given proposal in paginated view, oldest first.
+    pub fn get_comments(&self, id: u64, from_index: u64, limit: u64) -> Vec<Comment> {
+        match self.comments.get(&id) {
+            Some(comments) => (from_index..min(comments.len(), from_index + limit))
+                .filter_map(|index| comments.get(index))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Returns the approve weight of given proposal and the weight needed to pass it, for progress bars.
+    /// Returns zeros for proposals that are not in progress.
+    pub fn get_approval_progress(&self, id: u64) -> (U128, U128) {
+        let proposal = self.get_proposal(id).proposal;
+        if proposal.status != ProposalStatus::InProgress {
+            return (U128(0), U128(0));
+        }
+        let (approved, needed) = self
+            .policy
+            .get()
+            .unwrap()
+            .to_policy()
+            .approval_weights(&proposal, self.cached_total_supply());
+        (U128(approved), U128(needed))
+    }
+
+    /// Returns whether given account already voted on given proposal, including poll and split
+    /// votes. False if there is no such proposal.
+    pub fn has_voted(&self, id: u64, account_id: AccountId) -> bool {
+        self.proposals
+            .get(&id)
+            .map(|proposal| {
+                let proposal = Proposal::from(proposal);
+                proposal.votes.contains_key(&account_id)
+                    || proposal.poll_votes.contains_key(&account_id)
+                    || proposal.split_votes.contains_key(&account_id)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns the weight a vote of given account would add to the tallies of given proposal,
+    /// taking its roles, stake and lockup into account. A member of several roles adds its weight
+    /// in each of them, the largest one is returned. Zero if the account can't vote on it.
+    pub fn get_effective_weight(&self, id: u64, account_id: AccountId) -> U128 {
+        let proposal = self.get_proposal(id).proposal;
+        let policy = self.policy.get().unwrap().to_policy();
+        let user = self.internal_account_info(account_id.clone());
+        let stake = self.internal_proposal_stake(&account_id, &proposal, user.stake);
+        let (roles, allowed) =
+            policy.can_execute_action(user, &proposal.kind, &Action::VoteApprove);
+        if !allowed {
+            return U128(0);
+        }
+        let lockup = self.lockups.get(&account_id);
+        let weight = self
+            .internal_voting_roles(&account_id, roles)
+            .iter()
+            .map(|role| {
+                proposal.vote_weight(
+                    role,
+                    &policy,
+                    stake,
+                    lockup.as_ref(),
+                    self.cached_total_supply(),
+                )
+            })
+            .max()
+            .unwrap_or(0);
+        U128(weight)
+    }
+
+    /// Returns the timestamp after which given proposal is expired, taking the period of its kind
+    /// and any extension into account.
+    pub fn get_proposal_deadline(&self, id: u64) -> U64 {
+        let proposal = self.get_proposal(id).proposal;
+        U64(self
+            .policy
+            .get()
+            .unwrap()
+            .to_policy()
+            .proposal_deadline(&proposal))
+    }
+
+    /// Last fetched total supply of the governance token, if any.
+    pub fn get_total_supply(&self) -> Option<CachedTotalSupply> {
+        self.total_supply.clone()
+    }
+
+    /// Returns if `Finalize` action on given proposal would succeed, i.e. the proposal in progress
+    /// or failed resolves to approved, expired or rejected on a tie across all roles.
+    /// A failed proposal that is neither approved anymore nor expired can't be finalized yet.
+    pub fn can_finalize(&self, id: u64) -> bool {
+        let proposal = self.get_proposal(id).proposal;
+        if !matches!(
+            proposal.status,
+            ProposalStatus::InProgress | ProposalStatus::Failed
+        ) {
+            return false;
+        }
+        let policy = self.policy.get().unwrap().to_policy();
+        matches!(
+            policy.proposal_status(
+                &proposal,
+                policy.roles.iter().map(|r| r.name.clone()).collect(),
+                self.cached_total_supply(),
+                &self.config.get().unwrap().tie_break,
+            ),
+            ProposalStatus::Approved | ProposalStatus::Expired | ProposalStatus::Rejected
+        )
+    }
+
+    /// Get vote of given account on given proposal, if any.
+    pub fn get_vote(&self, id: u64, account_id: AccountId) -> Option<VoteWithTimestamp> {
+        self.get_proposal(id).proposal.votes.remove(&account_id)
+    }
+
+    /// Get votes on given proposal in the order they were cast, by block and then by account.
+    pub fn get_vote_timeline(&self, id: u64) -> Vec<(AccountId, Vote, BlockHeight)> {
+        let mut timeline: Vec<(AccountId, Vote, BlockHeight)> = self
+            .get_proposal(id)
+            .proposal
+            .votes
+            .into_iter()
+            .map(|(account_id, vote)| (account_id, vote.vote, vote.blocknumber))
+            .collect();
+        timeline.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| a.0.cmp(&b.0)));
+        timeline
+    }
+
+    /// Get all votes on given proposal.
+    pub fn get_votes(&self, id: u64) -> HashMap<AccountId, VoteWithTimestamp> {
+        self.get_proposal(id).proposal.votes
+    }
 }
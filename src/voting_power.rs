@@ -0,0 +1,175 @@
+//! Governance tokens locked in the DAO as voting power, for the `FtBalance` voting power source.
+//! Tokens are locked with `ft_transfer_call` and `LOCK_VOTING_POWER_MSG` as message. Each change
+//! of a locked balance is recorded with its block, and votes on a proposal count the balance
+//! locked before the block it was submitted at. Tokens moved to another account once a proposal
+//! is submitted can't vote on it again.
+
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_sdk::Gas;
+
+use crate::types::ONE_YOCTO_NEAR;
+use crate::*;
+
+/// Message of `ft_transfer_call` locking the transferred tokens as voting power of the sender.
+pub const LOCK_VOTING_POWER_MSG: &str = "lock_voting_power";
+
+/// Number of changes of a locked balance remembered. Proposals submitted before the oldest
+/// remembered change can't be voted on with that balance.
+pub const MAX_VOTING_BALANCE_HISTORY: usize = 10;
+
+/// Gas for the callback after unlocking, locking the tokens again on failure.
+const GAS_FOR_ON_UNLOCK: Gas = Gas(10_000_000_000_000);
+
+#[near_bindgen]
+impl Contract {
+    /// Returns `amount` of the tokens locked by the caller. Votes already cast keep their weight.
+    pub fn unlock_voting_balance(&mut self, amount: U128) -> Promise {
+        let token_id = self.internal_voting_token();
+        let account_id = env::predecessor_account_id();
+        let balance = self.internal_voting_balance(&token_id, &account_id);
+        assert!(amount.0 > 0 && amount.0 <= balance, "ERR_INVALID_AMOUNT");
+        self.internal_set_voting_balance(&token_id, &account_id, balance - amount.0);
+        let gas = self.config.get().unwrap().ft_transfer_gas(&token_id);
+        ext_fungible_token::ft_transfer(
+            account_id.clone(),
+            amount,
+            None,
+            token_id.clone(),
+            ONE_YOCTO_NEAR,
+            gas,
+        )
+        .then(ext_self::on_unlock_voting_balance(
+            token_id,
+            account_id,
+            amount,
+            env::current_account_id(),
+            0,
+            GAS_FOR_ON_UNLOCK,
+        ))
+    }
+
+    /// Locks the tokens again if returning them has failed.
+    #[private]
+    pub fn on_unlock_voting_balance(
+        &mut self,
+        token_id: AccountId,
+        account_id: AccountId,
+        amount: U128,
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "ERR_UNEXPECTED_CALLBACK_PROMISES"
+        );
+        if let PromiseResult::Failed = env::promise_result(0) {
+            self.internal_lock_voting_balance(&token_id, &account_id, amount.0);
+        }
+    }
+
+    /// Balance of given token currently locked by given account.
+    pub fn get_voting_balance(&self, token_id: AccountId, account_id: AccountId) -> U128 {
+        U128(self.internal_voting_balance(&token_id, &account_id))
+    }
+}
+
+impl Contract {
+    /// Token of the `FtBalance` voting power source.
+    fn internal_voting_token(&self) -> AccountId {
+        match self.config.get().unwrap().voting_power_source {
+            VotingPowerSource::FtBalance(token_id) => token_id,
+            _ => env::panic_str("ERR_NOT_FT_VOTING_POWER"),
+        }
+    }
+
+    /// Adds `amount` to the tokens locked by given account, if given token is the one of the
+    /// `FtBalance` voting power source.
+    pub(crate) fn internal_lock_voting_balance(
+        &mut self,
+        token_id: &AccountId,
+        account_id: &AccountId,
+        amount: Balance,
+    ) {
+        assert_eq!(
+            token_id,
+            &self.internal_voting_token(),
+            "ERR_NOT_VOTING_TOKEN"
+        );
+        let balance = self.internal_voting_balance(token_id, account_id);
+        self.internal_set_voting_balance(token_id, account_id, balance + amount);
+    }
+
+    /// Balance of given token currently locked by given account.
+    pub(crate) fn internal_voting_balance(
+        &self,
+        token_id: &AccountId,
+        account_id: &AccountId,
+    ) -> Balance {
+        self.voting_balances
+            .get(&(token_id.clone(), account_id.clone()))
+            .and_then(|history| history.last().map(|(_, balance)| *balance))
+            .unwrap_or(0)
+    }
+
+    /// Balance of given token locked by given account before given block.
+    fn internal_voting_balance_before(
+        &self,
+        token_id: &AccountId,
+        account_id: &AccountId,
+        block: BlockHeight,
+    ) -> Balance {
+        self.voting_balances
+            .get(&(token_id.clone(), account_id.clone()))
+            .and_then(|history| {
+                history
+                    .iter()
+                    .rev()
+                    .find(|(changed_at, _)| *changed_at < block)
+                    .map(|(_, balance)| *balance)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Records the new balance locked by given account at the current block, dropping the oldest
+    /// change past `MAX_VOTING_BALANCE_HISTORY`.
+    fn internal_set_voting_balance(
+        &mut self,
+        token_id: &AccountId,
+        account_id: &AccountId,
+        balance: Balance,
+    ) {
+        let key = (token_id.clone(), account_id.clone());
+        let mut history = self.voting_balances.get(&key).unwrap_or_default();
+        let block = env::block_height();
+        if history
+            .last()
+            .map(|(changed_at, _)| *changed_at == block)
+            .unwrap_or(false)
+        {
+            history.pop();
+        }
+        history.push((block, balance));
+        if history.len() > MAX_VOTING_BALANCE_HISTORY {
+            history.remove(0);
+        }
+        self.voting_balances.insert(&key, &history);
+    }
+
+    /// Stake of given account for voting on given proposal, `stake` being its current stake.
+    /// Tokens locked for the `FtBalance` source count as they were before the proposal was
+    /// submitted.
+    pub(crate) fn internal_proposal_stake(
+        &self,
+        account_id: &AccountId,
+        proposal: &Proposal,
+        stake: Balance,
+    ) -> Balance {
+        match self.config.get().unwrap().voting_power_source {
+            VotingPowerSource::FtBalance(token_id) => self.internal_voting_balance_before(
+                &token_id,
+                account_id,
+                proposal.submission_block,
+            ),
+            _ => stake,
+        }
+    }
+}